@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+
+/// The outline formed by a page's `h1`–`h6` elements.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Headings {
+    /// Heading levels, in document order
+    pub sequence: Vec<u32>,
+    pub h1s: u64,
+    /// Number of times a heading is more than one level deeper than the one before,
+    /// e.g. an `h4` directly following an `h2`
+    pub skipped_levels: u64,
+    /// Whether the outline has any of the issues above
+    pub issues: bool,
+}
+impl Headings {
+    #[must_use]
+    pub fn from_sequence(sequence: Vec<u32>) -> Self {
+        let h1s = sequence.iter().filter(|&&l| l == 1).count() as u64;
+        // the first heading is measured against an implicit level 0, i.e. it should be an `h1`
+        let skipped_levels = std::iter::once(0)
+            .chain(sequence.iter().copied())
+            .zip(&sequence)
+            .filter(|&(prev, &next)| next > prev + 1)
+            .count() as u64;
+
+        Self {
+            issues: h1s != 1 || skipped_levels > 0,
+            sequence,
+            h1s,
+            skipped_levels,
+        }
+    }
+}
+
+/// Counts images with and without alt text, and media elements with and without text tracks.
+const MEDIA_SCRIPT: &str = r#"
+const images = document.querySelectorAll("img");
+const videos = document.querySelectorAll("video");
+const audios = document.querySelectorAll("audio");
+const captioned = e => e.querySelector('track[kind="captions"], track[kind="subtitles"]') !== null;
+return {
+    images: images.length,
+    images_with_alt: Array.from(images).filter(e => e.alt.trim() !== "").length,
+    videos: videos.length,
+    videos_with_captions: Array.from(videos).filter(captioned).length,
+    audios: audios.length,
+    audios_with_tracks: Array.from(audios).filter(e => e.querySelector("track") !== null).length,
+};
+"#;
+
+/// How well a page's images and media are described for people who can't see or hear them.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MediaAlternatives {
+    pub images: u64,
+    /// Images with a non-empty `alt` attribute
+    pub images_with_alt: u64,
+    pub videos: u64,
+    /// Videos with a `captions` or `subtitles` track
+    pub videos_with_captions: u64,
+    pub audios: u64,
+    /// Audio elements with any text track
+    pub audios_with_tracks: u64,
+    /// Share of images with alt text, or `None` if there are no images
+    #[serde(default)]
+    pub alt_coverage: Option<f64>,
+}
+
+/// Measures the alt text and caption coverage of the currently loaded page.
+#[allow(clippy::cast_precision_loss)]
+pub async fn media_alternatives(client: &Client) -> Result<MediaAlternatives> {
+    let v = client.execute(MEDIA_SCRIPT, vec![]).await?;
+    let mut media: MediaAlternatives = serde_json::from_value(v)?;
+    media.alt_coverage =
+        (media.images > 0).then(|| media.images_with_alt as f64 / media.images as f64);
+    Ok(media)
+}
+
+/// Counts explicit ARIA roles, labels and other attributes, and landmarks both explicit and implicit.
+const ARIA_SCRIPT: &str = r#"
+const LANDMARKS = ["banner", "complementary", "contentinfo", "form", "main", "navigation", "region", "search"];
+const IMPLICIT = { header: "banner", aside: "complementary", footer: "contentinfo", main: "main", nav: "navigation" };
+
+const roles = {}, landmarks = {};
+let labelled = 0, attributes = 0;
+for (const e of document.querySelectorAll("*")) {
+    const role = e.getAttribute("role")?.trim().split(/\s+/)[0];
+    if (role) roles[role] = (roles[role] || 0) + 1;
+
+    const landmark = LANDMARKS.includes(role) ? role : IMPLICIT[e.localName];
+    if (landmark) landmarks[landmark] = (landmarks[landmark] || 0) + 1;
+
+    if (e.hasAttribute("aria-label") || e.hasAttribute("aria-labelledby")) labelled++;
+    if (e.getAttributeNames().some(a => a.startsWith("aria-"))) attributes++;
+}
+return { roles, landmarks, labelled, attributes };
+"#;
+
+/// Usage of ARIA, the markup that makes pages understandable to assistive technology.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Aria {
+    /// Explicit `role` attributes, by role
+    pub roles: BTreeMap<String, u64>,
+    /// Landmarks, by role, whether given by a `role` attribute or implied by the tag
+    pub landmarks: BTreeMap<String, u64>,
+    /// Elements with an `aria-label` or `aria-labelledby` attribute
+    pub labelled: u64,
+    /// Elements with any `aria-*` attribute
+    pub attributes: u64,
+}
+
+/// Counts the ARIA roles, landmarks and attributes on the currently loaded page.
+pub async fn aria(client: &Client) -> Result<Aria> {
+    let v = client.execute(ARIA_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
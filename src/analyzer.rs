@@ -0,0 +1,348 @@
+//! What's done with each element of a page, as a pipeline of [`Analyzer`]s.
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use eyre::Result;
+use serde::Deserialize;
+use strum::{Display, EnumString};
+use tracing::*;
+
+use crate::{
+    accessibility::Headings,
+    custom,
+    domains::Domains,
+    foreign,
+    geometry::Geometry,
+    obsolete,
+    state::{Freq, Output, SiteRecord},
+    topk::TopK,
+    vocabulary::Tag,
+};
+
+/// An element of the page being analyzed.
+#[derive(Clone, Copy, Debug)]
+pub struct PageContext<'a> {
+    /// The element's tag name
+    pub name: &'a str,
+    /// The element's tag, if it's in the vocabulary
+    pub tag: Option<Tag>,
+    /// The element's bounding rectangle as `(x, y, width, height)` relative to the viewport,
+    /// if any analyzer [asked for it](Analyzer::wants_rectangle) and the engine has layout information
+    pub rectangle: Option<(f64, f64, f64, f64)>,
+}
+
+/// The tag names on a page, for analyzers that look at the whole page at once.
+#[derive(Clone, Debug, Default)]
+pub struct PageTags {
+    /// The number of elements analyzed
+    pub elements: u64,
+    /// How often each tag name appears, including ones missing from the vocabulary
+    pub tags: HashMap<String, u64>,
+}
+impl PageTags {
+    pub fn add(&mut self, name: &str) {
+        *self.tags.entry(name.to_owned()).or_default() += 1;
+        self.elements += 1;
+    }
+}
+
+/// Looks at every element of every page a crawler visits.
+///
+/// Each crawler has its own analyzers, so they're free to keep track of the current page,
+/// as long as they add it to the [`Output`] in [`finalize`](Self::finalize).
+#[async_trait]
+pub trait Analyzer: Send + Sync {
+    /// Whether the element's rectangle is needed, which costs a round trip to the browser.
+    fn wants_rectangle(&self, _name: &str, _tag: Option<Tag>) -> bool {
+        false
+    }
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()>;
+    /// Called once every element of the page has been accepted, with the site's record to add to.
+    ///
+    /// Failing doesn't fail the page, which the other analyzers have counted by then, so
+    /// anything added to the [`Output`] should only be added once nothing else can fail.
+    async fn finalize(&mut self, _record: &mut SiteRecord) -> Result<()> {
+        Ok(())
+    }
+    /// Called instead of [`finalize`](Self::finalize) when the page is given up on halfway
+    /// through, to forget what was seen of it so that it isn't counted along with the next one.
+    fn reset(&mut self);
+}
+
+/// The analyzers that come with the crawler.
+#[derive(EnumString, Display, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Builtin {
+    /// Counts each tag, in total and per domain
+    Tags,
+    /// Records where the [configured tags](crate::config::Config::geometry_tags) are, and how large
+    Geometry,
+    /// Counts the tag names missing from the vocabulary, other than custom, SVG, MathML and
+    /// obsolete elements, which are counted separately
+    UnknownTags,
+    /// Audits the outline formed by each page's headings
+    Accessibility,
+}
+impl Builtin {
+    /// All of them, in the order they run by default.
+    pub const ALL: [Self; 4] = [
+        Self::Tags,
+        Self::Geometry,
+        Self::UnknownTags,
+        Self::Accessibility,
+    ];
+
+    fn make(self, output: &Output) -> Box<dyn Analyzer> {
+        match self {
+            Self::Tags => Box::new(TagFrequency {
+                freq: output.freq.clone(),
+                domains: output.domains.clone(),
+                page_freq: vec![0; output.vocabulary.len()],
+            }),
+            Self::Geometry => Box::new(ElementGeometry {
+                geometry: output.geometry.clone(),
+                page: Vec::new(),
+            }),
+            Self::UnknownTags => Box::new(UnknownTags {
+                top: output.unknown_tags.clone(),
+                page: HashMap::new(),
+            }),
+            Self::Accessibility => Box::new(HeadingOutline { page: Vec::new() }),
+        }
+    }
+}
+
+type Factory = Arc<dyn Fn(&Output) -> Result<Box<dyn Analyzer>> + Send + Sync>;
+
+/// Which analyzers each crawler gets a fresh set of, in order.
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    builtins: Vec<Builtin>,
+    custom: Vec<Factory>,
+}
+impl Pipeline {
+    #[must_use]
+    pub fn new(builtins: impl IntoIterator<Item = Builtin>) -> Self {
+        Self {
+            builtins: builtins.into_iter().collect(),
+            custom: Vec::new(),
+        }
+    }
+
+    /// Adds an analyzer of your own, made for each crawler from the output it reports to.
+    #[must_use]
+    pub fn with(
+        mut self,
+        make: impl Fn(&Output) -> Result<Box<dyn Analyzer>> + Send + Sync + 'static,
+    ) -> Self {
+        self.custom.push(Arc::new(make));
+        self
+    }
+
+    /// A fresh set of analyzers, reporting to `output`.
+    pub fn build(&self, output: &Output) -> Result<Vec<Box<dyn Analyzer>>> {
+        self.builtins
+            .iter()
+            .map(|builtin| Ok(builtin.make(output)))
+            .chain(self.custom.iter().map(|make| make(output)))
+            .collect()
+    }
+}
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("builtins", &self.builtins)
+            .field("custom", &self.custom.len())
+            .finish()
+    }
+}
+
+/// See [`Builtin::Tags`].
+///
+/// The counts are kept to the page until it's done, and then added to the totals all at once.
+struct TagFrequency {
+    freq: Freq,
+    domains: Domains,
+    /// Tag counts of the current page, indexed by [`Tag::index`]
+    page_freq: Vec<u64>,
+}
+#[async_trait]
+impl Analyzer for TagFrequency {
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        if let Some(tag) = page_ctx.tag {
+            self.page_freq[tag.index()] += 1;
+        }
+        Ok(())
+    }
+    async fn finalize(&mut self, record: &mut SiteRecord) -> Result<()> {
+        self.freq.add(&self.page_freq);
+        record.counted = self.page_freq.iter().sum();
+        if let Some(domain) = &record.domain {
+            self.domains.add(domain, &self.page_freq).await;
+        }
+        record.tag_counts.clone_from(&self.page_freq);
+        self.reset();
+        Ok(())
+    }
+    fn reset(&mut self) {
+        self.page_freq.fill(0);
+    }
+}
+
+/// See [`Builtin::Geometry`].
+struct ElementGeometry {
+    geometry: Geometry,
+    /// The rectangles found on the current page
+    page: Vec<(Tag, (f64, f64, f64, f64))>,
+}
+#[async_trait]
+impl Analyzer for ElementGeometry {
+    fn wants_rectangle(&self, _name: &str, tag: Option<Tag>) -> bool {
+        tag.is_some_and(|tag| self.geometry.tracks(tag))
+    }
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        let (Some(tag), Some(rect)) = (page_ctx.tag, page_ctx.rectangle) else {
+            return Ok(());
+        };
+        trace!(
+            name = page_ctx.name,
+            "Found element at ({:.2}, {:.2}) {:.2} x {:.2}",
+            rect.0,
+            rect.1,
+            rect.2,
+            rect.3
+        );
+        self.page.push((tag, rect));
+        Ok(())
+    }
+    async fn finalize(&mut self, _record: &mut SiteRecord) -> Result<()> {
+        self.geometry.add_all(self.page.drain(..)).await;
+        Ok(())
+    }
+    fn reset(&mut self) {
+        self.page.clear();
+    }
+}
+
+/// See [`Builtin::UnknownTags`].
+struct UnknownTags {
+    top: TopK,
+    /// How often each unknown tag name appears on the current page
+    page: HashMap<String, u64>,
+}
+#[async_trait]
+impl Analyzer for UnknownTags {
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        // these are all counted per page, separately
+        let name = page_ctx.name;
+        let counted =
+            custom::is_custom(name) || foreign::is_foreign(name) || obsolete::is_obsolete(name);
+        if page_ctx.tag.is_none() && !counted {
+            debug!(name, "Found unrecognized tag");
+            *self.page.entry(page_ctx.name.to_owned()).or_default() += 1;
+        }
+        Ok(())
+    }
+    async fn finalize(&mut self, _record: &mut SiteRecord) -> Result<()> {
+        self.top.add_all(self.page.drain()).await;
+        Ok(())
+    }
+    fn reset(&mut self) {
+        self.page.clear();
+    }
+}
+
+/// See [`Builtin::Accessibility`].
+///
+/// Elements are handed over in document order, so the outline comes out in order too.
+/// Pages with too many elements get none, since a sample of their headings would seem to
+/// skip levels, or lack an `h1`, where the page doesn't.
+struct HeadingOutline {
+    /// Heading levels of the current page, in document order
+    page: Vec<u32>,
+}
+#[async_trait]
+impl Analyzer for HeadingOutline {
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        let level = match page_ctx.name {
+            "h1" => 1,
+            "h2" => 2,
+            "h3" => 3,
+            "h4" => 4,
+            "h5" => 5,
+            "h6" => 6,
+            _ => return Ok(()),
+        };
+        self.page.push(level);
+        Ok(())
+    }
+    async fn finalize(&mut self, record: &mut SiteRecord) -> Result<()> {
+        let sequence = std::mem::take(&mut self.page);
+        record.headings = (!record.truncated).then(|| Headings::from_sequence(sequence));
+        Ok(())
+    }
+    fn reset(&mut self) {
+        self.page.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn leaves_elements_counted_elsewhere_out_of_the_unknown_tags() {
+        let (records_tx, _) = mpsc::unbounded_channel();
+        let output = Output::load(records_tx, &Config::default()).await.unwrap();
+        let mut analyzer = Builtin::UnknownTags.make(&output);
+        for name in [
+            "my-widget",
+            "path",
+            "linearGradient",
+            "mrow",
+            "marquee",
+            "center",
+            "blah",
+        ] {
+            let page_ctx = PageContext {
+                name,
+                tag: output.vocabulary.get(name),
+                rectangle: None,
+            };
+            analyzer.accept(&page_ctx).await.unwrap();
+        }
+        analyzer.finalize(&mut SiteRecord::default()).await.unwrap();
+
+        let top = output.unknown_tags.top().await;
+        let names: Vec<_> = top.iter().map(|item| &*item.name).collect();
+        assert_eq!(names, ["blah"]);
+    }
+
+    #[tokio::test]
+    async fn leaves_out_the_outline_of_sampled_pages() {
+        let (records_tx, _) = mpsc::unbounded_channel();
+        let output = Output::load(records_tx, &Config::default()).await.unwrap();
+        let mut analyzer = Builtin::Accessibility.make(&output);
+        for truncated in [false, true] {
+            for name in ["h1", "h2"] {
+                let page_ctx = PageContext {
+                    name,
+                    tag: output.vocabulary.get(name),
+                    rectangle: None,
+                };
+                analyzer.accept(&page_ctx).await.unwrap();
+            }
+            let mut record = SiteRecord {
+                truncated,
+                ..SiteRecord::default()
+            };
+            analyzer.finalize(&mut record).await.unwrap();
+            assert_eq!(record.headings.is_some(), !truncated);
+        }
+    }
+}
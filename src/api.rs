@@ -0,0 +1,474 @@
+//! A small HTTP API for keeping an eye on headless crawls, and stopping them.
+//!
+//! - `GET /progress`: how many sites are done, out of how many, in which run
+//! - `GET /crawlers`: what each crawler is up to
+//! - `GET /utilization`: how full the queue of sites is, and where the crawlers' time went
+//! - `GET /histogram`: the tag and category counts so far
+//! - `POST /shutdown`: shuts down gracefully, as on `SIGTERM`
+//! - `GET /events`: a WebSocket streaming a message as each site is done, and the changes
+//!   to the tag counts every second, the first time from zero
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::StatusCode,
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use eyre::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc,
+};
+use tracing::*;
+use ulid::Ulid;
+
+use crate::{
+    crawler::{Dashboard, QueueDepth},
+    report::Census,
+    state::{Output, SiteRecord},
+    util::Port,
+    ShutdownRx,
+};
+
+/// How often the changes to the tag counts are streamed
+const HISTOGRAM_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What's streamed over the `/events` WebSocket.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    /// A site is done
+    Site { record: &'a SiteRecord },
+    /// How much each tag's count went up since the last time
+    Histogram { delta: BTreeMap<String, u64> },
+    /// The client fell behind, and missed this many sites
+    Lagged { missed: u64 },
+}
+
+/// How far along the crawl is, also reported over the [control socket](crate::ctl).
+#[derive(Serialize)]
+pub(crate) struct Progress {
+    run: Option<Ulid>,
+    /// `running`, `paused`, or `shutting_down` once asked to
+    state: &'static str,
+    elapsed_secs: u64,
+    crawled: usize,
+    total: usize,
+    active_crawlers: usize,
+    respawned: usize,
+    gave_up: usize,
+}
+impl Progress {
+    fn new(crawl: &Crawl) -> Self {
+        let dashboard = crawl.dashboard.get();
+        // nobody marks the change as seen, so it stays changed once the shutdown is sent,
+        // and errors once the senders are gone after everything's done
+        let shutting_down = crawl.shutdown_rx.has_changed().unwrap_or(true);
+        Self {
+            run: crawl.output.run,
+            state: if shutting_down {
+                "shutting_down"
+            } else if dashboard.paused {
+                "paused"
+            } else {
+                "running"
+            },
+            elapsed_secs: crawl.started.elapsed().as_secs(),
+            crawled: dashboard.crawled(),
+            total: crawl.total_sites,
+            active_crawlers: dashboard.crawlers.len(),
+            respawned: dashboard.respawns,
+            gave_up: dashboard.given_up,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Crawler {
+    id: Port,
+    state: String,
+}
+
+/// The crawlers' time in seconds, added up across crawlers since the start, so that clients
+/// can work out the rates between two requests.
+#[derive(Serialize)]
+struct Utilization {
+    queue: Option<QueueDepth>,
+    waiting_secs: f64,
+    navigating_secs: f64,
+    walking_secs: f64,
+    other_secs: f64,
+    /// The part of the time spent crawling rather than waiting for sites, from 0 to 1
+    busy: f64,
+}
+
+/// A running crawl, as reported on and stopped by both the status API and the
+/// [control socket](crate::ctl).
+#[derive(Clone)]
+pub struct Crawl {
+    pub(crate) output: Output,
+    pub(crate) dashboard: Dashboard,
+    total_sites: usize,
+    started: Instant,
+    shutdown_rx: ShutdownRx,
+    stop_tx: mpsc::UnboundedSender<&'static str>,
+}
+impl Crawl {
+    /// The crawl producing `output`, passing requests to shut down on to `stop_tx`,
+    /// e.g. a [frontend](crate::tui::App::with_stop_requests).
+    #[must_use]
+    pub fn new(
+        output: Output,
+        dashboard: Dashboard,
+        total_sites: usize,
+        shutdown_rx: ShutdownRx,
+        stop_tx: mpsc::UnboundedSender<&'static str>,
+    ) -> Self {
+        Self {
+            output,
+            dashboard,
+            total_sites,
+            started: Instant::now(),
+            shutdown_rx,
+            stop_tx,
+        }
+    }
+
+    pub(crate) fn progress(&self) -> Progress {
+        Progress::new(self)
+    }
+
+    /// Asks for a graceful shutdown, as on `SIGTERM`, saying where the request came from.
+    pub(crate) fn stop(&self, via: &'static str) {
+        // fails only once the frontend is gone, when there's nothing left to stop
+        let _ = self.stop_tx.send(via);
+    }
+}
+
+/// Serves the status of a running crawl.
+pub struct Api {
+    crawl: Crawl,
+    /// Announces every site as it's done, see [`sink::tee`](crate::sink::tee)
+    sites: Option<broadcast::Sender<Arc<SiteRecord>>>,
+}
+impl Api {
+    /// Reports on `crawl`, which it can also stop.
+    #[must_use]
+    pub fn new(crawl: Crawl) -> Self {
+        Self { crawl, sites: None }
+    }
+
+    /// Streams the sites announced here as they're done.
+    #[must_use]
+    pub fn with_sites(mut self, sites: broadcast::Sender<Arc<SiteRecord>>) -> Self {
+        self.sites = Some(sites);
+        self
+    }
+
+    /// Starts listening right away, returning the server to run.
+    pub fn serve(self, addr: SocketAddr) -> Result<impl Future<Output = hyper::Result<()>>> {
+        let server = axum::Server::try_bind(&addr)?.serve(self.router().into_make_service());
+        info!(addr = %server.local_addr(), "Status API listening");
+        Ok(server)
+    }
+
+    /// The routes of the API, for serving or trying out.
+    pub fn router(self) -> Router {
+        Router::new()
+            .route(
+                "/progress",
+                get(|State(api): State<Arc<Self>>| async move { Json(api.crawl.progress()) }),
+            )
+            .route(
+                "/crawlers",
+                get(|State(api): State<Arc<Self>>| async move { Json(api.crawlers()) }),
+            )
+            .route(
+                "/utilization",
+                get(|State(api): State<Arc<Self>>| async move { Json(api.utilization()) }),
+            )
+            .route(
+                "/histogram",
+                get(|State(api): State<Arc<Self>>| async move { Json(Census::of(&api.crawl.output)) }),
+            )
+            .route("/shutdown", post(shutdown))
+            .route("/events", get(events))
+            .with_state(Arc::new(self))
+    }
+
+    /// Streams events over the WebSocket until either side closes it.
+    async fn stream(&self, socket: WebSocket) -> Result<()> {
+        let (mut writer, mut reader) = socket.split();
+        // pings are answered as they're read, and nothing else the client says matters
+        let (closed_tx, mut closed_rx) = tokio::sync::oneshot::channel::<()>();
+        let reading = tokio::spawn(async move {
+            while let Some(Ok(message)) = reader.next().await {
+                if matches!(message, Message::Close(_)) {
+                    break;
+                }
+            }
+            drop(closed_tx);
+        });
+
+        let mut sites = match &self.sites {
+            Some(sites) => sites.subscribe(),
+            // nothing will ever be sent
+            None => broadcast::channel(1).1,
+        };
+        let mut ticker = tokio::time::interval(HISTOGRAM_INTERVAL);
+        let mut last: BTreeMap<String, u64> = BTreeMap::new();
+
+        let res = loop {
+            let event = tokio::select! {
+                _ = &mut closed_rx => break Ok(()),
+                site = sites.recv() => match site {
+                    Ok(record) => serde_json::to_string(&Event::Site { record: &record })?,
+                    Err(RecvError::Lagged(missed)) => serde_json::to_string(&Event::Lagged { missed })?,
+                    // kept open so that the histogram keeps coming
+                    Err(RecvError::Closed) => {
+                        sites = broadcast::channel(1).1;
+                        continue;
+                    }
+                },
+                _ = ticker.tick() => {
+                    let now = Census::of(&self.crawl.output).freq;
+                    let delta: BTreeMap<_, _> = now
+                        .iter()
+                        .map(|(tag, &count)| (tag, count - last.get(tag).copied().unwrap_or(0)))
+                        .filter(|&(_, delta)| delta > 0)
+                        .map(|(tag, delta)| (tag.clone(), delta))
+                        .collect();
+                    last = now;
+                    if delta.is_empty() {
+                        continue;
+                    }
+                    serde_json::to_string(&Event::Histogram { delta })?
+                }
+            };
+            if let Err(e) = writer.send(Message::Text(event)).await {
+                break Err(e);
+            }
+        };
+
+        reading.abort();
+        let _ = writer.close().await;
+        res.wrap_err("Failed to stream events")
+    }
+
+    fn utilization(&self) -> Utilization {
+        let dashboard = self.crawl.dashboard.get();
+        let time = dashboard.utilization;
+        Utilization {
+            queue: dashboard.queue,
+            waiting_secs: time.waiting.as_secs_f64(),
+            navigating_secs: time.navigating.as_secs_f64(),
+            walking_secs: time.walking.as_secs_f64(),
+            other_secs: time.other.as_secs_f64(),
+            busy: time.share(time.busy()),
+        }
+    }
+
+    fn crawlers(&self) -> Vec<Crawler> {
+        let dashboard = self.crawl.dashboard.get();
+        dashboard
+            .crawlers
+            .into_iter()
+            .map(|(id, state)| Crawler {
+                id,
+                state: state.to_string(),
+            })
+            .collect()
+    }
+}
+
+async fn shutdown(State(api): State<Arc<Api>>) -> StatusCode {
+    info!("Shutdown requested through the status API");
+    api.crawl.stop("API request");
+    StatusCode::ACCEPTED
+}
+
+/// Switches the connection over to a WebSocket, and starts streaming events over it.
+async fn events(State(api): State<Arc<Api>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(|socket| async move {
+        if let Err(e) = api.stream(socket).await {
+            debug!(%e, "WebSocket closed");
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, Request};
+    use hyper::Body;
+    use tokio::sync::watch;
+    use tokio_tungstenite::tungstenite;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::Config;
+
+    struct Running {
+        router: Router,
+        stop_rx: mpsc::UnboundedReceiver<&'static str>,
+        /// Dropping it would look like the crawl is shutting down
+        _shutdown_tx: watch::Sender<()>,
+    }
+
+    async fn api() -> Running {
+        api_with_sites(broadcast::channel(16).0).await
+    }
+
+    async fn api_with_sites(sites: broadcast::Sender<Arc<SiteRecord>>) -> Running {
+        let (records_tx, _) = mpsc::unbounded_channel();
+        let output = Output::load(records_tx, &Config::default()).await.unwrap();
+        let (stop_tx, stop_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        let crawl = Crawl::new(output, Dashboard::default(), 10, shutdown_rx, stop_tx);
+        let api = Api::new(crawl).with_sites(sites);
+        Running {
+            router: api.router(),
+            stop_rx,
+            _shutdown_tx: shutdown_tx,
+        }
+    }
+
+    async fn send(router: Router, method: &str, path: &str) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        let res = router.oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn reports_progress() {
+        let api = api().await;
+        let (status, body) = send(api.router, "GET", "/progress").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["state"], "running");
+        assert_eq!(body["crawled"], 0);
+        assert_eq!(body["total"], 10);
+    }
+
+    #[tokio::test]
+    async fn reports_crawlers_utilization_and_histogram() {
+        let router = api().await.router;
+        let (status, body) = send(router.clone(), "GET", "/crawlers").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!([]));
+
+        let (status, body) = send(router.clone(), "GET", "/utilization").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["waiting_secs"], 0.0);
+
+        let (status, body) = send(router, "GET", "/histogram").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["freq"]["div"], 0);
+    }
+
+    #[tokio::test]
+    async fn passes_on_shutdown_requests() {
+        let mut api = api().await;
+        let (status, _) = send(api.router, "POST", "/shutdown").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(api.stop_rx.try_recv(), Ok("API request"));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_routes_and_methods() {
+        let mut api = api().await;
+        let (status, _) = send(api.router.clone(), "GET", "/nowhere").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(api.router, "GET", "/shutdown").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert!(api.stop_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_websocket_handshakes_without_a_key() {
+        let req = Request::builder()
+            .uri("/events")
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_VERSION, "13")
+            .body(Body::empty())
+            .unwrap();
+        let res = api().await.router.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn streams_sites_whatever_the_client_sends() {
+        let (sites_tx, _) = broadcast::channel(16);
+        let api = api_with_sites(sites_tx.clone()).await;
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(api.router.into_make_service());
+        let server = tokio::spawn(server);
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/events"))
+            .await
+            .unwrap();
+        // longer than any control frame
+        let chatter = "x".repeat(1000);
+        socket
+            .send(tungstenite::Message::Text(chatter.clone()))
+            .await
+            .unwrap();
+        socket
+            .send(tungstenite::Message::Ping(b"still there?".to_vec()))
+            .await
+            .unwrap();
+
+        // the subscription only starts once the handshake is done
+        let record = Arc::new(SiteRecord::new("https://example.com/".to_owned()));
+        while sites_tx.receiver_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        sites_tx.send(record).unwrap();
+
+        let (mut ponged, mut streamed) = (false, false);
+        while !(ponged && streamed) {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("the socket went quiet")
+                .unwrap()
+                .unwrap();
+            match message {
+                tungstenite::Message::Pong(payload) => {
+                    assert_eq!(payload, b"still there?");
+                    ponged = true;
+                }
+                tungstenite::Message::Text(text) => {
+                    let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if event["event"] == "site" {
+                        assert_eq!(event["record"]["url"], "https://example.com/");
+                        streamed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        server.abort();
+    }
+}
@@ -1,13 +1,23 @@
-use std::path::Path;
+use std::{collections::HashSet, path::Path, sync::atomic::Ordering};
 
 use eyre::{Context, ContextCompat, Result};
+use tokio::time::Instant;
 use tokio::{
     fs::File,
     io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader, Lines},
 };
+use tracing::{debug, error, info};
 use url::Url;
 
-use crate::{JobQueue, ShutdownRx};
+use crate::{
+    coordinator::RemoteSites,
+    crawler::Dashboard,
+    frontier::Visited,
+    resolve::{Resolution, Resolver},
+    state::{Output, SiteRecord},
+    util::{until, JobQueue},
+    ShutdownRx,
+};
 
 async fn read_largest_index(f: &mut BufReader<File>) -> Result<usize> {
     // TODO: make this work for not just specifically engineered input
@@ -15,27 +25,78 @@ async fn read_largest_index(f: &mut BufReader<File>) -> Result<usize> {
     if f.seek(std::io::SeekFrom::End(-128)).await.is_err() {
         f.rewind().await?;
     }
-    dbg!(f.read_to_end(&mut v).await?);
+    f.read_to_end(&mut v).await?;
 
     let mut iter = v.iter();
-    let first_lb = iter.by_ref().rposition(|&x| x == b'\n').unwrap();
-    let second_lb = iter.rposition(|&x| x == b'\n').unwrap();
+    let first_lb = iter
+        .by_ref()
+        .rposition(|&x| x == b'\n')
+        .wrap_err("Expected input to end with a line break")?;
+    let second_lb = iter
+        .rposition(|&x| x == b'\n')
+        .wrap_err("Expected input to have a line before the last")?;
 
     let s = std::str::from_utf8(&v[second_lb + 1..first_lb])
         .wrap_err("Expected last line of input to be UTF-8")?;
     let (idx, _) = s
         .split_once(',')
         .wrap_err("Expected last line of input to be comma-separated")?;
-    let idx = usize::from_str_radix(idx, 10)
+    let idx = idx
+        .parse()
         .wrap_err("Expected first entry of last line of input to be a numeric index")?;
 
     f.rewind().await?;
     Ok(idx)
 }
 
+/// Turns a line of the sites list, like `1,example.com`, into the URL to crawl,
+/// over HTTPS unless the line says otherwise, like `2,http://localhost:8080`.
+pub fn parse_line(line: &str) -> Result<Url> {
+    let (_, domain) = line
+        .split_once(',')
+        .wrap_err("Expected an index and a domain separated by a comma")?;
+    if domain.contains("://") {
+        return Ok(Url::parse(domain)?);
+    }
+    Ok(Url::parse(&format!("https://{domain}"))?)
+}
+
+/// Where the sites to crawl come from.
+enum Source {
+    /// A list of sites on disk
+    File(Lines<BufReader<File>>),
+    /// A coordinator handing out sites to many workers
+    Coordinator(RemoteSites),
+}
+impl Source {
+    async fn next(&mut self) -> Result<Option<Url>> {
+        match self {
+            Self::File(lines) => match lines.next_line().await? {
+                Some(line) => parse_line(&line).map(Some),
+                None => Ok(None),
+            },
+            Self::Coordinator(sites) => sites.next().await,
+        }
+    }
+}
+
 pub struct Assigner {
-    source: Lines<BufReader<File>>,
+    source: Source,
     queue: JobQueue,
+    /// Sites that were already crawled, as their URLs
+    skip: HashSet<String>,
+    /// Sites already assigned, to leave out ones listed more than once
+    visited: Option<Visited>,
+    /// Looks up the domains of upcoming sites, to leave out the ones that don't resolve
+    resolver: Option<Resolver>,
+    /// Where the sites left out for not resolving are recorded, and counted as done
+    left_out: Option<(Output, Dashboard)>,
+    /// Whether the source has run out of sites
+    exhausted: bool,
+    /// How many sites to assign at most
+    limit: Option<usize>,
+    /// When to stop assigning sites
+    deadline: Option<Instant>,
 }
 impl Assigner {
     pub async fn new(source: &Path, queue: JobQueue) -> Result<(Self, usize)> {
@@ -43,30 +104,175 @@ impl Assigner {
         let sites_count = read_largest_index(&mut source).await?;
 
         Ok((
-            Self {
-                source: source.lines(),
-                queue,
-            },
+            Self::with_source(Source::File(source.lines()), queue),
             sites_count,
         ))
     }
 
+    /// Assigns the sites a coordinator hands out, rather than ones from a list.
+    #[must_use]
+    pub fn remote(sites: RemoteSites, queue: JobQueue) -> Self {
+        Self::with_source(Source::Coordinator(sites), queue)
+    }
+
+    fn with_source(source: Source, queue: JobQueue) -> Self {
+        Self {
+            source,
+            queue,
+            skip: HashSet::new(),
+            visited: None,
+            resolver: None,
+            left_out: None,
+            exhausted: false,
+            limit: None,
+            deadline: None,
+        }
+    }
+
+    /// Leaves out the sites with these URLs, e.g. because a previous run already crawled them.
+    #[must_use]
+    pub fn skip(mut self, skip: HashSet<String>) -> Self {
+        self.skip = skip;
+        self
+    }
+
+    /// Leaves out the sites listed more than once, keeping track of them in `visited`, and
+    /// counting them in the [outcomes](crate::state::Outcomes) if [left out sites are
+    /// recorded](Self::record_left_out).
+    ///
+    /// Sites from a coordinator are never left out, since it hands out each of them once,
+    /// and again only if the worker it went to dropped it.
+    #[must_use]
+    pub fn dedup(mut self, visited: Option<Visited>) -> Self {
+        self.visited = visited;
+        self
+    }
+
+    /// Looks up the domains of as many as `ahead` upcoming sites at once, leaving out the ones
+    /// that don't resolve.
+    #[must_use]
+    pub fn resolve_ahead(mut self, ahead: Option<usize>) -> Self {
+        self.resolver = ahead.map(Resolver::new);
+        self
+    }
+
+    /// Records the sites left out for not resolving in `output`, and counts them as done on the
+    /// `dashboard`, so that they don't go missing from the results.
+    #[must_use]
+    pub fn record_left_out(mut self, output: Output, dashboard: Dashboard) -> Self {
+        self.left_out = Some((output, dashboard));
+        self
+    }
+
+    /// Stops after assigning this many sites.
+    #[must_use]
+    pub fn limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Stops assigning sites at this point in time.
+    #[must_use]
+    pub fn deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Fills the queue, and closes it once there's nothing more to add, or something went wrong.
     #[tracing::instrument(skip_all)]
-    pub async fn run(mut self, mut rx: ShutdownRx) -> Result<()> {
+    pub async fn run(mut self, rx: ShutdownRx) -> Result<()> {
+        let res = self.assign(rx).await;
+        // the crawlers would wait for more sites forever otherwise
+        self.queue.close();
+        if let Err(e) = &res {
+            error!(?e, "Failed to read the sites to crawl");
+        }
+        res
+    }
+
+    async fn assign(&mut self, mut rx: ShutdownRx) -> Result<()> {
+        let mut assigned = 0;
+        let deadline = until(self.deadline);
+        tokio::pin!(deadline);
+
         loop {
+            if self.limit.is_some_and(|limit| assigned >= limit) {
+                info!(assigned, "Assigned as many sites as allowed - stopping");
+                break;
+            }
             tokio::select! {
                 _ = rx.changed() => break,
+                () = &mut deadline => {
+                    info!(assigned, "Out of time - no longer assigning sites");
+                    break;
+                }
 
-                site = self.source.next_line() => {
-                    let Some(mut site) = site? else { break; };
-                    let idx = site.find(',').unwrap() + 1;
-                    site.insert_str(idx, "https://");
-
-                    self.queue.push(Url::parse(&site[idx..])?).await;
+                url = self.next() => {
+                    let Some(url) = url? else { break; };
+                    self.queue.push(url).await;
+                    assigned += 1;
                 }
             }
         }
 
         Ok(())
     }
+
+    /// The next site that isn't known to be gone, if any are left.
+    async fn next(&mut self) -> Result<Option<Url>> {
+        if self.resolver.is_none() {
+            return self.next_listed().await;
+        }
+        loop {
+            while !self.exhausted && self.resolver.as_ref().is_some_and(Resolver::wants_more) {
+                match self.next_listed().await? {
+                    Some(url) => self.resolver.as_mut().unwrap().push(url),
+                    None => self.exhausted = true,
+                }
+            }
+            match self.resolver.as_mut().unwrap().next().await {
+                Some((url, Resolution::Resolves)) => return Ok(Some(url)),
+                Some((url, Resolution::Missing(e))) => {
+                    info!(%url, e, "Domain doesn't resolve - leaving it out");
+                    self.record_missing(&url);
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Records a site whose domain doesn't resolve as excluded, as if a crawler had found out.
+    fn record_missing(&self, url: &Url) {
+        let Some((output, dashboard)) = &self.left_out else {
+            return;
+        };
+        let mut record = SiteRecord::new(url.to_string());
+        record.excluded = Some("nxdomain".to_owned());
+        output.record(record);
+        dashboard.left_out();
+    }
+
+    /// The next site from the source that isn't to be left out, if any are left.
+    async fn next_listed(&mut self) -> Result<Option<Url>> {
+        while let Some(url) = self.source.next().await? {
+            if self.skip.contains(url.as_str()) {
+                continue;
+            }
+            let listed = matches!(self.source, Source::File(_));
+            if listed
+                && self
+                    .visited
+                    .as_mut()
+                    .is_some_and(|v| v.insert(url.as_str()))
+            {
+                debug!(%url, "Site listed more than once - skipping");
+                if let Some((output, _)) = &self.left_out {
+                    output.outcomes.duplicates.fetch_add(1, Ordering::Relaxed);
+                }
+                continue;
+            }
+            return Ok(Some(url));
+        }
+        Ok(None)
+    }
 }
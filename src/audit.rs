@@ -0,0 +1,180 @@
+//! Checking a finished run's results file against the statistics aggregated along the way,
+//! to catch sites going missing, or being counted twice, between the crawlers and the sinks.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::atomic::Ordering,
+};
+
+use eyre::{Context, Result};
+use serde::Deserialize;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader},
+};
+use tracing::*;
+use ulid::Ulid;
+
+use crate::{crawler::Dashboard, state::Output};
+
+/// What the crawlers and the aggregate statistics say about a run, once it's over.
+#[derive(Clone, Copy, Debug)]
+pub struct Expected {
+    /// Sites the crawlers were done with
+    pub completed: usize,
+    /// Sites handed to crawlers, and the ones recorded without a crawler, if the engine has a
+    /// queue to hand them out from
+    pub assigned: Option<usize>,
+    /// Sites counted as failed
+    pub failed: u64,
+    /// Elements in the tag frequencies, leaving out the [baseline](Output::with_baseline)
+    pub counted: u64,
+    /// Whether the run was cut short, abandoning the sites in progress
+    pub shut_down: bool,
+}
+impl Expected {
+    /// Takes down the totals, which have to be final, i.e. every site is recorded.
+    #[must_use]
+    pub fn of(output: &Output, dashboard: &Dashboard, shut_down: bool) -> Self {
+        let dashboard = dashboard.get();
+        Self {
+            completed: dashboard.completed,
+            assigned: dashboard.assigned.map(|n| n + dashboard.left_out),
+            failed: output.outcomes.failed.load(Ordering::Relaxed),
+            counted: output.freq.get().iter().sum::<u64>()
+                - output.baseline.as_deref().map_or(0, |b| b.iter().sum()),
+            shut_down,
+        }
+    }
+}
+
+/// Something the results file and the statistics disagree on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// Fewer or more records than sites the crawlers were done with
+    Records { written: usize, completed: usize },
+    /// Fewer or more records than sites handed out
+    Assigned {
+        failed: usize,
+        succeeded: usize,
+        assigned: usize,
+    },
+    /// The records and the outcome counters disagree on how many sites failed
+    Failed { written: usize, counted: u64 },
+    /// The records' element counts don't add up to the tag frequencies
+    Counted { written: u64, counted: u64 },
+    /// Sites with more than one record
+    Duplicates { sites: usize },
+}
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Records { written, completed } => write!(
+                f,
+                "{written} site records were written, but {completed} sites were completed"
+            ),
+            Self::Assigned {
+                failed,
+                succeeded,
+                assigned,
+            } => write!(
+                f,
+                "{failed} sites failed and {succeeded} succeeded, but {assigned} were assigned"
+            ),
+            Self::Failed { written, counted } => write!(
+                f,
+                "{written} site records have errors, but {counted} sites were counted as failed"
+            ),
+            Self::Counted { written, counted } => write!(
+                f,
+                "The site records add up to {written} elements, but the tag frequencies to {counted}"
+            ),
+            Self::Duplicates { sites } => write!(
+                f,
+                "{sites} sites have more than one record - listed more than once without --dedup, \
+                 or written twice"
+            ),
+        }
+    }
+}
+
+/// The little of a site record that's checked.
+#[derive(Deserialize)]
+struct Written {
+    url: String,
+    run: Option<Ulid>,
+    #[serde(default)]
+    counted: u64,
+    error: Option<String>,
+}
+
+/// Goes through the records of `run` in the results file, and lists what they and the
+/// statistics disagree on, if anything.
+#[tracing::instrument(skip(expected))]
+pub async fn check(results: &Path, run: Ulid, expected: &Expected) -> Result<Vec<Discrepancy>> {
+    let file = File::open(results)
+        .await
+        .wrap_err_with(|| format!("Failed to open {}", results.display()))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let (mut written, mut failed, mut counted) = (0, 0, 0);
+    // hashes rather than URLs, to take up little memory however many sites there are
+    let mut records = HashMap::<u64, usize>::new();
+    while let Some(line) = lines.next_line().await? {
+        let record: Written = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!(%e, "Skipping unreadable site record");
+                continue;
+            }
+        };
+        // results can be appended to, by other runs
+        if record.run != Some(run) {
+            continue;
+        }
+        written += 1;
+        failed += usize::from(record.error.is_some());
+        counted += record.counted;
+        let mut hasher = DefaultHasher::new();
+        record.url.hash(&mut hasher);
+        *records.entry(hasher.finish()).or_default() += 1;
+    }
+
+    let mut discrepancies = vec![];
+    if written != expected.completed {
+        discrepancies.push(Discrepancy::Records {
+            written,
+            completed: expected.completed,
+        });
+    }
+    if let Some(assigned) = expected.assigned {
+        // sites in progress are abandoned when shutting down, and never recorded
+        if written > assigned || (written < assigned && !expected.shut_down) {
+            discrepancies.push(Discrepancy::Assigned {
+                failed,
+                succeeded: written - failed,
+                assigned,
+            });
+        }
+    }
+    if failed as u64 != expected.failed {
+        discrepancies.push(Discrepancy::Failed {
+            written: failed,
+            counted: expected.failed,
+        });
+    }
+    if counted != expected.counted {
+        discrepancies.push(Discrepancy::Counted {
+            written: counted,
+            counted: expected.counted,
+        });
+    }
+    let duplicates = records.values().filter(|&&n| n > 1).count();
+    if duplicates > 0 {
+        discrepancies.push(Discrepancy::Duplicates { sites: duplicates });
+    }
+    Ok(discrepancies)
+}
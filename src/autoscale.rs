@@ -0,0 +1,127 @@
+//! Adds and retires crawlers as the machine and the sites allow, instead of running a fixed number.
+//!
+//! Every so often, one crawler is retired if the machine is short on CPU or memory, or too many
+//! sites are failing, and otherwise one is added if sites wait too long for a crawler to take them.
+
+use std::{str::FromStr, sync::atomic::Ordering, time::Duration};
+
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+use tracing::*;
+
+use crate::{
+    config::AutoscalePolicy,
+    crawler::{Control, Shared},
+    resources::SystemSampler,
+    util::Port,
+};
+
+/// How many crawlers run at once: a fixed number, or `auto` to scale with the load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Workers {
+    Fixed(Port),
+    Auto,
+}
+impl FromStr for Workers {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Self::Auto);
+        }
+        s.parse()
+            .map(Self::Fixed)
+            .map_err(|_| format!("expected a number of workers or `auto`, not {s:?}"))
+    }
+}
+
+/// Decides when to add and retire crawlers, following an [`AutoscalePolicy`].
+pub struct Autoscaler {
+    policy: AutoscalePolicy,
+    max_workers: usize,
+    /// Time between decisions
+    period: Duration,
+    interval: Interval,
+    sampler: SystemSampler,
+    /// Sites completed and failed as of the previous decision
+    completed: usize,
+    failed: u64,
+}
+impl Autoscaler {
+    #[must_use]
+    pub fn new(policy: AutoscalePolicy) -> Self {
+        let period = Duration::from_secs(policy.interval_secs.max(1));
+        // the crawlers started along with the session get to settle in first
+        let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            max_workers: usize::from(policy.max_workers()),
+            policy,
+            period,
+            interval,
+            sampler: SystemSampler::new(),
+            completed: 0,
+            failed: 0,
+        }
+    }
+
+    /// Waits until it's time for the next decision.
+    pub async fn tick(&mut self) {
+        self.interval.tick().await;
+    }
+
+    /// Whether to add or retire a crawler, given how many there are now.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn decide(&mut self, workers: usize, shared: &Shared) -> Option<Control> {
+        let completed = shared.dashboard.get().completed;
+        let failed = shared.output.outcomes.failed.load(Ordering::Relaxed);
+        let crawled = completed.saturating_sub(self.completed);
+        let error_rate = if crawled == 0 {
+            0.0
+        } else {
+            failed.saturating_sub(self.failed) as f64 / crawled as f64
+        };
+        self.completed = completed;
+        self.failed = failed;
+
+        let usage = self.sampler.sample();
+        let queue = &shared.job_queue;
+        // nobody taking any of the sites waiting means they've waited all along
+        let wait = match queue.take_waits() {
+            Some(wait) => wait,
+            None if !queue.is_empty() => self.period,
+            None => Duration::ZERO,
+        };
+        debug!(workers, ?wait, error_rate, ?usage, "Deciding on crawlers");
+
+        let pressure =
+            if usage.is_some_and(|u| u.memory_available < self.policy.min_available_memory) {
+                Some("memory")
+            } else if usage.is_some_and(|u| u.cpu > self.policy.max_cpu) {
+                Some("CPU")
+            } else if error_rate > self.policy.max_error_rate {
+                Some("errors")
+            } else {
+                None
+            };
+        match pressure {
+            Some(reason) if workers > usize::from(self.policy.min_workers) => {
+                info!(workers, reason, "Under pressure - retiring a crawler");
+                Some(Control::RetireWorker)
+            }
+            None if wait.as_secs_f64() > self.policy.max_queue_wait_secs
+                && workers < self.max_workers =>
+            {
+                info!(workers, ?wait, "Sites wait too long - adding a crawler");
+                Some(Control::AddWorker)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Waits until the autoscaler, if there is one, wants to decide, or forever otherwise.
+pub async fn tick(autoscaler: Option<&mut Autoscaler>) {
+    match autoscaler {
+        Some(autoscaler) => autoscaler.tick().await,
+        None => std::future::pending().await,
+    }
+}
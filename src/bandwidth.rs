@@ -0,0 +1,109 @@
+//! How much crawling transfers over the network, going by the pages' [resource timing], for
+//! keeping an eye on metered or shared connections.
+//!
+//! [resource timing]: https://developer.mozilla.org/en-US/docs/Web/API/Performance_API/Resource_timing
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+
+/// How far back the current bandwidth looks
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Adds up what the page and everything it loaded took to transfer.
+///
+/// Cross-origin resources without `Timing-Allow-Origin` report nothing, and the browser only
+/// keeps a few hundred entries by default, so this errs on the low side.
+const TRANSFER_SCRIPT: &str = r#"
+let bytes = 0;
+for (const type of ["navigation", "resource"]) {
+    for (const entry of performance.getEntriesByType(type)) {
+        bytes += entry.transferSize || 0;
+    }
+}
+return bytes;
+"#;
+
+/// The bytes the currently loaded page transferred, headers included, as far as it tells.
+pub async fn transferred(client: &Client) -> Result<u64> {
+    let v = client.execute(TRANSFER_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+#[derive(Debug, Default)]
+struct Transfers {
+    /// Sites whose transfers are known
+    sites: u64,
+    bytes: u64,
+    /// When the first site was done
+    since: Option<Instant>,
+    /// When each site in the window was done, and what it transferred
+    recent: VecDeque<(Instant, u64)>,
+}
+impl Transfers {
+    fn forget_old(&mut self, now: Instant) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&(at, _)| now.duration_since(at) > RATE_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// The bytes transferred by all sites so far, shared between crawlers.
+#[derive(Clone, Debug, Default)]
+pub struct Bandwidth {
+    inner: Arc<Mutex<Transfers>>,
+}
+impl Bandwidth {
+    pub fn add(&self, bytes: u64) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        inner.sites += 1;
+        inner.bytes += bytes;
+        inner.since.get_or_insert(now);
+        inner.recent.push_back((now, bytes));
+        inner.forget_old(now);
+    }
+
+    /// The bytes transferred per second, over the last few seconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn rate(&self) -> f64 {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        inner.forget_old(now);
+        let bytes: u64 = inner.recent.iter().map(|&(_, bytes)| bytes).sum();
+        // the window isn't full yet early on, and a second's as short as it gets
+        let window = inner
+            .since
+            .map_or(RATE_WINDOW, |since| now.duration_since(since))
+            .clamp(Duration::from_secs(1), RATE_WINDOW);
+        bytes as f64 / window.as_secs_f64()
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> TransferSummary {
+        let inner = self.inner.lock().unwrap();
+        TransferSummary {
+            sites: inner.sites,
+            bytes: inner.bytes,
+        }
+    }
+}
+
+/// What the sites transferred, as written to the summary.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct TransferSummary {
+    /// How many sites told what they transferred
+    pub sites: u64,
+    pub bytes: u64,
+}
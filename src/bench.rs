@@ -0,0 +1,327 @@
+//! Measuring how fast the crawl pipeline gets through a fixed set of pages, to catch it
+//! getting slower.
+//!
+//! The pages are bundled with the binary and served from an embedded server for the
+//! WebDriver engine, or saved as snapshots for the snapshot engine, so that neither the
+//! network nor the sites themselves change between runs. The time spent in each stage is
+//! taken from the spans the engines already open, by a [`StageLayer`].
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::{self, Write as _},
+    net::SocketAddr,
+    path::Path,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use eyre::{ensure, Result};
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, StatusCode,
+};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use url::Url;
+
+use crate::{
+    config::Config,
+    crawler::Dashboard,
+    offline::Snapshots,
+    state::{Output, State},
+    util::{Engine, Port},
+    CrawlSession,
+};
+
+/// The pages crawled, by name
+pub const FIXTURES: &[(&str, &str)] = &[
+    ("article", include_str!("../fixtures/bench/article.html")),
+    ("form", include_str!("../fixtures/bench/form.html")),
+    ("table", include_str!("../fixtures/bench/table.html")),
+    ("app", include_str!("../fixtures/bench/app.html")),
+];
+
+/// The spans timed, in the order they happen to a page
+pub const STAGES: &[&str] = &[
+    // snapshot engine
+    "read",
+    "parse",
+    // WebDriver engine
+    "navigate",
+    "dismiss_dialogs",
+    "walk",
+    "measure_page",
+    // the whole of a site, with the WebDriver engine
+    "crawl",
+];
+
+/// Serves the [fixtures](FIXTURES) at `/<name>.html` on localhost, until dropped.
+pub struct FixtureServer {
+    addr: SocketAddr,
+    server: JoinHandle<hyper::Result<()>>,
+}
+impl FixtureServer {
+    pub fn start() -> Result<Self> {
+        let service = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(
+                |req| async move { Ok::<_, Infallible>(serve(&req)) },
+            ))
+        });
+        let server =
+            hyper::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?.serve(service);
+        Ok(Self {
+            addr: server.local_addr(),
+            server: tokio::spawn(server),
+        })
+    }
+
+    /// Where the fixture is served, told apart from other visits to it by `round`.
+    #[must_use]
+    pub fn url(&self, name: &str, round: usize) -> Url {
+        Url::parse(&format!("http://{}/{name}.html?round={round}", self.addr))
+            .expect("fixture URL should be valid")
+    }
+}
+impl Drop for FixtureServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn serve(req: &Request<Body>) -> Response<Body> {
+    let name = req
+        .uri()
+        .path()
+        .trim_start_matches('/')
+        .trim_end_matches(".html");
+    let Some((_, html)) = FIXTURES.iter().find(|(n, _)| *n == name) else {
+        let mut res = Response::new(Body::from("Not found"));
+        *res.status_mut() = StatusCode::NOT_FOUND;
+        return res;
+    };
+    let mut res = Response::new(Body::from(*html));
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    res
+}
+
+/// How long each [stage](STAGES) took, every time it came up.
+#[derive(Clone, Debug, Default)]
+pub struct StageTimes(Arc<Mutex<HashMap<&'static str, Vec<Duration>>>>);
+impl StageTimes {
+    /// A layer adding the stages' times to these.
+    #[must_use]
+    pub fn layer(&self) -> StageLayer {
+        StageLayer(self.clone())
+    }
+
+    /// Forgets the times taken so far.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    /// The times taken so far, starting over from none.
+    #[must_use]
+    pub fn take(&self) -> Vec<(&'static str, Latency)> {
+        let mut times = std::mem::take(&mut *self.0.lock().unwrap());
+        STAGES
+            .iter()
+            .filter_map(|stage| Some((*stage, Latency::of(times.remove(stage)?))))
+            .collect()
+    }
+}
+
+/// Times the spans of the [stages](STAGES), from when they're opened to when they're closed.
+pub struct StageLayer(StageTimes);
+
+/// When a stage's span was opened
+struct Opened(Instant);
+
+impl<S> Layer<S> for StageLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if !STAGES.contains(&attrs.metadata().name()) {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(Opened(opened)) = span.extensions().get::<Opened>().map(|o| Opened(o.0)) else {
+            return;
+        };
+        let mut times = (self.0).0.lock().unwrap();
+        times
+            .entry(span.metadata().name())
+            .or_default()
+            .push(opened.elapsed());
+    }
+}
+
+/// How long a stage took across all pages.
+#[derive(Clone, Copy, Debug)]
+pub struct Latency {
+    pub count: usize,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+impl Latency {
+    #[allow(clippy::cast_possible_truncation)]
+    fn of(mut times: Vec<Duration>) -> Self {
+        times.sort_unstable();
+        let count = times.len();
+        let percentile = |p: usize| times[(count * p).div_ceil(100).clamp(1, count) - 1];
+        Self {
+            count,
+            mean: times.iter().sum::<Duration>() / count as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            max: times[count - 1],
+        }
+    }
+}
+
+/// How one engine did on the fixtures.
+#[derive(Debug)]
+pub struct BenchReport {
+    pub engine: String,
+    pub pages: usize,
+    pub elapsed: Duration,
+    pub stages: Vec<(&'static str, Latency)>,
+}
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        #[allow(clippy::cast_precision_loss)]
+        let rate = self.pages as f64 / self.elapsed.as_secs_f64();
+        writeln!(
+            f,
+            "{}: {} pages in {:.2}s ({rate:.1} pages/s)",
+            self.engine,
+            self.pages,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "  {:<16} {:>6} {:>10} {:>10} {:>10} {:>10}",
+            "stage", "count", "mean ms", "p50 ms", "p95 ms", "max ms"
+        )?;
+        for (stage, l) in &self.stages {
+            writeln!(
+                f,
+                "  {stage:<16} {:>6} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+                l.count,
+                ms(l.mean),
+                ms(l.p50),
+                ms(l.p95),
+                ms(l.max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Analyzes `rounds` snapshots of each fixture with the snapshot engine.
+pub async fn snapshot_engine(rounds: usize, stages: &StageTimes) -> Result<BenchReport> {
+    let dir = tempfile::tempdir()?;
+    for round in 0..rounds {
+        for (name, html) in FIXTURES {
+            let url = format!("http://fixtures/{name}.html?round={round}");
+            let contents = format!("<!-- saved from url=({:04}){url} -->\n{html}", url.len());
+            let path = dir.path().join(format!("{round:04}-{name}.html"));
+            tokio::fs::write(path, contents).await?;
+        }
+    }
+
+    let (output, dashboard) = (output().await?, Dashboard::default());
+    let (_shutdown_tx, shutdown_rx) = watch::channel(());
+    let snapshots = Snapshots::new(dir.path()).await?;
+    let started = Instant::now();
+    snapshots
+        .run(
+            State::offline(output.clone())?,
+            dashboard.clone(),
+            shutdown_rx,
+        )
+        .await?;
+    report(Engine::Snapshot, started, &output, &dashboard, stages)
+}
+
+/// Crawls each fixture `rounds` times with the WebDriver engine, using `workers` sessions
+/// of the WebDriver at `driver`.
+pub async fn webdriver_engine(
+    driver: &Path,
+    workers: Port,
+    base_port: Port,
+    rounds: usize,
+    stages: &StageTimes,
+) -> Result<BenchReport> {
+    let server = FixtureServer::start()?;
+    let dir = tempfile::tempdir()?;
+    let sites = dir.path().join("sites.csv");
+    let mut list = String::new();
+    let urls = (0..rounds).flat_map(|round| FIXTURES.iter().map(move |(name, _)| (round, name)));
+    for (i, (round, name)) in urls.enumerate() {
+        writeln!(list, "{},{}", i + 1, server.url(name, round))?;
+    }
+    tokio::fs::write(&sites, list).await?;
+
+    let (output, dashboard) = (output().await?, Dashboard::default());
+    let session = CrawlSession::builder()
+        .driver(driver)
+        .sites(&sites)
+        .workers(workers)
+        .base_port(base_port)
+        .output(output.clone())
+        .dashboard(dashboard.clone())
+        .build()
+        .await?;
+    // e.g. WebDrivers starting up
+    stages.clear();
+    let started = Instant::now();
+    session.run().await?;
+    report(Engine::WebDriver, started, &output, &dashboard, stages)
+}
+
+/// Counts pages from zero, without writing their records anywhere.
+async fn output() -> Result<Output> {
+    let (records_tx, mut records_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move { while records_rx.recv().await.is_some() {} });
+    Output::load(records_tx, &Config::default()).await
+}
+
+fn report(
+    engine: Engine,
+    started: Instant,
+    output: &Output,
+    dashboard: &Dashboard,
+    stages: &StageTimes,
+) -> Result<BenchReport> {
+    let elapsed = started.elapsed();
+    let failed = output.outcomes.failed.load(Ordering::Relaxed);
+    ensure!(
+        failed == 0,
+        "{failed} fixture pages failed with the {engine} engine"
+    );
+    Ok(BenchReport {
+        engine: engine.to_string(),
+        pages: dashboard.get().completed,
+        elapsed,
+        stages: stages.take(),
+    })
+}
@@ -0,0 +1,132 @@
+//! The subcommands other than crawling.
+
+use eyre::{ensure, Context, Result};
+use tokio::sync::watch;
+use tracing::info;
+
+use quotelementa::{
+    assigner,
+    bench::{self, StageTimes},
+    config::Config,
+    coordinator::{self, Coordinator},
+    plugin::Plugin,
+    report::Census,
+    script::Script,
+    signals::Signals,
+    timing::TimingSummary,
+    vocabulary::Vocabulary,
+};
+
+use crate::opts::{BenchOpts, CoordinateOpts, DiffOpts, MergeOpts, ReportOpts, ValidateOpts};
+
+pub(crate) async fn report(opts: &ReportOpts) -> Result<()> {
+    let census = Census::load(&opts.summary).await?;
+    println!("{} elements", census.total());
+    println!();
+    for (tag, count) in census.top(opts.top) {
+        println!("{tag:>12} {count:>12} {:>7.2}%", census.share(tag) * 100.0);
+    }
+    println!();
+    for (group, count) in &census.categories {
+        println!("{group:>12} {count:>12}");
+    }
+    // summaries from before sites were timed have no timings to show
+    if let Some(timing) = TimingSummary::load(&opts.summary).await? {
+        println!();
+        print!("{timing}");
+    }
+    Ok(())
+}
+
+pub(crate) async fn merge(opts: &MergeOpts) -> Result<()> {
+    ensure!(!opts.summaries.is_empty(), "Nothing to merge");
+    let mut merged = Census::default();
+    for path in &opts.summaries {
+        merged.merge(&Census::load(path).await?);
+    }
+    tokio::fs::write(&opts.output, serde_json::to_vec_pretty(&merged)?).await?;
+    println!(
+        "Merged {} summaries into {}",
+        opts.summaries.len(),
+        opts.output.display()
+    );
+    Ok(())
+}
+
+pub(crate) async fn diff(opts: &DiffOpts) -> Result<()> {
+    let before = Census::load(&opts.before).await?;
+    let after = Census::load(&opts.after).await?;
+    for change in before.diff(&after).iter().take(opts.top) {
+        println!(
+            "{:>12} {:>7.2}% -> {:>7.2}% ({:+.2})",
+            change.tag,
+            change.before * 100.0,
+            change.after * 100.0,
+            change.delta() * 100.0
+        );
+    }
+    Ok(())
+}
+
+pub(crate) async fn coordinate(opts: CoordinateOpts) -> Result<()> {
+    let token = coordinator::token(opts.token)?;
+    let coordinator = Coordinator::new(
+        &opts.sites,
+        &opts.output,
+        opts.summary,
+        opts.lease_timeout,
+        token,
+    )
+    .await?;
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let mut signals = Signals::new()?;
+    tokio::spawn(async move {
+        let signal = signals.recv().await;
+        info!(signal, "Received signal - shutting down");
+        shutdown_tx.send_replace(());
+    });
+
+    coordinator.serve(opts.listen, shutdown_rx).await
+}
+
+pub(crate) async fn bench(opts: &BenchOpts, stages: &StageTimes) -> Result<()> {
+    ensure!(opts.rounds > 0, "At least one round is needed");
+    println!("{}", bench::snapshot_engine(opts.rounds, stages).await?);
+    match &opts.driver {
+        Some(driver) => {
+            let report =
+                bench::webdriver_engine(driver, opts.workers, opts.base_port, opts.rounds, stages)
+                    .await?;
+            println!("{report}");
+        }
+        None => println!("webdriver: skipped - no WebDriver binary given"),
+    }
+    Ok(())
+}
+
+pub(crate) async fn validate(opts: &ValidateOpts) -> Result<()> {
+    if let Some(path) = &opts.config {
+        let config = Config::load(path).await?;
+        if let Some(path) = &config.vocabulary {
+            Vocabulary::load(path).await?;
+        }
+        Script::load_all(&config.scripts).await?;
+        Plugin::load_all(&config.plugins).await?;
+        println!("{}: ok", path.display());
+    }
+
+    let sites = tokio::fs::read_to_string(&opts.sites)
+        .await
+        .wrap_err_with(|| format!("Failed to read {}", opts.sites.display()))?;
+    let mut problems = 0;
+    for (i, line) in sites.lines().enumerate() {
+        if let Err(e) = assigner::parse_line(line) {
+            println!("{}:{}: {e}", opts.sites.display(), i + 1);
+            problems += 1;
+        }
+    }
+    ensure!(problems == 0, "Found {problems} invalid sites");
+    println!("{}: ok", opts.sites.display());
+    Ok(())
+}
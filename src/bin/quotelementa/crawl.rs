@@ -0,0 +1,656 @@
+//! Running crawls, by either engine, with whatever's around them: the frontend, the
+//! servers, and where the results go.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use eyre::{bail, ensure, ContextCompat, Result};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot, watch},
+    task::{AbortHandle, JoinHandle},
+    time::Instant,
+};
+use tracing::{info, warn, Instrument};
+use ulid::Ulid;
+
+#[cfg(unix)]
+use quotelementa::ctl::Ctl;
+use quotelementa::{
+    api::{Api, Crawl},
+    audit::{self, Expected},
+    autoscale::Workers,
+    config::Config,
+    coordinator::{self, CoordinatorClient},
+    crawler::{Control, Dashboard},
+    download,
+    frontend::{Frontend, Plain},
+    logs::{self, LogLine},
+    notify::{self, Outcome},
+    offline::Snapshots,
+    report::Census,
+    resources::DriverPids,
+    rundir::RunDir,
+    schedule::Schedule,
+    signals::Signals,
+    sink,
+    state::{Output, SiteRecord, State},
+    tui::{App, Keymap, Tui},
+    util::{deadline_after, Engine},
+    CrawlSession, CrawlSessionBuilder, ShutdownRx,
+};
+
+use crate::opts::CrawlOpts;
+
+/// How many finished sites the status API's event stream buffers for clients that fall behind
+const SITE_EVENTS_CAPACITY: usize = 256;
+
+pub(crate) async fn crawl(
+    opts: CrawlOpts,
+    resume: bool,
+    log_rx: mpsc::UnboundedReceiver<LogLine>,
+    run_dir: &RunDir,
+    log_dir: &Path,
+) -> Result<()> {
+    let dirs = Dirs {
+        run: run_dir,
+        logs: log_dir,
+    };
+    let res = if let Some(path) = &opts.replay {
+        replay(&opts, path, resume, log_rx, dirs).await
+    } else {
+        match opts.every.clone() {
+            Some(schedule) => observe(&opts, &schedule, resume, dirs).await,
+            None if resume => match opts.resumed_run(run_dir).await {
+                Ok(run) => crawl_once(&opts, run, true, true, log_rx, dirs).await,
+                Err(e) => Err(e),
+            },
+            None => crawl_once(&opts, Ulid::new(), false, false, log_rx, dirs).await,
+        }
+    };
+    // runs that get to the end notify by themselves, as soon as they're done
+    if let (true, Err(e)) = (opts.notify_desktop, &res) {
+        notify::send(&Outcome::Failed(e)).await;
+    }
+    res
+}
+
+/// Analyzes the snapshots of the run recorded in the results at `path` again, under the same
+/// run ID, to reproduce its results.
+async fn replay(
+    opts: &CrawlOpts,
+    path: &Path,
+    resume: bool,
+    log_rx: mpsc::UnboundedReceiver<LogLine>,
+    dirs: Dirs<'_>,
+) -> Result<()> {
+    ensure!(
+        opts.engine == Engine::Snapshot,
+        "Runs are replayed from their snapshots - add --engine snapshot"
+    );
+    ensure!(opts.every.is_none(), "Replays can't be scheduled");
+    let recorded = sink::read_records(path).await?;
+    let run = recorded
+        .iter()
+        .find_map(|record| record.run)
+        .unwrap_or_else(Ulid::new);
+    ensure!(
+        opts.results(dirs.run, run) != path,
+        "The replay would overwrite the results it replays - pass another -o"
+    );
+    info!(%run, sites = recorded.len(), "Replaying a previous run");
+    crawl_once(opts, run, resume, resume, log_rx, dirs).await
+}
+
+/// Crawls the sites over and over on a schedule, until told to stop.
+async fn observe(
+    opts: &CrawlOpts,
+    schedule: &Schedule,
+    resume: bool,
+    dirs: Dirs<'_>,
+) -> Result<()> {
+    ensure!(
+        opts.no_tui,
+        "Scheduled crawls run unattended, without the terminal UI - add --no-tui"
+    );
+    let mut signals = Signals::new()?;
+    // only the first run can pick up where an interrupted one left off
+    let mut skip_crawled = resume;
+
+    loop {
+        let started = SystemTime::now();
+        // nothing shows the logs without the terminal UI
+        let (_, log_rx) = mpsc::unbounded_channel();
+        let run = if skip_crawled {
+            opts.resumed_run(dirs.run).await?
+        } else {
+            Ulid::new()
+        };
+        let run = crawl_once(opts, run, skip_crawled, true, log_rx, dirs);
+        tokio::pin!(run);
+
+        // the run itself shuts down gracefully on signals, and this just doesn't start another
+        let stop = tokio::select! {
+            res = &mut run => {
+                res?;
+                false
+            }
+            signal = signals.recv() => {
+                info!(signal, "Stopping scheduled crawls after this run");
+                run.await?;
+                true
+            }
+        };
+        if stop {
+            return Ok(());
+        }
+        skip_crawled = false;
+
+        let next = schedule
+            .next(started)
+            .wrap_err("The schedule never comes around again")?;
+        let wait = next
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO);
+        info!(
+            wait_secs = wait.as_secs(),
+            "Waiting for the next scheduled run"
+        );
+        tokio::select! {
+            () = tokio::time::sleep(wait) => {}
+            signal = signals.recv() => {
+                info!(signal, "Stopping scheduled crawls");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Where a crawl's files go.
+#[derive(Clone, Copy)]
+struct Dirs<'a> {
+    run: &'a RunDir,
+    /// Shared between runs
+    logs: &'a Path,
+}
+
+/// Crawls every site once, leaving out the ones already in the results when `skip_crawled`,
+/// and keeping the existing results when `append`ing.
+#[tracing::instrument(skip_all, fields(%run))]
+async fn crawl_once(
+    opts: &CrawlOpts,
+    run: Ulid,
+    skip_crawled: bool,
+    append: bool,
+    log_rx: mpsc::UnboundedReceiver<LogLine>,
+    dirs: Dirs<'_>,
+) -> Result<()> {
+    let started = SystemTime::now();
+    let config = load_config(opts, dirs.logs, run).await?;
+    let theme = config.theme;
+    let keymap = Keymap::new(&config.keys)?;
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let (status_rx, audit_rx) = (shutdown_rx.clone(), shutdown_rx.clone());
+    let (close_tx, close_rx) = oneshot::channel();
+
+    let (records_tx, records_rx) = mpsc::unbounded_channel();
+    let results = opts.results(dirs.run, run);
+    let skip_sites = if skip_crawled {
+        already_crawled(&results).await?
+    } else {
+        HashSet::new()
+    };
+    let coordinator = connect_coordinator(opts).await?;
+    let output = load_output(opts, records_tx, &config, run).await?;
+    let dashboard = Dashboard::default();
+    dashboard.resume_from(skip_sites.len());
+
+    let mut crawl = start(
+        opts,
+        config,
+        &output,
+        &dashboard,
+        shutdown_rx,
+        skip_sites,
+        coordinator.clone(),
+    )
+    .await?;
+
+    // the records wait in the channel until there's somewhere to write them
+    dirs.run.create()?;
+    let (sink, sites_tx) = spawn_sink(
+        opts,
+        results.clone(),
+        append,
+        coordinator.clone(),
+        &output,
+        records_rx,
+    );
+
+    let sites_count = crawl.sites_count;
+    let (stop_tx, stop_rx) = mpsc::unbounded_channel();
+    let app = App::new(
+        output.clone(),
+        dashboard.clone(),
+        log_rx,
+        sites_count,
+        shutdown_tx,
+    )
+    .with_theme(theme)
+    .with_keymap(keymap)
+    .with_stop_requests(stop_rx)
+    .with_export_dir(dirs.run.path().to_owned());
+    let app = crawl.steer(app);
+    let servers = spawn_servers(
+        opts,
+        &output,
+        &dashboard,
+        sites_count,
+        status_rx,
+        stop_tx,
+        sites_tx,
+    )?;
+
+    let frontend = spawn_frontend(opts, app, close_rx)?;
+
+    let res = crawl.work.await;
+    for server in servers {
+        server.abort();
+    }
+    // crawlers that were aborted, or panicked, never got to stop their WebDrivers
+    if let Some(pids) = &crawl.driver_pids {
+        pids.sweep();
+    }
+    res??;
+    write_statistics(
+        opts,
+        dirs.run,
+        run,
+        started,
+        &output,
+        &dashboard,
+        coordinator.as_ref(),
+    )
+    .await?;
+    // every site is recorded by now, so the totals are final
+    let shut_down = audit_rx.has_changed().unwrap_or(true);
+    let expected = Expected::of(&output, &dashboard, shut_down);
+    drop(output);
+    if opts.notify_desktop {
+        // rather than once the UI is closed, which may be a while after
+        let outcome = Outcome::of(&dashboard, sites_count, started, shut_down);
+        notify::send(&outcome).await;
+    }
+
+    info!("Everything done! Waiting for UI to stop...");
+
+    close_tx.send(()).unwrap();
+    frontend.await??;
+
+    // the UI and the workers held the last senders, so this finishes promptly
+    sink.await??;
+
+    if coordinator.is_none() {
+        audit_results(&results, run, &expected).await?;
+    }
+    Ok(())
+}
+
+/// Shows the crawl's progress in the terminal UI, or prints it with `--no-tui`, until `close_rx`
+/// says it's over.
+fn spawn_frontend(
+    opts: &CrawlOpts,
+    app: App,
+    close_rx: oneshot::Receiver<()>,
+) -> Result<JoinHandle<Result<()>>> {
+    let frontend = if opts.no_tui {
+        Frontend::Plain(Plain::new(app, opts.progress_format))
+    } else {
+        Frontend::Tui(Tui::new(app)?)
+    };
+    Ok(tokio::spawn(frontend.run(close_rx).in_current_span()))
+}
+
+/// Checks the results just written against the statistics, reporting whatever doesn't add up.
+async fn audit_results(results: &Path, run: Ulid, expected: &Expected) -> Result<()> {
+    let discrepancies = audit::check(results, run, expected).await?;
+    if discrepancies.is_empty() {
+        info!("The results add up to the statistics");
+    }
+    for discrepancy in &discrepancies {
+        warn!(%discrepancy, "The results don't add up to the statistics");
+        // the UI is gone by now, and the logs are easy to miss
+        eprintln!("Warning: {discrepancy}");
+    }
+    Ok(())
+}
+
+/// Writes the summary of a finished run, tells the coordinator it's finished if there's one,
+/// and adds the run to the time series if it's one of many.
+async fn write_statistics(
+    opts: &CrawlOpts,
+    run_dir: &RunDir,
+    run: Ulid,
+    started: SystemTime,
+    output: &Output,
+    dashboard: &Dashboard,
+    coordinator: Option<&CoordinatorClient>,
+) -> Result<()> {
+    sink::write_summary(&opts.summary(run_dir, run), output).await?;
+    if let Some(client) = coordinator {
+        client.finish().await?;
+    }
+    if opts.every.is_some() {
+        let sites = dashboard.get().completed;
+        let path = opts.timeseries(run_dir, run);
+        sink::append_run(&path, started, SystemTime::now(), sites, output).await?;
+    }
+    Ok(())
+}
+
+/// A crawl under way, by either engine.
+struct Started {
+    sites_count: usize,
+    work: JoinHandle<Result<()>>,
+    /// Where to add and retire crawlers, if the engine has any
+    control_tx: Option<mpsc::UnboundedSender<Control>>,
+    driver_pids: Option<DriverPids>,
+}
+impl Started {
+    /// Lets the UI add and retire crawlers, and keep an eye on their WebDrivers,
+    /// if the engine has any.
+    fn steer(&mut self, mut app: App) -> App {
+        if let Some(tx) = self.control_tx.take() {
+            app = app.with_control(tx);
+        }
+        if let Some(pids) = self.driver_pids.clone() {
+            app = app.with_driver_pids(pids);
+        }
+        app
+    }
+}
+
+async fn start(
+    opts: &CrawlOpts,
+    config: Config,
+    output: &Output,
+    dashboard: &Dashboard,
+    shutdown_rx: ShutdownRx,
+    skip_sites: HashSet<String>,
+    coordinator: Option<CoordinatorClient>,
+) -> Result<Started> {
+    match opts.engine {
+        Engine::WebDriver => {
+            let session = session_builder(opts, &config, coordinator)
+                .await?
+                .config(config)
+                .output(output.clone())
+                .dashboard(dashboard.clone())
+                .shutdown(shutdown_rx)
+                .skip_sites(skip_sites)
+                .build()
+                .await?;
+
+            let control_tx = Some(session.control());
+            let driver_pids = Some(session.driver_pids().clone());
+            let sites_count = session.sites_count();
+            let work = async move { session.run().await.map(drop) };
+            Ok(Started {
+                sites_count,
+                work: tokio::spawn(work.in_current_span()),
+                control_tx,
+                driver_pids,
+            })
+        }
+        Engine::Snapshot => {
+            let deadline = deadline_after(opts.max_duration).map(Instant::into_std);
+            let snapshots = match &opts.replay {
+                Some(path) => Snapshots::replay(&opts.driver, sink::read_records(path).await?),
+                None => Snapshots::new(&opts.driver).await?,
+            };
+            let snapshots = snapshots
+                .skip(skip_sites)
+                .limit(opts.max_sites)
+                .deadline(deadline);
+            let sites_count = snapshots.len();
+            let work = snapshots.run(
+                State::offline(output.clone())?,
+                dashboard.clone(),
+                shutdown_rx,
+            );
+            Ok(Started {
+                sites_count,
+                work: tokio::spawn(work.in_current_span()),
+                control_tx: None,
+                driver_pids: None,
+            })
+        }
+    }
+}
+
+/// Connects to the coordinator to get the sites from, if there's one.
+async fn connect_coordinator(opts: &CrawlOpts) -> Result<Option<CoordinatorClient>> {
+    let Some(url) = &opts.coordinator else {
+        return Ok(None);
+    };
+    let token = coordinator::token(opts.coordinator_token.clone())?;
+    Ok(Some(
+        CoordinatorClient::connect(url.clone(), token).await?.0,
+    ))
+}
+
+/// The sites in the results of an interrupted crawl, to leave out when resuming it.
+async fn already_crawled(output: &Path) -> Result<HashSet<String>> {
+    let urls = sink::read_record_urls(output).await?;
+    info!(
+        sites = urls.len(),
+        "Resuming - skipping sites already crawled"
+    );
+    Ok(urls)
+}
+
+/// Writes the site records to the results file, or sends them to the coordinator if there's one,
+/// announcing them to the status API's clients first, through the sender returned, if it's enabled.
+fn spawn_sink(
+    opts: &CrawlOpts,
+    path: PathBuf,
+    append: bool,
+    coordinator: Option<CoordinatorClient>,
+    output: &Output,
+    mut records_rx: mpsc::UnboundedReceiver<SiteRecord>,
+) -> (JoinHandle<Result<()>>, broadcast::Sender<Arc<SiteRecord>>) {
+    let (sites_tx, _) = broadcast::channel(SITE_EVENTS_CAPACITY);
+    if opts.api_addr.is_some() {
+        records_rx = sink::tee(records_rx, sites_tx.clone());
+    }
+    if let Some(client) = coordinator {
+        let vocabulary = output.vocabulary.clone();
+        let sink = sink::send_records(client, vocabulary, records_rx);
+        return (tokio::spawn(sink.in_current_span()), sites_tx);
+    }
+    let sink = async move { sink::write_records(&path, append, records_rx).await };
+    (tokio::spawn(sink.in_current_span()), sites_tx)
+}
+
+/// Serves the status API and the control socket, if they're enabled, returning what to abort
+/// them with once the crawl is over.
+fn spawn_servers(
+    opts: &CrawlOpts,
+    output: &Output,
+    dashboard: &Dashboard,
+    sites_count: usize,
+    status_rx: ShutdownRx,
+    stop_tx: mpsc::UnboundedSender<&'static str>,
+    sites_tx: broadcast::Sender<Arc<SiteRecord>>,
+) -> Result<Vec<AbortHandle>> {
+    let crawl = Crawl::new(
+        output.clone(),
+        dashboard.clone(),
+        sites_count,
+        status_rx,
+        stop_tx,
+    );
+    let ctl = spawn_ctl(opts, crawl.clone())?;
+    let api = spawn_api(opts, crawl, sites_tx)?;
+    Ok(api
+        .map(|api| api.abort_handle())
+        .into_iter()
+        .chain(ctl.map(|ctl| ctl.abort_handle()))
+        .collect())
+}
+
+/// Serves the status API, if there's an address for it.
+fn spawn_api(
+    opts: &CrawlOpts,
+    crawl: Crawl,
+    sites_tx: broadcast::Sender<Arc<SiteRecord>>,
+) -> Result<Option<JoinHandle<hyper::Result<()>>>> {
+    let Some(addr) = opts.api_addr else {
+        return Ok(None);
+    };
+    let server = Api::new(crawl).with_sites(sites_tx).serve(addr)?;
+    Ok(Some(tokio::spawn(server.in_current_span())))
+}
+
+/// Takes commands on the control socket, if there's a path for it.
+#[cfg(unix)]
+fn spawn_ctl(opts: &CrawlOpts, crawl: Crawl) -> Result<Option<JoinHandle<()>>> {
+    let Some(path) = &opts.ctl_socket else {
+        return Ok(None);
+    };
+    let server = Ctl::new(crawl).serve(path)?;
+    Ok(Some(tokio::spawn(server.in_current_span())))
+}
+#[cfg(not(unix))]
+fn spawn_ctl(opts: &CrawlOpts, _crawl: Crawl) -> Result<Option<JoinHandle<()>>> {
+    if opts.ctl_socket.is_some() {
+        bail!("--ctl-socket needs Unix domain sockets, which this platform doesn't have");
+    }
+    Ok(None)
+}
+
+/// Sets up a session with the WebDrivers and sites given on the command line.
+async fn session_builder(
+    opts: &CrawlOpts,
+    config: &Config,
+    coordinator: Option<CoordinatorClient>,
+) -> Result<CrawlSessionBuilder> {
+    let remote = !opts.remote_webdriver.is_empty() || config.grid.is_some();
+    let download = opts.download_driver.is_some();
+    let (driver, sites) = match &opts.sites {
+        Some(sites) => {
+            ensure!(
+                opts.coordinator.is_none(),
+                "No list of sites is needed with a coordinator, only a WebDriver binary"
+            );
+            ensure!(
+                !remote && !download,
+                "No WebDriver binary is needed with a remote WebDriver, grid or downloaded driver, only a list of sites"
+            );
+            (Some(&opts.driver), Some(sites))
+        }
+        // the coordinator says which sites to crawl, so the only positional argument is the binary
+        None if opts.coordinator.is_some() => (Some(&opts.driver), None),
+        // without a binary to run, the only positional argument is the list of sites
+        None if remote || download || !config.profiles.is_empty() => (None, Some(&opts.driver)),
+        None => bail!("A list of sites to crawl is required"),
+    };
+
+    let mut builder = CrawlSession::builder()
+        .base_port(opts.base_port)
+        .headless(!opts.no_headless);
+    builder = match opts.workers {
+        Workers::Fixed(workers) => builder.workers(workers),
+        Workers::Auto => builder.autoscale(config.autoscale.clone()),
+    };
+    if let Some(driver) = driver {
+        builder = builder.driver(driver);
+    }
+    if let Some(sites) = sites {
+        builder = builder.sites(sites);
+    }
+    if let Some(client) = coordinator {
+        builder = builder.coordinator(client);
+    }
+    if let Some(kind) = opts.download_driver {
+        let driver = download::driver(kind, &download::cache_dir()).await?;
+        builder = builder.driver(driver).driver_kind(kind);
+    }
+    if let Some(kind) = opts.driver_kind {
+        builder = builder.driver_kind(kind);
+    }
+    for url in &opts.remote_webdriver {
+        builder = builder.remote(url.clone());
+    }
+    if let Some(max_sites) = opts.max_sites {
+        builder = builder.max_sites(max_sites);
+    }
+    builder = builder.dedup(opts.dedup);
+    if let Some(ahead) = opts.resolve_ahead {
+        builder = builder.resolve_ahead(ahead);
+    }
+    if let Some(max_duration) = opts.max_duration {
+        builder = builder.max_duration(max_duration);
+    }
+    Ok(builder)
+}
+
+/// Sets up the statistics of the run, starting from the baseline if there's one.
+async fn load_output(
+    opts: &CrawlOpts,
+    records_tx: mpsc::UnboundedSender<SiteRecord>,
+    config: &Config,
+    run: Ulid,
+) -> Result<Output> {
+    let output = Output::load(records_tx, config).await?.with_run(run);
+    Ok(match &opts.baseline {
+        Some(path) => output.with_baseline(&Census::load(path).await?),
+        None => output,
+    })
+}
+
+/// Loads the config file, if any, with the command-line flags taking precedence.
+async fn load_config(opts: &CrawlOpts, log_dir: &Path, run: Ulid) -> Result<Config> {
+    let mut config = match &opts.config {
+        Some(path) => Config::load(path).await?,
+        None => Config::default(),
+    };
+    config.vocabulary = opts.vocabulary.clone().or(config.vocabulary);
+    config.max_elements = opts.max_elements.or(config.max_elements);
+    config.max_rps = opts.max_rps.or(config.max_rps);
+    config.max_bandwidth = opts.max_bandwidth.or(config.max_bandwidth);
+    ensure!(
+        config.max_bandwidth != Some(0),
+        "The maximum bandwidth must be positive"
+    );
+    if let Some(attempts) = opts.attempts {
+        config.retry.attempts = attempts;
+    }
+    config.recycle.after_sites = opts.recycle_after.or(config.recycle.after_sites);
+    config.exclude_off_domain_redirects |= opts.exclude_off_domain_redirects;
+    config.exclude_parked |= opts.exclude_parked;
+    config.respect_robots_txt |= opts.respect_robots_txt;
+    config.slow_sites.min_walk_rate = opts.min_walk_rate.or(config.slow_sites.min_walk_rate);
+    if let Some(rate) = config.slow_sites.min_walk_rate {
+        ensure!(rate > 0.0, "The minimum walk rate must be positive");
+    }
+    config.guess_language |= opts.guess_language;
+    config.theme = opts.theme.unwrap_or(config.theme);
+    config.grid = opts.grid.clone().or(config.grid);
+    config.snapshot_dir = opts.snapshot_dir.clone().or(config.snapshot_dir);
+    config.eval_script = opts.eval_script.clone().or(config.eval_script);
+    // each run gets a directory of its own, wherever the WebDriver logs go
+    let run = run.to_string();
+    config.driver_log_dir = Some(match config.driver_log_dir {
+        Some(dir) => dir.join(run),
+        None => logs::driver_log_dir(log_dir, &run),
+    });
+    if let Some(dir) = &config.snapshot_dir {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    Ok(config)
+}
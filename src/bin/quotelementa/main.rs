@@ -0,0 +1,92 @@
+#![deny(rust_2018_idioms)]
+#![warn(clippy::pedantic)]
+#![allow(
+    missing_docs,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::doc_markdown,
+    clippy::wildcard_imports
+)]
+mod commands;
+mod crawl;
+mod opts;
+
+use eyre::{Context, Result};
+use tracing::{info, warn, Level};
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+};
+
+use quotelementa::{
+    bench::StageTimes,
+    logs::{self, LogFormat, LogLayer},
+    telemetry,
+};
+
+use crate::{
+    commands::{bench, coordinate, diff, merge, report, validate},
+    crawl::crawl,
+    opts::{Command, Opts, ResumeOpts},
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts: Opts = argh::from_env();
+
+    // crawls keep their files in a directory of their own, once they've started
+    let run_dir = match &opts.command {
+        Command::Crawl(crawl_opts) => Some(crawl_opts.run_dir(false)?),
+        Command::Resume(ResumeOpts(crawl_opts)) => Some(crawl_opts.run_dir(true)?),
+        _ => None,
+    };
+    let log_dir = opts.log_dir;
+
+    std::fs::create_dir_all(&log_dir)
+        .wrap_err_with(|| format!("Failed to create {}", log_dir.display()))?;
+    let run_dir = run_dir.map(|run_dir| run_dir.with_logs(log_dir.clone()));
+    // so much that it doesn't fit is as good as no limit
+    logs::prune(&log_dir, opts.max_log_size.saturating_mul(1024 * 1024))
+        .wrap_err("Failed to clean up old logs")?;
+    let appender = tracing_appender::rolling::daily(&log_dir, logs::LOG_FILE);
+    let (non_blocking, _guard) = tracing_appender::non_blocking(appender);
+
+    let (log_layer, log_rx) = LogLayer::new(Level::INFO);
+    let (otlp_layer, _telemetry) = match &opts.otlp_endpoint {
+        Some(endpoint) => {
+            let (layer, telemetry) = telemetry::otlp_layer(endpoint)?;
+            (Some(layer), Some(telemetry))
+        }
+        None => (None, None),
+    };
+    let file_layer = match opts.log_format {
+        LogFormat::Text => fmt::layer()
+            .with_ansi(false)
+            .with_writer(non_blocking)
+            .boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(non_blocking).boxed(),
+    };
+    let level = LevelFilter::from_level(opts.log_level);
+    let stages = StageTimes::default();
+    let stage_layer = matches!(opts.command, Command::Bench(_)).then(|| stages.layer());
+    tracing_subscriber::registry()
+        .with(file_layer.with_filter(level))
+        .with(log_layer)
+        .with(otlp_layer.with_filter(level))
+        .with(stage_layer)
+        .init();
+
+    let resume = matches!(opts.command, Command::Resume(_));
+    match opts.command {
+        Command::Crawl(crawl_opts) | Command::Resume(ResumeOpts(crawl_opts)) => {
+            let run_dir = run_dir.expect("crawls have a run directory");
+            info!(run_dir = %run_dir.path().display(), "Keeping the run's files");
+            crawl(crawl_opts, resume, log_rx, &run_dir, &log_dir).await
+        }
+        Command::Report(opts) => report(&opts).await,
+        Command::Merge(opts) => merge(&opts).await,
+        Command::Diff(opts) => diff(&opts).await,
+        Command::Validate(opts) => validate(&opts).await,
+        Command::Coordinate(opts) => coordinate(opts).await,
+        Command::Bench(opts) => bench(&opts, &stages).await,
+    }
+}
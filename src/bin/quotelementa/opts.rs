@@ -0,0 +1,545 @@
+//! The command line.
+
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use argh::{CommandInfo, EarlyExit, FromArgs, SubCommand};
+use eyre::{ContextCompat, Result};
+use tracing::Level;
+use ulid::Ulid;
+use url::Url;
+
+use quotelementa::{
+    autoscale::Workers,
+    driver::DriverKind,
+    frontend::ProgressFormat,
+    logs::LogFormat,
+    rundir::{RunDir, LATEST, RUNS_DIR},
+    schedule::Schedule,
+    sink,
+    tui::Theme,
+    util::{parse_bytes, parse_duration, Engine, Port},
+};
+
+/// Crawls the interwebs and analyzes the utilization of elemental constituents
+#[derive(FromArgs)]
+pub(crate) struct Opts {
+    #[argh(subcommand)]
+    pub(crate) command: Command,
+
+    /// an OTLP/HTTP collector to send traces to, e.g. `http://localhost:4318`
+    #[argh(option)]
+    pub(crate) otlp_endpoint: Option<Url>,
+
+    /// how the log file is written: `text` (default) or `json`, one object per line
+    #[argh(option, default = "LogFormat::Text")]
+    pub(crate) log_format: LogFormat,
+
+    /// the directory logs are written into, shared between runs and linked to from each run
+    /// directory as `logs`, with each run's WebDriver logs under `webdriver/`
+    #[argh(option, default = "PathBuf::from(\"logs\")")]
+    pub(crate) log_dir: PathBuf,
+
+    /// how many MiB of logs to keep around, deleting the oldest at startup (default: 100)
+    #[argh(option, default = "100")]
+    pub(crate) max_log_size: u64,
+
+    /// the least severe events logged and traced: `error`, `warn`, `info` (default),
+    /// `debug` or `trace`
+    #[argh(option, default = "Level::INFO")]
+    pub(crate) log_level: Level,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub(crate) enum Command {
+    Crawl(CrawlOpts),
+    Resume(ResumeOpts),
+    Report(ReportOpts),
+    Merge(MergeOpts),
+    Diff(DiffOpts),
+    Validate(ValidateOpts),
+    Coordinate(CoordinateOpts),
+    Bench(BenchOpts),
+}
+
+/// Crawl a list of sites, or re-analyze saved snapshots
+#[derive(FromArgs)]
+#[argh(subcommand, name = "crawl")]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct CrawlOpts {
+    /// the number of workers running concurrently, or `auto` to add and retire them as the
+    /// machine and the sites allow, within the bounds of the `autoscale` config
+    #[argh(option, short = 'n', default = "Workers::Fixed(3)")]
+    pub(crate) workers: Workers,
+
+    /// the port of the first WebDriver, with the others counting up from it
+    /// and skipping any in use; 0 to let the OS pick a free port for each
+    #[argh(option, short = 'p', default = "4444")]
+    pub(crate) base_port: Port,
+
+    /// do not run the WebDriver in headless mode
+    /// (GeckoDriver and ChromeDriver only)
+    #[argh(switch)]
+    pub(crate) no_headless: bool,
+
+    /// the engine used to obtain pages: `webdriver` (default) to crawl live sites,
+    /// or `snapshot` to re-analyze previously saved DOM snapshots
+    #[argh(option, default = "Engine::WebDriver")]
+    pub(crate) engine: Engine,
+
+    /// the WebDriver binary to be run (left out with `--remote-webdriver`, `--grid`,
+    /// `--download-driver`, or profiles that each say which WebDriver to use),
+    /// or the directory of saved snapshots for the snapshot engine;
+    /// the only positional argument with `--coordinator`.
+    #[argh(positional)]
+    pub(crate) driver: PathBuf,
+
+    /// the URL of an already running WebDriver to connect to instead of spawning one,
+    /// e.g. `http://localhost:4444`; can be given several times to spread the workers across them
+    #[argh(option)]
+    pub(crate) remote_webdriver: Vec<Url>,
+
+    /// the kind of WebDriver: `gecko`, `chrome`, `edge`, `safari` or `generic`;
+    /// guessed from the binary's name if left out, and `generic` for remote ones
+    #[argh(option)]
+    pub(crate) driver_kind: Option<DriverKind>,
+
+    /// download the WebDriver of this kind (`gecko` or `chrome`) matching the installed browser,
+    /// instead of running the binary given
+    #[argh(option)]
+    pub(crate) download_driver: Option<DriverKind>,
+
+    /// the URL of a Selenium Grid or Moon hub to create every session against,
+    /// instead of spawning WebDrivers
+    #[argh(option)]
+    pub(crate) grid: Option<Url>,
+
+    /// a TOML configuration file
+    #[argh(option, short = 'c')]
+    pub(crate) config: Option<PathBuf>,
+
+    /// a TOML or JSON file listing the tags to count,
+    /// instead of the built-in HTML ones
+    #[argh(option)]
+    pub(crate) vocabulary: Option<PathBuf>,
+
+    /// the maximum number of elements analyzed per page;
+    /// larger pages are sampled uniformly
+    #[argh(option)]
+    pub(crate) max_elements: Option<usize>,
+
+    /// the maximum number of page loads per second, across all workers
+    #[argh(option)]
+    pub(crate) max_rps: Option<f64>,
+
+    /// the maximum number of bytes transferred per second, across all workers, e.g. `2M`;
+    /// sites that go over it are made up for by waiting before loading the next ones
+    #[argh(option, from_str_fn(parse_bytes))]
+    pub(crate) max_bandwidth: Option<u64>,
+
+    /// the number of navigation attempts per site before giving up (default: 3)
+    #[argh(option)]
+    pub(crate) attempts: Option<u32>,
+
+    /// stop after assigning this many sites, finishing the ones in progress
+    #[argh(option)]
+    pub(crate) max_sites: Option<usize>,
+
+    /// leave out sites listed more than once, which are kept track of in bounded memory,
+    /// at the cost of very rarely leaving out one that isn't
+    #[argh(switch)]
+    pub(crate) dedup: bool,
+
+    /// look up the domains of this many upcoming sites at once, recording the ones that
+    /// don't resolve as excluded instead of navigating to them
+    #[argh(option)]
+    pub(crate) resolve_ahead: Option<usize>,
+
+    /// stop taking on new sites after crawling for this long, e.g. `90m`, `2h`
+    /// or a number of seconds, finishing the ones in progress
+    #[argh(option, from_str_fn(parse_duration))]
+    pub(crate) max_duration: Option<Duration>,
+
+    /// restart each browser after crawling this many sites
+    #[argh(option)]
+    pub(crate) recycle_after: Option<u32>,
+
+    /// leave sites that redirect to a different domain out of the statistics
+    #[argh(switch)]
+    pub(crate) exclude_off_domain_redirects: bool,
+
+    /// leave sites that look like parked domains or placeholders out of the statistics
+    #[argh(switch)]
+    pub(crate) exclude_parked: bool,
+
+    /// leave sites whose robots.txt disallows crawling them out, without loading them
+    #[argh(switch)]
+    pub(crate) respect_robots_txt: bool,
+
+    /// give up on pages whose elements are walked slower than this many per second,
+    /// after a few seconds, leaving them out of the statistics as too slow
+    #[argh(option)]
+    pub(crate) min_walk_rate: Option<f64>,
+
+    /// guess each page's language from its text
+    #[argh(switch)]
+    pub(crate) guess_language: bool,
+
+    /// save each page's serialized DOM into this directory
+    #[argh(option)]
+    pub(crate) snapshot_dir: Option<PathBuf>,
+
+    /// run this JavaScript file in every page, and record what it returns with the site
+    #[argh(option)]
+    pub(crate) eval_script: Option<PathBuf>,
+
+    /// print plain progress lines to stderr instead of running the terminal UI
+    #[argh(switch)]
+    pub(crate) no_tui: bool,
+
+    /// the colors of the terminal UI: `default`, `dark`, `light` or `colorblind`
+    #[argh(option)]
+    pub(crate) theme: Option<Theme>,
+
+    /// the format of the progress lines printed with `--no-tui`:
+    /// `text` (default) or `json`
+    #[argh(option, default = "ProgressFormat::Text")]
+    pub(crate) progress_format: ProgressFormat,
+
+    /// serve the progress, the state of each crawler and the tag counts over HTTP
+    /// at this address, e.g. `127.0.0.1:8080`, along with a way to shut down
+    #[argh(option)]
+    pub(crate) api_addr: Option<SocketAddr>,
+
+    /// take `status`, `pause`, `resume` and `shutdown` commands, one per line, on a Unix
+    /// domain socket at this path, e.g. with `echo pause | nc -U path`
+    #[argh(option)]
+    pub(crate) ctl_socket: Option<PathBuf>,
+
+    /// show a desktop notification once the crawl completes, is shut down or fails
+    #[argh(switch)]
+    pub(crate) notify_desktop: bool,
+
+    /// the directory the run's results, summary and exports go into, instead of a new one in
+    /// `runs/` named after the time, which `runs/latest` points to; `resume` picks up the one
+    /// `runs/latest` points to unless given one
+    #[argh(option)]
+    pub(crate) run_dir: Option<PathBuf>,
+
+    /// the file per-site results are written to, as JSON lines; `{{run}}` in the path
+    /// is replaced with the run's ID (default: `results.jsonl` in the run directory)
+    #[argh(option, short = 'o')]
+    pub(crate) output: Option<PathBuf>,
+
+    /// the file statistics aggregated across all sites are written to; `{{run}}` in the path
+    /// is replaced with the run's ID (default: `summary.json` in the run directory)
+    #[argh(option)]
+    pub(crate) summary: Option<PathBuf>,
+
+    /// keep running, and crawl the sites again on this schedule: an interval like `24h`,
+    /// or a cron expression in UTC like `"0 3 * * 1"`; results are appended to the output
+    #[argh(option)]
+    pub(crate) every: Option<Schedule>,
+
+    /// the file each scheduled run's statistics are appended to, as JSON lines; `{{run}}`
+    /// in the path is replaced with the run's ID (default: `timeseries.jsonl` in the run
+    /// directory)
+    #[argh(option)]
+    pub(crate) timeseries: Option<PathBuf>,
+
+    /// the URL of a coordinator (see `coordinate`) to get the sites to crawl from and send
+    /// the results to, as one of many workers, instead of a list of sites
+    #[argh(option)]
+    pub(crate) coordinator: Option<Url>,
+
+    /// the token the coordinator was started with; better left to the
+    /// `QUOTELEMENTA_COORDINATOR_TOKEN` environment variable, so that other users can't see it
+    #[argh(option)]
+    pub(crate) coordinator_token: Option<String>,
+
+    /// the results of a previous snapshot engine run to reproduce exactly: its snapshots,
+    /// found by name in the directory given, are analyzed again in the same order, under the
+    /// same run ID and as if crawled at the same times
+    #[argh(option)]
+    pub(crate) replay: Option<PathBuf>,
+
+    /// start the tag counts from those in a previous run's summary, e.g. to extend that run
+    /// with more sites; only the summary's tag and category counts include the previous run's,
+    /// which the summary lists separately, while the terminal UI, the API and every other
+    /// statistic only cover this run
+    #[argh(option)]
+    pub(crate) baseline: Option<PathBuf>,
+
+    /// a file containing a list of sites to crawl
+    /// (not needed for the snapshot engine)
+    #[argh(positional)]
+    pub(crate) sites: Option<PathBuf>,
+}
+
+impl CrawlOpts {
+    /// Where the run's files go: the directory given, the latest one when resuming,
+    /// or a new one.
+    pub(crate) fn run_dir(&self, resume: bool) -> Result<RunDir> {
+        let runs = Path::new(RUNS_DIR);
+        match &self.run_dir {
+            Some(dir) => Ok(RunDir::open(dir)),
+            None if resume => RunDir::latest(runs),
+            None => Ok(RunDir::new(runs, SystemTime::now())),
+        }
+    }
+
+    // the files the run writes are in the run directory, unless given elsewhere
+    pub(crate) fn results(&self, run_dir: &RunDir, run: Ulid) -> PathBuf {
+        run_path(
+            &self.output.clone().unwrap_or_else(|| run_dir.results()),
+            run,
+        )
+    }
+    pub(crate) fn summary(&self, run_dir: &RunDir, run: Ulid) -> PathBuf {
+        run_path(
+            &self.summary.clone().unwrap_or_else(|| run_dir.summary()),
+            run,
+        )
+    }
+    pub(crate) fn timeseries(&self, run_dir: &RunDir, run: Ulid) -> PathBuf {
+        let path = self.timeseries.clone();
+        run_path(&path.unwrap_or_else(|| run_dir.timeseries()), run)
+    }
+
+    /// The run being resumed, so that the rest of its records go under the same ID: the latest
+    /// one with results of its own if each run gets its own, or the last one recorded in the
+    /// results otherwise.
+    pub(crate) async fn resumed_run(&self, run_dir: &RunDir) -> Result<Ulid> {
+        let results = self.output.clone().unwrap_or_else(|| run_dir.results());
+        if results.to_str().is_some_and(|p| p.contains("{run}")) {
+            return latest_run(&results).wrap_err_with(|| {
+                format!(
+                    "No results of a previous run to resume at {}",
+                    results.display()
+                )
+            });
+        }
+        let recorded = sink::read_records(&results).await?;
+        // results written before runs had IDs can't be told apart anyway
+        Ok(recorded
+            .iter()
+            .rev()
+            .find_map(|record| record.run)
+            .unwrap_or_else(Ulid::new))
+    }
+}
+
+/// Takes the same options as `crawl`, which argh can't share between subcommands by itself.
+pub(crate) struct ResumeOpts(pub(crate) CrawlOpts);
+impl FromArgs for ResumeOpts {
+    fn from_args(command_name: &[&str], args: &[&str]) -> Result<Self, EarlyExit> {
+        CrawlOpts::from_args(command_name, args).map(Self)
+    }
+}
+impl SubCommand for ResumeOpts {
+    const COMMAND: &'static CommandInfo = &CommandInfo {
+        name: "resume",
+        description:
+            "Continue an interrupted crawl, the latest one unless given its --run-dir, skipping the sites already in its results file",
+    };
+}
+
+/// Print the most common tags and categories of a summary, and how long sites took
+#[derive(FromArgs)]
+#[argh(subcommand, name = "report")]
+pub(crate) struct ReportOpts {
+    /// the summary to report on (default: the latest run's)
+    #[argh(
+        positional,
+        default = "Path::new(RUNS_DIR).join(LATEST).join(\"summary.json\")"
+    )]
+    pub(crate) summary: PathBuf,
+
+    /// the number of tags to list
+    #[argh(option, default = "20")]
+    pub(crate) top: usize,
+}
+
+/// Add up the tag counts of several summaries
+#[derive(FromArgs)]
+#[argh(subcommand, name = "merge")]
+pub(crate) struct MergeOpts {
+    /// the summaries to merge
+    #[argh(positional)]
+    pub(crate) summaries: Vec<PathBuf>,
+
+    /// the file the merged tag counts are written to
+    #[argh(option, short = 'o', default = "PathBuf::from(\"merged.json\")")]
+    pub(crate) output: PathBuf,
+}
+
+/// Compare how common each tag is between two summaries
+#[derive(FromArgs)]
+#[argh(subcommand, name = "diff")]
+pub(crate) struct DiffOpts {
+    /// the earlier summary
+    #[argh(positional)]
+    pub(crate) before: PathBuf,
+
+    /// the later summary
+    #[argh(positional)]
+    pub(crate) after: PathBuf,
+
+    /// the number of tags to list, biggest changes first
+    #[argh(option, default = "20")]
+    pub(crate) top: usize,
+}
+
+/// Check a list of sites, and optionally a config file, without crawling anything
+#[derive(FromArgs)]
+#[argh(subcommand, name = "validate")]
+pub(crate) struct ValidateOpts {
+    /// the list of sites to check
+    #[argh(positional)]
+    pub(crate) sites: PathBuf,
+
+    /// a TOML configuration file to check
+    #[argh(option, short = 'c')]
+    pub(crate) config: Option<PathBuf>,
+}
+
+/// Hand out a list of sites to workers on other machines, and collect what they find
+#[derive(FromArgs)]
+#[argh(subcommand, name = "coordinate")]
+pub(crate) struct CoordinateOpts {
+    /// the list of sites to hand out
+    #[argh(positional)]
+    pub(crate) sites: PathBuf,
+
+    /// the address to listen for workers on, e.g. `0.0.0.0:7878` for workers on other
+    /// machines (default: `127.0.0.1:7878`)
+    #[argh(option, default = "SocketAddr::from(([127, 0, 0, 1], 7878))")]
+    pub(crate) listen: SocketAddr,
+
+    /// the token workers have to present, shared with them; better left to the
+    /// `QUOTELEMENTA_COORDINATOR_TOKEN` environment variable, so that other users can't see it
+    #[argh(option)]
+    pub(crate) token: Option<String>,
+
+    /// how long a worker gets to send back a site's results before the site is handed out
+    /// again, e.g. `10m` (default)
+    #[argh(
+        option,
+        from_str_fn(parse_duration),
+        default = "Duration::from_secs(10 * 60)"
+    )]
+    pub(crate) lease_timeout: Duration,
+
+    /// the file per-site results are written to, as JSON lines
+    #[argh(option, short = 'o', default = "PathBuf::from(\"results.jsonl\")")]
+    pub(crate) output: PathBuf,
+
+    /// the file the tag counts added up across all workers are written to
+    #[argh(option, default = "PathBuf::from(\"summary.json\")")]
+    pub(crate) summary: PathBuf,
+}
+
+/// Time how fast both engines get through a bundled set of pages, served locally
+#[derive(FromArgs)]
+#[argh(subcommand, name = "bench")]
+pub(crate) struct BenchOpts {
+    /// the WebDriver binary to benchmark the WebDriver engine with; without it, only the
+    /// snapshot engine is benchmarked
+    #[argh(positional)]
+    pub(crate) driver: Option<PathBuf>,
+
+    /// how many times each page is crawled
+    #[argh(option, default = "25")]
+    pub(crate) rounds: usize,
+
+    /// the number of WebDriver sessions running concurrently
+    #[argh(option, short = 'n', default = "1")]
+    pub(crate) workers: Port,
+
+    /// the port of the first WebDriver, with the others counting up from it
+    #[argh(option, short = 'p', default = "4444")]
+    pub(crate) base_port: Port,
+}
+
+/// The path with `{run}` replaced by the run's ID, so that each run can get files of its own.
+fn run_path(path: &Path, run: Ulid) -> PathBuf {
+    path.to_str().map_or_else(
+        || path.to_owned(),
+        |p| p.replace("{run}", &run.to_string()).into(),
+    )
+}
+
+/// The latest run with a file at the path, whose file name has `{run}` standing in for the
+/// run's ID.
+fn latest_run(path: &Path) -> Option<Ulid> {
+    let (prefix, suffix) = path.file_name()?.to_str()?.split_once("{run}")?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // IDs sort by when the run started
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_prefix(prefix)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resume(output: &Path) -> CrawlOpts {
+        let output = output.to_str().unwrap();
+        CrawlOpts::from_args(&["resume"], &["-o", output, "sites.csv"]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resumes_the_latest_run_with_results_of_its_own() {
+        let dir = tempfile::tempdir().unwrap();
+        let (earlier, later) = (Ulid::from_parts(1, 0), Ulid::from_parts(2, 0));
+        for run in [later, earlier] {
+            std::fs::write(dir.path().join(format!("results-{run}.jsonl")), "").unwrap();
+        }
+        std::fs::write(dir.path().join("results.jsonl"), "").unwrap();
+
+        let opts = resume(&dir.path().join("results-{run}.jsonl"));
+        let run_dir = RunDir::open(dir.path());
+        assert_eq!(opts.resumed_run(&run_dir).await.unwrap(), later);
+        assert_eq!(
+            opts.results(&run_dir, later),
+            dir.path().join(format!("results-{later}.jsonl"))
+        );
+    }
+
+    #[tokio::test]
+    async fn cant_resume_a_run_without_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = resume(&dir.path().join("results-{run}.jsonl"));
+        assert!(opts.resumed_run(&RunDir::open(dir.path())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn resumes_the_run_recorded_in_shared_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let results = dir.path().join("results.jsonl");
+        let run = Ulid::from_parts(1, 0);
+        let record = format!(r#"{{"url":"https://example.com/","crawled_at":0,"run":"{run}"}}"#);
+        std::fs::write(&results, record + "\n").unwrap();
+
+        let opts = resume(&results);
+        assert_eq!(
+            opts.resumed_run(&RunDir::open(dir.path())).await.unwrap(),
+            run
+        );
+    }
+}
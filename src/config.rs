@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eyre::{Context, Result};
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    analyzer::Builtin,
+    driver::DriverKind,
+    tui::{Action, KeyBinding, Keymap, Theme},
+    util::{Capabilities, Port},
+};
+
+/// Settings that are too unwieldy to pass as command-line flags.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    /// HTTP basic-auth credentials, keyed by domain.
+    /// A domain's credentials also apply to all of its subdomains.
+    pub credentials: HashMap<String, Credentials>,
+
+    /// Maximum number of elements analyzed per page.
+    /// Pages with more elements are sampled uniformly.
+    pub max_elements: Option<usize>,
+
+    /// Maximum number of page loads per second, across all crawlers
+    pub max_rps: Option<f64>,
+
+    /// Maximum number of bytes transferred per second, across all crawlers, as far as the
+    /// pages' resource timing tells
+    pub max_bandwidth: Option<u64>,
+
+    /// How failed navigations are retried
+    pub retry: RetryPolicy,
+
+    /// When crawlers start over with a fresh browser
+    pub recycle: RecyclePolicy,
+
+    /// How crawlers that fail to start are started again
+    pub respawn: RespawnPolicy,
+
+    /// When crawlers are added and retired, if their number is left to the crawl
+    pub autoscale: AutoscalePolicy,
+
+    /// When walking a page is given up on for being too slow
+    pub slow_sites: SlowSitePolicy,
+
+    /// Whether to leave sites that redirect to a different domain out of the statistics
+    pub exclude_off_domain_redirects: bool,
+
+    /// Whether to leave sites that look like parked domains or placeholders out of the
+    /// statistics
+    pub exclude_parked: bool,
+
+    /// Whether to leave sites whose robots.txt disallows crawling them out, without loading
+    /// them at all
+    pub respect_robots_txt: bool,
+
+    /// Directory the WebDrivers write their logs into, instead of the working directory
+    pub driver_log_dir: Option<PathBuf>,
+
+    /// Directory to save each page's serialized DOM into
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// A JavaScript file run in every page, whose return value is recorded with the site.
+    ///
+    /// It's the body of a function, as with WebDriver's Execute Script, so it has to `return`
+    /// something JSON can represent, or a promise of it.
+    pub eval_script: Option<PathBuf>,
+
+    /// A TOML or JSON file listing the tags to count, instead of the built-in HTML ones
+    pub vocabulary: Option<PathBuf>,
+
+    /// The analyzers run on every element, in order: any of `tags`, `geometry`, `unknown_tags`
+    /// and `accessibility`
+    pub analyzers: Vec<Builtin>,
+
+    /// Lua scripts run on every page, whose metrics are recorded for every site,
+    /// see [`script`](crate::script)
+    pub scripts: Vec<PathBuf>,
+
+    /// WebAssembly modules run on every page, sandboxed, whose metrics are recorded for every site,
+    /// see [`plugin`](crate::plugin)
+    pub plugins: Vec<PathBuf>,
+
+    /// Tags whose size and position on the page are recorded
+    pub geometry_tags: Vec<String>,
+
+    /// Whether to guess each page's language from its text,
+    /// in addition to reading what it declares
+    pub guess_language: bool,
+
+    /// How many of the most common `class` tokens to keep track of
+    pub top_classes: usize,
+
+    /// How many of the domains with the most pages to keep the tag counts of
+    pub top_domains: usize,
+
+    /// How many of the most common custom element names to keep track of
+    pub top_custom_elements: usize,
+
+    /// How many of the most common unrecognized tag names to keep track of
+    pub top_unknown_tags: usize,
+
+    /// The colors the terminal UI is drawn in
+    pub theme: Theme,
+
+    /// Keys for terminal UI actions, replacing the default keys of each action listed,
+    /// e.g. `quit = ["q", "ctrl-c"]`
+    pub keys: HashMap<Action, Vec<KeyBinding>>,
+
+    /// A Selenium Grid or Moon hub to create every session against, instead of spawning WebDrivers
+    pub grid: Option<Url>,
+
+    /// Capabilities asked of every WebDriver session on top of the default ones,
+    /// e.g. `browserName` to pick a browser from a grid, or `"moon:options"`.
+    /// These replace any default capability of the same name.
+    pub capabilities: Capabilities,
+
+    /// Kinds of browsers to split the crawlers between, e.g. to compare engines in a single run.
+    /// When given, these decide how many crawlers there are.
+    pub profiles: Vec<Profile>,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            credentials: HashMap::default(),
+            max_elements: None,
+            max_rps: None,
+            max_bandwidth: None,
+            retry: RetryPolicy::default(),
+            recycle: RecyclePolicy::default(),
+            respawn: RespawnPolicy::default(),
+            autoscale: AutoscalePolicy::default(),
+            slow_sites: SlowSitePolicy::default(),
+            exclude_off_domain_redirects: false,
+            exclude_parked: false,
+            respect_robots_txt: false,
+            driver_log_dir: None,
+            snapshot_dir: None,
+            eval_script: None,
+            vocabulary: None,
+            analyzers: Builtin::ALL.to_vec(),
+            scripts: Vec::new(),
+            plugins: Vec::new(),
+            geometry_tags: vec!["div".to_owned()],
+            guess_language: false,
+            top_classes: 1000,
+            top_domains: 1000,
+            top_custom_elements: 1000,
+            top_unknown_tags: 1000,
+            theme: Theme::default(),
+            keys: HashMap::new(),
+            grid: None,
+            capabilities: Capabilities::new(),
+            profiles: Vec::new(),
+        }
+    }
+}
+
+/// A browser that some of the crawlers run, e.g.
+///
+/// ```toml
+/// [[profiles]]
+/// name = "firefox"
+/// workers = 2
+/// driver = "geckodriver"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Recorded with each site crawled by this profile
+    pub name: String,
+    /// How many crawlers to start with this profile
+    pub workers: Port,
+    /// The WebDriver binary to run, instead of the one given on the command line
+    pub driver: Option<PathBuf>,
+    /// An already running WebDriver to connect to, instead of the one given on the command line
+    pub remote: Option<Url>,
+    /// Which kind of driver it is, instead of guessing it from the binary's name
+    pub kind: Option<DriverKind>,
+    /// Capabilities asked of this profile's sessions, on top of all the others
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Total number of navigation attempts per site, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled after every further attempt
+    pub backoff_ms: u64,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff_ms: 1000,
+        }
+    }
+}
+impl RetryPolicy {
+    /// The delay before the given retry, counting from 0, which only stops doubling once it
+    /// no longer fits.
+    #[must_use]
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2_u64.checked_pow(retry).unwrap_or(u64::MAX);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecyclePolicy {
+    /// Number of sites after which the browser is restarted, since long sessions degrade
+    pub after_sites: Option<u32>,
+    /// Seconds between checks that the browser still responds, made between sites.
+    /// One that doesn't is restarted.
+    pub ping_interval_secs: u64,
+}
+impl Default for RecyclePolicy {
+    fn default() -> Self {
+        Self {
+            after_sites: None,
+            ping_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RespawnPolicy {
+    /// Number of times in a row a crawler may fail to start before it's given up on
+    pub attempts: u32,
+    /// Delay before the first respawn in milliseconds, doubled after every further failure
+    pub backoff_ms: u64,
+    /// Number of failed starts across all crawlers after which the whole crawl is abandoned
+    pub give_up_after: Option<u32>,
+    /// Whether to keep a crawler started but idle, to take over at once from one that crashes
+    /// rather than make it wait for a browser to start
+    pub warm_spare: bool,
+}
+impl Default for RespawnPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            backoff_ms: 1000,
+            give_up_after: None,
+            warm_spare: false,
+        }
+    }
+}
+impl RespawnPolicy {
+    /// The delay before respawning a crawler that failed to start the given number of times in
+    /// a row, which only stops doubling once it no longer fits.
+    #[must_use]
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2_u64
+            .checked_pow(failures.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoscalePolicy {
+    /// Number of crawlers to start with, and never to go below
+    pub min_workers: Port,
+    /// Number of crawlers never to go above, by default one per CPU core
+    pub max_workers: Option<Port>,
+    /// Seconds between decisions, each adding or retiring at most one crawler
+    pub interval_secs: u64,
+    /// Seconds sites may wait in the queue for a crawler on average before another is added
+    pub max_queue_wait_secs: f64,
+    /// Share of the sites crawled since the last decision that may fail before crawlers are retired
+    pub max_error_rate: f64,
+    /// Share of the machine's CPU time that may be busy before crawlers are retired
+    pub max_cpu: f64,
+    /// Share of the machine's memory to keep available, retiring crawlers below it
+    pub min_available_memory: f64,
+}
+impl Default for AutoscalePolicy {
+    fn default() -> Self {
+        Self {
+            min_workers: 1,
+            max_workers: None,
+            interval_secs: 10,
+            max_queue_wait_secs: 1.0,
+            max_error_rate: 0.2,
+            max_cpu: 0.9,
+            min_available_memory: 0.15,
+        }
+    }
+}
+impl AutoscalePolicy {
+    /// The most crawlers there may be, as configured or going by the number of CPU cores.
+    #[must_use]
+    pub fn max_workers(&self) -> Port {
+        let cores = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+        self.max_workers
+            .unwrap_or_else(|| Port::try_from(cores).unwrap_or(Port::MAX))
+            .max(self.min_workers)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlowSitePolicy {
+    /// Elements per second below which walking a page is given up on, leaving the site out of
+    /// the statistics as too slow
+    pub min_walk_rate: Option<f64>,
+    /// Seconds a page is walked for before its rate counts, as the first elements can be slow
+    /// on any page
+    pub grace_secs: u64,
+}
+impl Default for SlowSitePolicy {
+    fn default() -> Self {
+        Self {
+            min_walk_rate: None,
+            grace_secs: 5,
+        }
+    }
+}
+impl SlowSitePolicy {
+    /// Whether walking a page has gone on long enough, and slowly enough, to give up on it.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn is_too_slow(&self, walked: usize, elapsed: Duration) -> bool {
+        let Some(min_rate) = self.min_walk_rate else {
+            return false;
+        };
+        elapsed >= Duration::from_secs(self.grace_secs)
+            && (walked as f64 / elapsed.as_secs_f64()) < min_rate
+    }
+}
+
+impl Config {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let s = tokio::fs::read_to_string(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read config file {}", path.display()))?;
+        let config: Self = toml::from_str(&s).wrap_err("Failed to parse config file")?;
+        // better now than once the terminal UI is up, with a key quietly doing the wrong thing
+        Keymap::new(&config.keys).wrap_err("Invalid keys in config file")?;
+        Ok(config)
+    }
+
+    /// Finds the credentials for the most specific domain matching the URL's host.
+    #[must_use]
+    pub fn credentials_for(&self, url: &Url) -> Option<&Credentials> {
+        let mut host = url.host_str()?;
+        loop {
+            if let Some(creds) = self.credentials.get(host) {
+                return Some(creds);
+            }
+            (_, host) = host.split_once('.')?;
+        }
+    }
+
+    /// Returns the URL to navigate to, with credentials embedded if there are any.
+    #[must_use]
+    pub fn authenticate(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        if let Some(creds) = self.credentials_for(&url) {
+            // only fails for URLs that cannot have a host, which we never crawl
+            let _ = url.set_username(&creds.username);
+            let _ = url.set_password(Some(&creds.password));
+        }
+        url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_without_overflowing() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(64), Duration::from_millis(u64::MAX));
+        let policy = RetryPolicy {
+            backoff_ms: u64::MAX,
+            ..policy
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(u64::MAX));
+    }
+
+    #[test]
+    fn respawns_back_off_without_overflowing() {
+        let policy = RespawnPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(u64::MAX));
+        let policy = RespawnPolicy {
+            backoff_ms: u64::MAX,
+            ..policy
+        };
+        assert_eq!(policy.backoff(2), Duration::from_millis(u64::MAX));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_key_bound_to_two_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        // `s` is also the default key for skipping
+        std::fs::write(&path, "[keys]\nexport = [\"s\"]\n").unwrap();
+        assert!(Config::load(&path).await.is_err());
+
+        std::fs::write(&path, "[keys]\nexport = [\"s\"]\nskip = [\"k\"]\n").unwrap();
+        assert!(Config::load(&path).await.is_ok());
+    }
+}
@@ -0,0 +1,66 @@
+use std::{cmp::Reverse, sync::Arc};
+
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::vocabulary::Vocabulary;
+
+/// How often each tag directly contains each other tag, aggregated across all sites.
+#[derive(Clone, Debug)]
+pub struct Cooccurrence {
+    vocabulary: Vocabulary,
+    /// A square matrix with a row and column per tag, indexed by `[parent * len + child]`
+    inner: Arc<RwLock<Vec<u64>>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Pair {
+    pub parent: String,
+    pub child: String,
+    pub count: u64,
+}
+
+impl Cooccurrence {
+    #[must_use]
+    pub fn new(vocabulary: Vocabulary) -> Self {
+        let len = vocabulary.len();
+        Self {
+            vocabulary,
+            inner: Arc::new(RwLock::new(vec![0; len * len])),
+        }
+    }
+
+    /// Adds counts keyed by `parent>child` tag names. Unrecognized tags are skipped.
+    pub async fn add_all(&self, pairs: impl IntoIterator<Item = (String, u64)>) {
+        let len = self.vocabulary.len();
+        let mut inner = self.inner.write().await;
+        for (pair, n) in pairs {
+            let Some((parent, child)) = pair.split_once('>') else {
+                continue;
+            };
+            if let (Some(parent), Some(child)) =
+                (self.vocabulary.get(parent), self.vocabulary.get(child))
+            {
+                inner[parent.index() * len + child.index()] += n;
+            }
+        }
+    }
+
+    /// All pairs that occurred at least once, most common first.
+    pub async fn pairs(&self) -> Vec<Pair> {
+        let names: Vec<_> = self.vocabulary.names().collect();
+        let inner = self.inner.read().await;
+        let mut pairs: Vec<_> = inner
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(i, &count)| Pair {
+                parent: names[i / names.len()].to_owned(),
+                child: names[i % names.len()].to_owned(),
+                count,
+            })
+            .collect();
+        pairs.sort_by_key(|p| Reverse(p.count));
+        pairs
+    }
+}
@@ -0,0 +1,809 @@
+//! Spreading one list of sites across crawlers on many machines.
+//!
+//! A [`Coordinator`] reads the list and hands out sites over HTTP to workers, which are
+//! ordinary crawls started with a [coordinator](crate::CrawlSessionBuilder::coordinator),
+//! each with WebDrivers of its own. Workers send back every site's record along with its tag
+//! counts as they go, and say so once they run out of sites.
+//!
+//! Sites are leased rather than given away: one that isn't reported back in time, say because
+//! its worker died, is handed out again. Only the first report of a site counts, so that sites
+//! handed out more than once aren't counted twice. Sites are told apart by their index in the
+//! list, so that the same URL listed twice is crawled twice, like it would be without a
+//! coordinator, while a line listed twice is only crawled once.
+//!
+//! Workers have to present the token the coordinator was started with.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, DefaultBodyLimit, Query, State as Shared},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use eyre::{bail, Context, ContextCompat, Result};
+use hyper::{Body, Method};
+use hyper_tls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter, Lines},
+    sync::{Mutex, Notify},
+    time::Instant,
+};
+use tracing::*;
+use url::Url;
+
+use crate::{
+    assigner::parse_line,
+    report::Census,
+    state::SiteRecord,
+    util::{HttpClient, USER_AGENT},
+    ShutdownRx,
+};
+
+/// How long workers wait before asking again, when every site left is leased to someone else
+pub const JOB_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where the token is taken from when it isn't given on the command line, which
+/// keeps it out of the process list
+pub const TOKEN_VAR: &str = "QUOTELEMENTA_COORDINATOR_TOKEN";
+
+/// How often leases and workers are checked for having run out, whether or not anyone asks
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The most sites handed out at once, however many a worker asks for
+const MAX_BATCH: usize = 1024;
+
+/// The largest request accepted, which a site's record and tag counts stay well below
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// A site's index in the list
+pub type SiteId = u64;
+
+/// The token given, or else the one in [`TOKEN_VAR`].
+pub fn token(given: Option<String>) -> Result<String> {
+    let token = given
+        .or_else(|| std::env::var(TOKEN_VAR).ok())
+        .filter(|token| !token.is_empty())
+        .wrap_err_with(|| {
+            format!("A token shared with the workers is required - set {TOKEN_VAR}")
+        })?;
+    Ok(token)
+}
+
+/// Turns a line of the sites list, like `1,example.com`, into the site's index and URL.
+fn parse_site(line: &str) -> Result<(SiteId, Url)> {
+    let (index, _) = line
+        .split_once(',')
+        .wrap_err("Expected an index and a domain separated by a comma")?;
+    let id = index
+        .trim()
+        .parse()
+        .wrap_err("Expected the index of a site to be a number")?;
+    Ok((id, parse_line(line)?))
+}
+
+/// What the coordinator says about the crawl as a whole.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Status {
+    /// The number of sites in the list
+    pub total: usize,
+    /// The number of sites reported back by workers
+    pub completed: usize,
+    /// The number of sites handed out and not reported back yet
+    pub leased: usize,
+}
+
+/// A site handed out to a worker.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: SiteId,
+    pub url: Url,
+}
+
+/// Sites handed out to a worker.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Jobs {
+    pub sites: Vec<Job>,
+    /// Whether every site has been crawled, and so there won't ever be any more
+    pub done: bool,
+}
+
+/// What a worker sends back about a site.
+#[derive(Serialize, Deserialize)]
+struct Report<R> {
+    /// Which site it was
+    site: SiteId,
+    /// The site's record, written out as is
+    record: R,
+    /// The site's tag counts, added up across all sites reported back
+    census: Census,
+}
+
+/// Which worker a request comes from, alongside its address.
+#[derive(Deserialize)]
+struct WorkerParams {
+    #[serde(default)]
+    worker: String,
+    n: Option<usize>,
+}
+
+/// Hands out sites to workers and collects what they found.
+pub struct Coordinator {
+    state: Mutex<State>,
+    lease_timeout: Duration,
+    token: String,
+    summary: PathBuf,
+    /// Notified once every site is crawled and every worker has said it's finished
+    done: Notify,
+}
+struct State {
+    source: Lines<BufReader<File>>,
+    /// The sites read from the list so far, to leave out lines listed again
+    read: HashSet<SiteId>,
+    exhausted: bool,
+    /// The number of distinct sites in the list
+    total: usize,
+    completed: usize,
+    /// Sites handed out and not reported back yet, with when and to whom
+    leases: HashMap<SiteId, Lease>,
+    /// Sites whose lease ran out, to be handed out again first
+    expired: VecDeque<Job>,
+    records: BufWriter<File>,
+    /// The tag counts of every site reported back
+    census: Census,
+    /// Every worker that was handed sites, except for ones presumed dead
+    workers: HashMap<String, Worker>,
+}
+struct Worker {
+    /// Whether it has said it's finished
+    finished: bool,
+    /// When it was last heard from
+    seen: Instant,
+}
+struct Lease {
+    site: Job,
+    since: Instant,
+    worker: String,
+}
+impl Coordinator {
+    /// Sets up to hand out the sites in the list to workers presenting the token, writing
+    /// the records that come back to `output`, and the tag counts of all sites to `summary`
+    /// once they're all in.
+    pub async fn new(
+        sites: &Path,
+        output: &Path,
+        summary: PathBuf,
+        lease_timeout: Duration,
+        token: String,
+    ) -> Result<Self> {
+        let list = tokio::fs::read_to_string(sites)
+            .await
+            .wrap_err_with(|| format!("Failed to read {}", sites.display()))?;
+        let ids: HashSet<_> = list
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| parse_site(l).map(|(id, _)| id))
+            .collect::<Result<_>>()?;
+        let source = BufReader::new(File::open(sites).await?).lines();
+        let records = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(output)
+            .await?;
+
+        Ok(Self {
+            state: Mutex::new(State {
+                source,
+                read: HashSet::new(),
+                exhausted: false,
+                total: ids.len(),
+                completed: 0,
+                leases: HashMap::new(),
+                expired: VecDeque::new(),
+                records: BufWriter::new(records),
+                census: Census::default(),
+                workers: HashMap::new(),
+            }),
+            lease_timeout,
+            token,
+            summary,
+            done: Notify::new(),
+        })
+    }
+
+    /// Serves workers until every site is crawled and every worker has said it's finished,
+    /// or until shut down, and then writes the summary.
+    pub async fn serve(self, addr: SocketAddr, mut shutdown_rx: ShutdownRx) -> Result<()> {
+        let this = Arc::new(self);
+
+        let service = this
+            .clone()
+            .router()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let server = axum::Server::try_bind(&addr)?.serve(service);
+        info!(addr = %server.local_addr(), "Coordinator listening");
+
+        let reaper = this.clone();
+        let reaper = tokio::spawn(
+            async move {
+                loop {
+                    tokio::time::sleep(REAP_INTERVAL).await;
+                    reaper.reap().await;
+                }
+            }
+            .in_current_span(),
+        );
+
+        let done = this.clone();
+        let server = server
+            .with_graceful_shutdown(async move {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => info!("Shutdown received - stopping"),
+                    () = done.done.notified() => info!("All sites crawled"),
+                }
+            })
+            .await;
+        reaper.abort();
+        server?;
+
+        let mut state = this.state.lock().await;
+        state.records.flush().await?;
+        tokio::fs::write(&this.summary, serde_json::to_vec_pretty(&state.census)?).await?;
+        info!(completed = state.completed, "Summary written");
+        Ok(())
+    }
+
+    /// The routes workers use, which expect to know the address of whoever's asking.
+    fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/status", get(status))
+            .route("/jobs", post(jobs))
+            .route("/results", post(results))
+            .route("/finished", post(finished))
+            .layer(middleware::from_fn_with_state(self.clone(), authorize))
+            .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+            .with_state(self)
+    }
+
+    async fn status(&self) -> Result<Status> {
+        let state = self.state.lock().await;
+        Ok(Status {
+            total: state.total,
+            completed: state.completed,
+            leased: state.leases.len(),
+        })
+    }
+
+    /// Hands out the sites of leases that ran out again, and stops waiting for workers that
+    /// went quiet, finishing the crawl if that was all it was waiting for.
+    async fn reap(&self) {
+        let mut state = self.state.lock().await;
+        self.expire(&mut state, Instant::now());
+        self.check_done(&state);
+    }
+
+    fn expire(&self, state: &mut State, now: Instant) {
+        let expired: Vec<_> = state
+            .leases
+            .iter()
+            .filter(|(_, lease)| now.duration_since(lease.since) > self.lease_timeout)
+            .map(|(&id, _)| id)
+            .collect();
+        // a worker that's only slow keeps its other leases, and is waited for like any other
+        for id in expired {
+            let Lease { site, worker, .. } = state.leases.remove(&id).unwrap();
+            warn!(site = %site.url, %worker, "Lease ran out - handing the site out again");
+            state.expired.push_back(site);
+        }
+        // workers ask for sites every so often until they're done, so one that's been quiet
+        // for as long as a lease lasts has died, even if it had no leases left
+        state.workers.retain(|worker, w| {
+            let alive = w.finished || now.duration_since(w.seen) <= self.lease_timeout;
+            if !alive {
+                warn!(%worker, "Worker went quiet - no longer waiting for it");
+            }
+            alive
+        });
+    }
+
+    /// Lets the server stop once every site is crawled and every worker left has finished.
+    fn check_done(&self, state: &State) {
+        let done = state.exhausted
+            && state.expired.is_empty()
+            && state.leases.is_empty()
+            && state.workers.values().all(|w| w.finished);
+        if done {
+            self.done.notify_one();
+        }
+    }
+
+    /// Takes down that the worker is still around.
+    fn seen(state: &mut State, worker: &str, now: Instant) {
+        if let Some(w) = state.workers.get_mut(worker) {
+            w.seen = now;
+        }
+    }
+
+    async fn jobs(&self, worker: String, n: usize) -> Result<Jobs> {
+        let mut state = self.state.lock().await;
+        let state = &mut *state;
+
+        let now = Instant::now();
+        self.expire(state, now);
+        Self::seen(state, &worker, now);
+
+        let n = n.min(MAX_BATCH);
+        let mut sites = Vec::with_capacity(n);
+        while sites.len() < n {
+            let site = if let Some(site) = state.expired.pop_front() {
+                site
+            } else if state.exhausted {
+                break;
+            } else {
+                let Some(line) = state.source.next_line().await? else {
+                    state.exhausted = true;
+                    continue;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let (id, url) = parse_site(&line)?;
+                if !state.read.insert(id) {
+                    debug!(id, "Site listed again - leaving it out");
+                    continue;
+                }
+                Job { id, url }
+            };
+            let lease = Lease {
+                site: site.clone(),
+                since: now,
+                worker: worker.clone(),
+            };
+            state.leases.insert(site.id, lease);
+            sites.push(site);
+        }
+        if !sites.is_empty() {
+            state.workers.entry(worker.clone()).or_insert(Worker {
+                finished: false,
+                seen: now,
+            });
+        }
+
+        let done = state.exhausted && state.expired.is_empty() && state.leases.is_empty();
+        debug!(%worker, sites = sites.len(), done, "Handing out sites");
+        Ok(Jobs { sites, done })
+    }
+
+    async fn result(&self, worker: String, body: &Bytes) -> Result<()> {
+        let Report {
+            site,
+            record,
+            census,
+        } = serde_json::from_slice::<Report<&RawValue>>(body)?;
+        let mut state = self.state.lock().await;
+        Self::seen(&mut state, &worker, Instant::now());
+        if state.leases.remove(&site).is_none() {
+            // a lease that ran out still counts, as long as nobody else has reported the site back
+            let Some(i) = state.expired.iter().position(|job| job.id == site) else {
+                debug!(site, "Record for a site that isn't leased");
+                return Ok(());
+            };
+            state.expired.remove(i);
+        }
+        state.completed += 1;
+        state.census.merge(&census);
+        state.records.write_all(record.get().as_bytes()).await?;
+        state.records.write_all(b"\n").await?;
+        state.records.flush().await?;
+        self.check_done(&state);
+        Ok(())
+    }
+
+    async fn finished(&self, worker: String) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.workers.insert(
+            worker.clone(),
+            Worker {
+                finished: true,
+                seen: Instant::now(),
+            },
+        );
+        info!(%worker, "Worker finished");
+        self.check_done(&state);
+        Ok(())
+    }
+}
+
+/// Lets through only the requests that present the coordinator's token.
+async fn authorize<B>(
+    Shared(this): Shared<Arc<Coordinator>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(token) if same(token.as_bytes(), this.token.as_bytes()) => next.run(req).await,
+        _ => {
+            warn!("Request without the right token");
+            (StatusCode::UNAUTHORIZED, "Missing or wrong token").into_response()
+        }
+    }
+}
+
+/// Whether the tokens are the same, taking as long to tell whichever byte they differ in.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn status(Shared(this): Shared<Arc<Coordinator>>) -> Response {
+    respond(this.status().await)
+}
+
+async fn jobs(
+    Shared(this): Shared<Arc<Coordinator>>,
+    peer: ConnectInfo<SocketAddr>,
+    Query(params): Query<WorkerParams>,
+) -> Response {
+    let n = params.n.unwrap_or(1);
+    respond(this.jobs(worker(peer, &params), n).await)
+}
+
+async fn results(
+    Shared(this): Shared<Arc<Coordinator>>,
+    peer: ConnectInfo<SocketAddr>,
+    Query(params): Query<WorkerParams>,
+    body: Bytes,
+) -> Response {
+    respond(this.result(worker(peer, &params), &body).await)
+}
+
+async fn finished(
+    Shared(this): Shared<Arc<Coordinator>>,
+    peer: ConnectInfo<SocketAddr>,
+    Query(params): Query<WorkerParams>,
+) -> Response {
+    respond(this.finished(worker(peer, &params)).await)
+}
+
+/// Names a worker by its address and what it calls itself, since the same worker process
+/// number can well turn up on different machines.
+fn worker(ConnectInfo(peer): ConnectInfo<SocketAddr>, params: &WorkerParams) -> String {
+    format!("{}/{}", peer.ip(), params.worker)
+}
+
+fn respond<T: Serialize>(res: Result<T>) -> Response {
+    match res {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => {
+            warn!(%e, "Bad request from worker");
+            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+    }
+}
+
+/// How a worker talks to the [`Coordinator`].
+#[derive(Clone, Debug)]
+pub struct CoordinatorClient {
+    http: HttpClient,
+    url: Url,
+    token: String,
+    /// Tells this worker apart from others on the same machine
+    worker: u32,
+    /// The sites leased to this worker and not reported back yet, by URL, since the same URL
+    /// may be listed more than once
+    leased: Arc<std::sync::Mutex<HashMap<String, VecDeque<SiteId>>>>,
+}
+impl CoordinatorClient {
+    /// Connects to the coordinator with its token, returning how it's getting along.
+    pub async fn connect(url: Url, token: String) -> Result<(Self, Status)> {
+        let this = Self {
+            http: hyper::Client::builder().build(HttpsConnector::new()),
+            url,
+            token,
+            worker: std::process::id(),
+            leased: Arc::default(),
+        };
+        let status = this
+            .status()
+            .await
+            .wrap_err_with(|| format!("Failed to reach the coordinator at {}", this.url))?;
+        info!(url = %this.url, total = status.total, "Connected to coordinator");
+        Ok((this, status))
+    }
+
+    /// Asks how the crawl as a whole is getting along.
+    pub async fn status(&self) -> Result<Status> {
+        let body = self
+            .request(Method::GET, "/status", &[], Body::empty())
+            .await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Asks for up to `n` sites to crawl.
+    pub async fn jobs(&self, n: usize) -> Result<Jobs> {
+        let body = self
+            .request(
+                Method::POST,
+                "/jobs",
+                &[("n", &n.to_string())],
+                Body::empty(),
+            )
+            .await?;
+        let jobs: Jobs = serde_json::from_slice(&body)?;
+        let mut leased = self.leased.lock().unwrap();
+        for job in &jobs.sites {
+            let ids = leased.entry(job.url.to_string()).or_default();
+            ids.push_back(job.id);
+        }
+        Ok(jobs)
+    }
+
+    /// Sends back what was found on a site, and its tag counts.
+    pub async fn send_record(&self, record: &SiteRecord, census: Census) -> Result<()> {
+        let site = {
+            let mut leased = self.leased.lock().unwrap();
+            let ids = leased
+                .get_mut(&record.url)
+                .wrap_err_with(|| format!("{} wasn't leased to this worker", record.url))?;
+            let site = ids
+                .pop_front()
+                .expect("sites with no leases left are removed");
+            if ids.is_empty() {
+                leased.remove(&record.url);
+            }
+            site
+        };
+        let body = serde_json::to_vec(&Report {
+            site,
+            record,
+            census,
+        })?;
+        self.request(Method::POST, "/results", &[], body.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Says this worker is done, and won't send back any more sites.
+    pub async fn finish(&self) -> Result<()> {
+        self.request(Method::POST, "/finished", &[], Body::empty())
+            .await?;
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, &str)],
+        body: Body,
+    ) -> Result<Bytes> {
+        let mut url = self.url.join(path)?;
+        url.query_pairs_mut()
+            .append_pair("worker", &self.worker.to_string())
+            .extend_pairs(params);
+        let req = Request::builder()
+            .method(method)
+            .uri(url.as_str())
+            .header(header::USER_AGENT, USER_AGENT)
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.token))
+            .body(body)?;
+        let res = self.http.request(req).await?;
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        if !status.is_success() {
+            bail!(
+                "Coordinator answered {status}: {}",
+                String::from_utf8_lossy(&body)
+            );
+        }
+        Ok(body)
+    }
+}
+
+/// Sites leased from a [`Coordinator`], fetched a batch at a time.
+pub struct RemoteSites {
+    client: CoordinatorClient,
+    batch: usize,
+    buffer: VecDeque<Url>,
+}
+impl RemoteSites {
+    #[must_use]
+    pub fn new(client: CoordinatorClient, batch: usize) -> Self {
+        Self {
+            client,
+            batch: batch.max(1),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// The next site to crawl, waiting for the coordinator to have one if need be,
+    /// or `None` once every site has been crawled.
+    pub async fn next(&mut self) -> Result<Option<Url>> {
+        loop {
+            if let Some(site) = self.buffer.pop_front() {
+                return Ok(Some(site));
+            }
+            let Jobs { sites, done } = self.client.jobs(self.batch).await?;
+            if sites.is_empty() {
+                if done {
+                    return Ok(None);
+                }
+                tokio::time::sleep(JOB_POLL_INTERVAL).await;
+                continue;
+            }
+            self.buffer.extend(sites.into_iter().map(|job| job.url));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::connect_info::MockConnectInfo;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+    const LEASE_TIMEOUT: Duration = Duration::from_secs(90);
+
+    /// A coordinator for the list, and the directory its files are in.
+    async fn coordinator(list: &str) -> (TempDir, Arc<Coordinator>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        std::fs::write(path.join("sites.csv"), list).unwrap();
+        let coordinator = Coordinator::new(
+            &path.join("sites.csv"),
+            &path.join("results.jsonl"),
+            path.join("summary.json"),
+            LEASE_TIMEOUT,
+            TOKEN.to_owned(),
+        )
+        .await
+        .unwrap();
+        (dir, Arc::new(coordinator))
+    }
+
+    async fn send(
+        coordinator: &Arc<Coordinator>,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: Vec<u8>,
+    ) -> (StatusCode, serde_json::Value) {
+        let router = coordinator
+            .clone()
+            .router()
+            .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        let mut req = Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let res = router
+            .oneshot(req.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn only_serves_workers_with_the_token() {
+        let (_dir, coordinator) = coordinator("1,example.com\n").await;
+        let (status, _) = send(&coordinator, "GET", "/status", None, vec![]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(&coordinator, "POST", "/jobs", Some("guess"), vec![]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = send(&coordinator, "GET", "/status", Some(TOKEN), vec![]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total"], 1);
+    }
+
+    #[tokio::test]
+    async fn leases_sites_sharing_a_url_separately() {
+        let list = "1,http://a.example/\n2,http://a.example/\n2,http://a.example/\n";
+        let (_dir, coordinator) = coordinator(list).await;
+        let (_, status) = send(&coordinator, "GET", "/status", Some(TOKEN), vec![]).await;
+        assert_eq!(status["total"], 2);
+
+        let (_, jobs) = send(&coordinator, "POST", "/jobs?n=5", Some(TOKEN), vec![]).await;
+        let ids: Vec<_> = jobs["sites"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|job| job["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, [1, 2]);
+
+        let report = serde_json::to_vec(&Report {
+            site: 1,
+            record: serde_json::json!({ "url": "http://a.example/" }),
+            census: Census::default(),
+        })
+        .unwrap();
+        let (status, _) = send(&coordinator, "POST", "/results", Some(TOKEN), report).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, status) = send(&coordinator, "GET", "/status", Some(TOKEN), vec![]).await;
+        assert_eq!(status["completed"], 1);
+        assert_eq!(status["leased"], 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_a_slow_workers_other_leases() {
+        let (_dir, coordinator) = coordinator("1,a.example\n2,b.example\n").await;
+        coordinator.jobs("worker".to_owned(), 2).await.unwrap();
+
+        let mut state = coordinator.state.lock().await;
+        let now = Instant::now();
+        // leased later, by a worker that's still around, so still good when the first one runs out
+        state.leases.get_mut(&2).unwrap().since = now + LEASE_TIMEOUT;
+        state.workers.get_mut("worker").unwrap().seen = now + LEASE_TIMEOUT;
+        coordinator.expire(&mut state, now + LEASE_TIMEOUT * 3 / 2);
+
+        assert!(state.leases.contains_key(&2));
+        assert_eq!(
+            state.expired.iter().map(|job| job.id).collect::<Vec<_>>(),
+            [1]
+        );
+        assert!(state.workers.contains_key("worker"));
+    }
+
+    #[tokio::test]
+    async fn hands_out_at_most_a_batch() {
+        let (_dir, coordinator) = coordinator("1,example.com\n").await;
+        let path = format!("/jobs?n={}", usize::MAX);
+        let (status, jobs) = send(&coordinator, "POST", &path, Some(TOKEN), vec![]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(jobs["sites"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn takes_reports_of_sites_whose_lease_ran_out() {
+        let (_dir, coordinator) = coordinator("1,example.com\n").await;
+        coordinator.jobs("worker".to_owned(), 1).await.unwrap();
+        {
+            let mut state = coordinator.state.lock().await;
+            coordinator.expire(&mut state, Instant::now() + LEASE_TIMEOUT * 2);
+            assert_eq!(state.expired.len(), 1);
+        }
+
+        let report = serde_json::to_vec(&Report {
+            site: 1,
+            record: serde_json::json!({ "url": "https://example.com/" }),
+            census: Census::default(),
+        })
+        .unwrap();
+        let (status, _) = send(&coordinator, "POST", "/results", Some(TOKEN), report).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let state = coordinator.state.lock().await;
+        assert_eq!(state.completed, 1);
+        assert!(state.expired.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_oversized_requests() {
+        let (_dir, coordinator) = coordinator("1,example.com\n").await;
+        let body = vec![b' '; MAX_BODY_SIZE + 1];
+        let (status, _) = send(&coordinator, "POST", "/results", Some(TOKEN), body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
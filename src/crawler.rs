@@ -1,29 +1,236 @@
-use std::{fmt::Display, path::PathBuf, process::Stdio};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
 use eyre::{Context, Result};
-use fantoccini::{wd::Capabilities, Client, ClientBuilder, Locator};
-use futures_util::{StreamExt, TryStreamExt};
+use fantoccini::{error::CmdError, wd::Capabilities, Client, ClientBuilder, Locator};
+use serde::Serialize;
 use tokio::{
     process::{Child, Command},
-    sync::mpsc,
+    sync::{
+        broadcast::{
+            self,
+            error::{RecvError, TryRecvError},
+        },
+        watch,
+    },
 };
 use tracing::*;
 use url::Url;
 
 use crate::{
-    state::{Output, State},
-    util::Port,
-    JobQueue, ShutdownRx,
+    accessibility, bandwidth,
+    config::Config,
+    custom,
+    driver::DriverKind,
+    fetch::Fetcher,
+    fingerprint, foreign, forms, language,
+    limiter::{BandwidthLimiter, RateLimiter},
+    meta, metrics, obsolete, parked,
+    redirect::Redirects,
+    resources::{DriverPids, ProcessTree, Signal},
+    robots,
+    security::Security,
+    semantic, snapshot,
+    state::{Output, SiteRecord, State},
+    tables,
+    timing::Timing,
+    util::{without_credentials, JobQueue, Port, USER_AGENT},
+    ShutdownRx,
 };
 
-#[derive(Clone, Debug)]
-pub struct CrawlerReport {
-    pub port: Port,
-    pub state: CrawlerState,
+/// Requests from the UI to change the set of running crawlers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// Start another crawler
+    AddWorker,
+    /// Have some crawler stop after its current site
+    RetireWorker,
+    /// Abandon the site the crawler on the given port is working on
+    Skip(Port),
+}
+
+/// What every crawler is up to, shared between the crawlers and the UI.
+///
+/// Crawlers overwrite their own entry rather than sending a message for every change,
+/// so the UI only ever has to look at the latest state, however many crawlers there are.
+#[derive(Clone, Debug, Default)]
+pub struct Dashboard {
+    inner: Arc<Mutex<DashboardInner>>,
+    /// The queue the crawlers take their sites from, if the engine has one
+    queue: Arc<OnceLock<JobQueue>>,
+    /// Whether the crawlers are to hold off on taking new sites
+    paused: Arc<watch::Sender<bool>>,
+}
+#[derive(Clone, Debug, Default)]
+pub struct DashboardInner {
+    pub crawlers: BTreeMap<Port, CrawlerState>,
+    /// Sites completed so far, across all crawlers, and the ones [left out](Self::left_out)
+    pub completed: usize,
+    /// Sites recorded without ever being handed to a crawler, e.g. ones whose domain doesn't
+    /// resolve
+    pub left_out: usize,
+    /// Sites crawled before resuming, which `completed` leaves out
+    pub resumed: usize,
+    /// Crawlers started again after failing to start
+    pub respawns: usize,
+    /// Crawlers that failed to start too many times in a row to try again
+    pub given_up: usize,
+    /// Where the crawlers' time went so far
+    pub utilization: Utilization,
+    /// How full the queue of sites is, if the engine has one
+    pub queue: Option<QueueDepth>,
+    /// Sites handed to crawlers so far, if the engine has a queue to hand them out from
+    pub assigned: Option<usize>,
+    /// Whether the crawlers hold off on taking new sites
+    pub paused: bool,
+}
+impl DashboardInner {
+    /// Sites crawled so far, including the ones from before resuming.
+    #[must_use]
+    pub fn crawled(&self) -> usize {
+        self.resumed + self.completed
+    }
+}
+impl Dashboard {
+    pub fn report(&self, port: Port, state: CrawlerState) {
+        let mut inner = self.inner.lock().unwrap();
+        match state {
+            // the crawler keeps showing its last site until it starts on the next one
+            CrawlerState::Complete => inner.completed += 1,
+            CrawlerState::Terminated => {
+                inner.crawlers.remove(&port);
+            }
+            state => {
+                inner.crawlers.insert(port, state);
+            }
+        }
+    }
+    /// A site was recorded without being handed to a crawler.
+    pub fn left_out(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.completed += 1;
+        inner.left_out += 1;
+    }
+    pub fn respawned(&self) {
+        self.inner.lock().unwrap().respawns += 1;
+    }
+    pub fn gave_up(&self) {
+        self.inner.lock().unwrap().given_up += 1;
+    }
+    /// Picks up where an interrupted crawl left off, after this many sites.
+    pub fn resume_from(&self, sites: usize) {
+        self.inner.lock().unwrap().resumed = sites;
+    }
+    /// A crawler waited this long for a site to crawl.
+    pub fn waited(&self, waited: Duration) {
+        self.inner.lock().unwrap().utilization.waiting += waited;
+    }
+    /// A crawler is done with a site, which took as long as it says.
+    pub fn busy(&self, timing: &Timing) {
+        let ms = |ms: Option<u64>| Duration::from_millis(ms.unwrap_or_default());
+        let (navigating, walking) = (ms(timing.navigation_ms), ms(timing.walk_ms));
+        let total = Duration::from_millis(timing.total_ms);
+
+        let utilization = &mut self.inner.lock().unwrap().utilization;
+        utilization.navigating += navigating;
+        utilization.walking += walking;
+        utilization.other += total.saturating_sub(navigating + walking);
+    }
+    /// Keeps an eye on how full the queue is.
+    pub fn watch_queue(&self, queue: JobQueue) {
+        let _ = self.queue.set(queue);
+    }
+    /// Has the crawlers finish their current sites, and hold off on taking new ones until
+    /// [resumed](Self::resume), returning whether they were running until now.
+    #[must_use]
+    pub fn pause(&self) -> bool {
+        !self.paused.send_replace(true)
+    }
+    /// Lets paused crawlers take new sites again, returning whether they were paused.
+    #[must_use]
+    pub fn resume(&self) -> bool {
+        self.paused.send_replace(false)
+    }
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+    /// Waits until the crawlers aren't paused, if they are.
+    pub async fn unpaused(&self) {
+        // the sender is right here, so the receiver can't be left without one
+        let _ = self.paused.subscribe().wait_for(|paused| !paused).await;
+    }
+    #[must_use]
+    pub fn get(&self) -> DashboardInner {
+        let mut inner = self.inner.lock().unwrap().clone();
+        inner.paused = self.is_paused();
+        if let Some(queue) = self.queue.get() {
+            inner.queue = Some(QueueDepth {
+                waiting: queue.len(),
+                capacity: queue.capacity(),
+            });
+            inner.assigned = Some(queue.assigned());
+        }
+        inner
+    }
+}
+
+/// How the crawlers spent their time, added up across crawlers.
+///
+/// Mostly waiting means the assigner can't keep up, mostly navigating means the network
+/// or the sites are slow, and mostly walking means analyzing the DOM is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Utilization {
+    /// Waiting for a site to crawl
+    pub waiting: Duration,
+    /// Loading pages, retries and dialogs included
+    pub navigating: Duration,
+    /// Handing the pages' elements to the analyzers
+    pub walking: Duration,
+    /// Everything else about crawling sites, e.g. following redirects or measuring pages
+    pub other: Duration,
+}
+impl Utilization {
+    #[must_use]
+    pub fn busy(&self) -> Duration {
+        self.navigating + self.walking + self.other
+    }
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.waiting + self.busy()
+    }
+    /// The part of all the time that went to `part`, or 0 before there's been any.
+    #[must_use]
+    pub fn share(&self, part: Duration) -> f64 {
+        let total = self.total();
+        if total.is_zero() {
+            0.0
+        } else {
+            part.as_secs_f64() / total.as_secs_f64()
+        }
+    }
+}
+
+/// How many sites are waiting for a crawler, out of how many fit in the queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct QueueDepth {
+    /// Sites put back by crawlers count too, so this can go over the capacity
+    pub waiting: usize,
+    pub capacity: usize,
 }
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum CrawlerState {
     Initializing,
+    Connected(Browser),
     InProgress(String),
     Complete,
     ShuttingDown,
@@ -33,6 +240,7 @@ impl Display for CrawlerState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Initializing => write!(f, "Initializing..."),
+            Self::Connected(browser) => write!(f, "Connected to {browser}"),
             Self::InProgress(url) => write!(f, "{url}"),
             Self::Complete => write!(f, "Complete!"),
             Self::ShuttingDown => write!(f, "Shutting down..."),
@@ -41,74 +249,284 @@ impl Display for CrawlerState {
     }
 }
 
+/// The browser behind a WebDriver session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Browser {
+    pub name: String,
+    pub version: String,
+}
+impl Browser {
+    /// Works out the browser from its user agent, since fantoccini
+    /// does not expose the capabilities the session was created with.
+    pub async fn detect(client: &Client) -> Result<Self> {
+        let v = client.execute(BROWSER_SCRIPT, vec![]).await?;
+        let (name, version) = serde_json::from_value(v)?;
+        Ok(Self { name, version })
+    }
+}
+impl Display for Browser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+/// Where a crawler gets its browser from.
+#[derive(Clone, Debug)]
+pub enum WebDriver {
+    /// Run this WebDriver binary on the crawler's port
+    Spawn { path: PathBuf, kind: DriverKind },
+    /// Connect to a WebDriver that's already running elsewhere, e.g. in a container
+    Remote { url: Url, kind: DriverKind },
+}
+impl WebDriver {
+    /// Runs the binary, as the kind of driver its name suggests unless told otherwise.
+    #[must_use]
+    pub fn spawn(path: PathBuf, kind: Option<DriverKind>) -> Self {
+        let kind = kind.unwrap_or_else(|| DriverKind::detect(&path));
+        Self::Spawn { path, kind }
+    }
+    /// Connects to the URL, with a [generic](DriverKind::Generic) driver unless told otherwise.
+    #[must_use]
+    pub fn remote(url: Url, kind: Option<DriverKind>) -> Self {
+        Self::Remote {
+            url,
+            kind: kind.unwrap_or_default(),
+        }
+    }
+    /// The same WebDriver, treated as another kind of driver.
+    #[must_use]
+    pub fn with_kind(mut self, new: DriverKind) -> Self {
+        match &mut self {
+            Self::Spawn { kind, .. } | Self::Remote { kind, .. } => *kind = new,
+        }
+        self
+    }
+    #[must_use]
+    pub fn kind(&self) -> DriverKind {
+        match self {
+            Self::Spawn { kind, .. } | Self::Remote { kind, .. } => *kind,
+        }
+    }
+}
+impl Display for WebDriver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Spawn { path, .. } => write!(f, "{}", path.display()),
+            Self::Remote { url, .. } => write!(f, "{url}"),
+        }
+    }
+}
+
+/// Everything shared between all crawlers.
+#[derive(Clone, Debug)]
+pub struct Shared {
+    pub config: Arc<Config>,
+    pub output: Output,
+    pub job_queue: JobQueue,
+    pub limiter: Option<Arc<RateLimiter>>,
+    pub bandwidth_limiter: Option<Arc<BandwidthLimiter>>,
+    pub dashboard: Dashboard,
+    /// How many crawlers still have to retire, whichever ones get to it first
+    pub retiring: Arc<AtomicUsize>,
+    /// Ports of crawlers that should give up on their current site
+    pub skip_tx: broadcast::Sender<Port>,
+    pub driver_pids: DriverPids,
+    /// When to stop taking on new sites
+    pub deadline: Option<Instant>,
+    /// The contents of the [eval script](Config::eval_script)
+    pub eval_script: Option<Arc<str>>,
+}
+
+/// Extracts the browser name and version from the user agent.
+const BROWSER_SCRIPT: &str = r#"
+const ua = navigator.userAgent;
+const browsers = [
+    ["edge", /Edg\/([\d.]+)/],
+    ["chrome", /Chrome\/([\d.]+)/],
+    ["firefox", /Firefox\/([\d.]+)/],
+    ["safari", /Version\/([\d.]+).*Safari/],
+];
+for (const [name, re] of browsers) {
+    const m = ua.match(re);
+    if (m) return [name, m[1]];
+}
+return ["unknown", ua];
+"#;
+
+/// How long a browser gets to answer before its session is considered dead
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a WebDriver and its browser get to exit by themselves before they're killed
+const DRIVER_KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// How much of a WebDriver's log to show when it fails to start
+const LOG_TAIL_LINES: usize = 20;
+
+/// The last lines of a log file, or a note saying why there aren't any.
+async fn log_tail(path: &Path) -> String {
+    match tokio::fs::read_to_string(path).await {
+        Ok(log) if log.trim().is_empty() => "(empty)".to_owned(),
+        Ok(log) => {
+            let lines: Vec<_> = log.lines().collect();
+            lines[lines.len().saturating_sub(LOG_TAIL_LINES)..].join("\n")
+        }
+        Err(e) => format!("(unreadable: {e})"),
+    }
+}
+
+/// Gets the HTTP status code of the main document, or 0 if the browser doesn't expose it.
+const NAVIGATION_STATUS_SCRIPT: &str = r#"
+const nav = performance.getEntriesByType("navigation")[0];
+return (nav && nav.responseStatus) || 0;
+"#;
+
 pub struct Crawler {
+    /// Identifies the crawler, and is also where its WebDriver listens if it spawned one
     port: Port,
-    driver: Child,
+    /// The WebDriver process, unless it's a remote one
+    driver: Option<Child>,
+    /// Where the WebDriver came from, and what was asked of it, to start over the same way
+    webdriver: WebDriver,
+    capabilities: Capabilities,
     client: Client,
+    browser: Option<Browser>,
+    profile: Option<String>,
+    fetcher: Fetcher,
+    config: Arc<Config>,
     pub state: State,
 
     job_queue: JobQueue,
-    report_tx: mpsc::Sender<CrawlerReport>,
+    limiter: Option<Arc<RateLimiter>>,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>,
+    dashboard: Dashboard,
+    retiring: Arc<AtomicUsize>,
+    skip_tx: broadcast::Sender<Port>,
+    driver_pids: DriverPids,
+    deadline: Option<Instant>,
+    eval_script: Option<Arc<str>>,
+
+    /// Sites crawled since the browser was last (re)started
+    sites_since_restart: u32,
+    pinged_at: Instant,
+    /// A site to try again after the browser died while crawling it
+    retry: Option<Url>,
 }
 impl Crawler {
     #[tracing::instrument(skip_all, fields(port = port))]
     pub async fn new(
-        driver: PathBuf,
+        driver: WebDriver,
         port: Port,
-        output: Output,
-        job_queue: JobQueue,
         capabilities: Capabilities,
-        report_tx: mpsc::Sender<CrawlerReport>,
+        profile: Option<String>,
+        shared: Shared,
     ) -> Result<Self> {
+        let Shared {
+            config,
+            output,
+            job_queue,
+            limiter,
+            bandwidth_limiter,
+            dashboard,
+            retiring,
+            skip_tx,
+            driver_pids,
+            deadline,
+            eval_script,
+        } = shared;
+
         info!("Initializing crawler instance");
-        report_tx
-            .send(CrawlerReport {
-                port,
-                state: CrawlerState::Initializing,
-            })
-            .await
-            .expect("UI should still be alive");
-
-        match Self::init_session(port, driver, capabilities, output).await {
-            Ok((driver, client, state)) => Ok(Self {
-                port,
-                driver,
-                client,
-                state,
-                job_queue,
-                report_tx,
-            }),
+        dashboard.report(port, CrawlerState::Initializing);
+
+        let webdriver = driver.clone();
+        let log_dir = config.driver_log_dir.as_deref().unwrap_or(Path::new("."));
+        match Self::init_session(port, driver, capabilities.clone(), output, log_dir).await {
+            Ok((driver, client, state)) => {
+                if let Some(pid) = driver.as_ref().and_then(Child::id) {
+                    driver_pids.insert(port, pid);
+                }
+                let browser = Self::detect_browser(port, &client, &dashboard).await;
+                Ok(Self {
+                    port,
+                    driver,
+                    webdriver,
+                    capabilities,
+                    client,
+                    browser,
+                    profile,
+                    fetcher: Fetcher::new(limiter.clone(), config.clone()),
+                    config,
+                    state,
+                    job_queue,
+                    limiter,
+                    bandwidth_limiter,
+                    dashboard,
+                    retiring,
+                    skip_tx,
+                    driver_pids,
+                    deadline,
+                    eval_script,
+                    sites_since_restart: 0,
+                    pinged_at: Instant::now(),
+                    retry: None,
+                })
+            }
             Err(e) => {
-                report_tx
-                    .send(CrawlerReport {
-                        port,
-                        state: CrawlerState::Terminated,
-                    })
-                    .await
-                    .expect("UI should still be alive");
+                dashboard.report(port, CrawlerState::Terminated);
                 Err(e)
             }
         }
     }
+    async fn detect_browser(port: Port, client: &Client, dashboard: &Dashboard) -> Option<Browser> {
+        let browser = match Browser::detect(client).await {
+            Ok(browser) => browser,
+            Err(e) => {
+                warn!(%e, "Failed to detect browser");
+                return None;
+            }
+        };
+        info!(%browser, "Browser detected");
+        dashboard.report(port, CrawlerState::Connected(browser.clone()));
+        Some(browser)
+    }
     async fn init_session(
         port: Port,
-        driver: PathBuf,
+        driver: WebDriver,
         capabilities: Capabilities,
         output: Output,
-    ) -> Result<(Child, Client, State)> {
-        let log_path = format!("webdriver-{port}.log");
-        let log_file = std::fs::File::create(&log_path)?;
-        debug!(?log_path, "WebDriver log file created");
-
-        let driver = Command::new(driver)
-            .arg(format!("--port={port}"))
-            .stdout(Stdio::from(log_file.try_clone()?))
-            .stderr(Stdio::from(log_file))
-            .kill_on_drop(true)
-            .spawn()?;
-        debug!(id = driver.id(), "WebDriver spawned");
-
-        let url = format!("http://localhost:{port}");
+        log_dir: &Path,
+    ) -> Result<(Option<Child>, Client, State)> {
+        let (driver, url) = match driver {
+            WebDriver::Spawn { path, kind } => {
+                tokio::fs::create_dir_all(log_dir).await?;
+                let log_path = log_dir.join(format!("webdriver-{port}.log"));
+                let log_file = std::fs::File::create(&log_path)?;
+                debug!(?log_path, "WebDriver log file created");
+
+                let mut command = std::process::Command::new(path);
+                command
+                    .args(kind.args(port))
+                    .stdout(Stdio::from(log_file.try_clone()?))
+                    .stderr(Stdio::from(log_file));
+                // so that the browser can be killed along with it, and Ctrl-C in a terminal
+                // doesn't reach it before we had a chance to close the session
+                #[cfg(unix)]
+                std::os::unix::process::CommandExt::process_group(&mut command, 0);
+                let mut driver = Command::from(command).kill_on_drop(true).spawn()?;
+                debug!(id = driver.id(), "WebDriver spawned");
+
+                let url = format!("http://localhost:{port}");
+                if let Err(e) = kind.wait_until_ready(&url, &mut driver).await {
+                    let tail = log_tail(&log_path).await;
+                    return Err(e.wrap_err(format!(
+                        "WebDriver on port {port} failed to start. The end of {}:\n{tail}",
+                        log_path.display()
+                    )));
+                }
+                (Some(driver), url)
+            }
+            WebDriver::Remote { url, .. } => (None, url.to_string()),
+        };
+
         let client = ClientBuilder::native()
             .capabilities(capabilities)
             .connect(&url)
@@ -117,7 +535,7 @@ impl Crawler {
 
         info!(?url, "Crawler instance initialized");
 
-        client.set_ua("Quotelementa-Crawler").await?;
+        client.set_ua(USER_AGENT).await?;
 
         let state = State::new(output, &client).await?;
 
@@ -126,97 +544,593 @@ impl Crawler {
 
     #[tracing::instrument(skip_all, fields(port = self.port))]
     pub async fn run(mut self, mut shutdown_rx: ShutdownRx) -> Result<()> {
-        loop {
-            tokio::select! {
-                _ = shutdown_rx.changed() => {
-                    info!("Shutdown received - exiting");
-                    break;
-                }
-                res = self.crawl_loop() => match res {
-                    Ok(()) => break,
-                    Err(e) => return Err(e),
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                info!("Shutdown received - exiting");
+            }
+            res = self.crawl_loop() => {
+                // don't take a site that was going to be retried down with us
+                if let (Err(_), Some(site)) = (&res, self.retry.take()) {
+                    self.job_queue.put_back(site);
                 }
+                res?;
             }
         }
 
-        self.report_tx
-            .send(CrawlerReport {
-                port: self.port,
-                state: CrawlerState::ShuttingDown,
-            })
-            .await?;
+        self.dashboard.report(self.port, CrawlerState::ShuttingDown);
 
         tokio::select! {
             _ = shutdown_rx.changed() => {
                 info!("Forcibly shutting down!");
             }
-            res = self.client.close() => res?,
+            res = self.client.clone().close() => res?,
         }
 
-        self.driver.start_kill()?;
-        self.report_tx
-            .send(CrawlerReport {
-                port: self.port,
-                state: CrawlerState::Terminated,
-            })
-            .await?;
+        self.stop_driver().await?;
+        self.dashboard.report(self.port, CrawlerState::Terminated);
 
         Ok(())
     }
 
+    /// Ends the session and stops the WebDriver without crawling anything, e.g. for a spare
+    /// that was never needed.
+    #[tracing::instrument(skip_all, fields(port = self.port))]
+    pub async fn close(mut self) -> Result<()> {
+        self.dashboard.report(self.port, CrawlerState::ShuttingDown);
+        self.client.clone().close().await?;
+        self.stop_driver().await?;
+        self.dashboard.report(self.port, CrawlerState::Terminated);
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     async fn crawl_loop(&mut self) -> Result<()> {
-        while let Some(site) = self.job_queue.try_pop() {
-            if let Err(e) = self.crawl(site).await {
-                error!(%e, "Error while crawling");
+        let mut skip_rx = self.skip_tx.subscribe();
+        let port = self.port;
+
+        loop {
+            // a site to retry has to be done before retiring, or nobody would crawl it
+            let retrying = self.retry.is_some();
+            let site = if let Some(site) = self.retry.take() {
+                site
+            } else {
+                let retire =
+                    self.retiring
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+                if retire.is_ok() {
+                    info!("Retiring as requested");
+                    return Ok(());
+                }
+                // paused crawlers hold off here, between sites
+                self.dashboard.unpaused().await;
+                if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                    info!("Out of time - not taking on any more sites");
+                    break;
+                }
+                // waits for the assigner to catch up, and only comes up empty once it's done
+                let waiting = Instant::now();
+                let site = self.job_queue.pop().await;
+                self.dashboard.waited(waiting.elapsed());
+                let Some(site) = site else {
+                    break;
+                };
+                site
+            };
+            // a browser that fails to restart mustn't take the site down with it
+            self.retry = Some(site.clone());
+            self.maintain().await?;
+            self.retry = None;
+
+            let mut record = SiteRecord::new(site.to_string());
+            record.browser = self.browser.clone();
+            record.profile = self.profile.clone();
+
+            // don't let requests from while we were between sites skip this one
+            while !matches!(
+                skip_rx.try_recv(),
+                Err(TryRecvError::Empty | TryRecvError::Closed)
+            ) {}
+
+            // dropping the crawl midway is fine - at worst the browser finishes loading
+            // a page nobody is interested in anymore, before being sent to the next one
+            let started = Instant::now();
+            let res = tokio::select! {
+                res = self.crawl(site.clone(), &mut record) => Some(res),
+                () = skip_requested(&mut skip_rx, port) => None,
+            };
+            self.sites_since_restart += 1;
+            match res {
+                Some(Ok(())) => {}
+                // only retried once, in case it's the site that brings the browser down
+                Some(Err(e)) if !retrying && !self.is_alive().await => {
+                    warn!(%e, "Browser died while crawling - restarting it to try again");
+                    self.retry = Some(site);
+                    self.restart().await?;
+                    continue;
+                }
+                Some(Err(e)) => {
+                    error!(%e, "Error while crawling");
+                    record.error = Some(format!("{e:#}"));
+                    self.state.discard_page();
+                }
+                None => {
+                    info!("Skipping site as requested");
+                    record.skipped = true;
+                    self.state.discard_page();
+                }
             }
+            record.timing.total_ms = Timing::millis(started.elapsed());
+            self.dashboard.busy(&record.timing);
+            self.state.output.record(record);
 
-            self.report_tx
-                .send(CrawlerReport {
-                    port: self.port,
-                    state: CrawlerState::Complete,
-                })
-                .await?;
+            self.dashboard.report(self.port, CrawlerState::Complete);
         }
 
         info!("No work remains - I'm done!");
         Ok(())
     }
 
+    /// Takes down what the site transferred, and takes it out of the bandwidth budget.
+    ///
+    /// Only meaningful once navigation succeeded, since until then the browser is still
+    /// showing the previous site.
+    async fn account_transfer(&self, record: &mut SiteRecord) {
+        // a page that's still busy loading can be slow to answer, and this isn't worth waiting on
+        match tokio::time::timeout(PING_TIMEOUT, bandwidth::transferred(&self.client)).await {
+            Ok(Ok(bytes)) => {
+                record.transferred_bytes = Some(bytes);
+                if let Some(limiter) = &self.bandwidth_limiter {
+                    limiter.spend(bytes);
+                }
+            }
+            Ok(Err(e)) => debug!(%e, "Failed to measure what the site transferred"),
+            Err(_) => debug!("Timed out measuring what the site transferred"),
+        }
+    }
+
+    /// Restarts the browser if it's been running for too long, or stopped responding.
+    async fn maintain(&mut self) -> Result<()> {
+        let recycle = &self.config.recycle;
+        if recycle
+            .after_sites
+            .is_some_and(|n| self.sites_since_restart >= n)
+        {
+            info!(sites = self.sites_since_restart, "Recycling the browser");
+            return self.restart().await;
+        }
+        if self.pinged_at.elapsed() >= Duration::from_secs(recycle.ping_interval_secs) {
+            if !self.is_alive().await {
+                warn!("Browser stopped responding - restarting it");
+                return self.restart().await;
+            }
+            self.pinged_at = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Whether the browser still answers commands.
+    async fn is_alive(&self) -> bool {
+        matches!(
+            tokio::time::timeout(PING_TIMEOUT, self.client.current_url()).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Stops the WebDriver, if we spawned it, along with anything it started,
+    /// giving them a moment to exit by themselves first.
+    async fn stop_driver(&mut self) -> Result<()> {
+        let Some(driver) = &mut self.driver else {
+            return Ok(());
+        };
+        if let Some(pid) = driver.id() {
+            let tree = ProcessTree::of(pid);
+            tree.signal(Signal::Terminate);
+            let _ = tokio::time::timeout(DRIVER_KILL_GRACE, driver.wait()).await;
+            tree.signal(Signal::Kill);
+        }
+        if driver.try_wait()?.is_none() {
+            driver.kill().await?;
+        }
+        self.driver_pids.remove(self.port);
+        Ok(())
+    }
+
+    /// Throws away the browser, and its WebDriver if we spawned it, and starts over.
+    async fn restart(&mut self) -> Result<()> {
+        self.dashboard.report(self.port, CrawlerState::Initializing);
+
+        // the session may well be gone already, in which case there's nothing to close
+        let _ = tokio::time::timeout(PING_TIMEOUT, self.client.clone().close()).await;
+        self.stop_driver().await?;
+
+        let (driver, client, state) = Self::init_session(
+            self.port,
+            self.webdriver.clone(),
+            self.capabilities.clone(),
+            self.state.output.clone(),
+            self.config
+                .driver_log_dir
+                .as_deref()
+                .unwrap_or(Path::new(".")),
+        )
+        .await?;
+        if let Some(pid) = driver.as_ref().and_then(Child::id) {
+            self.driver_pids.insert(self.port, pid);
+        }
+        self.driver = driver;
+        self.client = client;
+        self.state = state;
+        self.browser = Self::detect_browser(self.port, &self.client, &self.dashboard).await;
+        self.sites_since_restart = 0;
+        self.pinged_at = Instant::now();
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all, fields(url = url.as_str()))]
-    async fn crawl(&mut self, url: Url) -> Result<()> {
+    async fn crawl(&mut self, url: Url, record: &mut SiteRecord) -> Result<()> {
         info!(?url, ?self.port, "Start crawling");
 
-        self.report_tx
-            .send(CrawlerReport {
-                port: self.port,
-                state: CrawlerState::InProgress(
-                    url.as_str().trim_start_matches("https://").to_owned(),
-                ),
-            })
-            .await?;
-
-        self.client
-            .goto(url.as_str())
-            .await
-            .wrap_err("Failed to navigate to site")?;
+        self.dashboard.report(
+            self.port,
+            CrawlerState::InProgress(url.as_str().trim_start_matches("https://").to_owned()),
+        );
+
+        if self.config.respect_robots_txt {
+            match robots::allows(&self.fetcher, &url).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("Site disallows crawling it in robots.txt - excluding");
+                    record.excluded = Some(robots::EXCLUSION.to_owned());
+                    return Ok(());
+                }
+                Err(e) => warn!(%e, "Failed to check robots.txt - crawling anyway"),
+            }
+        }
+
+        let started = Instant::now();
+        self.navigate(&url, record).await?;
+        self.dismiss_dialogs(record).await?;
+        record.timing.navigation_ms = Some(Timing::millis(started.elapsed()));
+        self.account_transfer(record).await;
 
+        // browsers may well keep the credentials they navigated with in the URL
+        let landed = without_credentials(&self.client.current_url().await?);
+        let redirects = Redirects::trace(&self.fetcher, &url, &landed).await;
+        let off_domain = redirects.off_domain;
+        record.redirects = Some(redirects);
+
+        if off_domain && self.config.exclude_off_domain_redirects {
+            info!(%landed, "Site redirected to a different domain - excluding");
+            record.excluded = Some("off-domain redirect".to_owned());
+            return Ok(());
+        }
+
+        match parked::signs(&self.client, &url, &landed).await {
+            Ok(signs) => record.parked = signs,
+            Err(e) => warn!(%e, "Failed to check whether the domain is parked"),
+        }
+        if parked::is_parked(&record.parked) && self.config.exclude_parked {
+            info!(signs = ?record.parked, "Site looks parked - excluding");
+            record.excluded = Some("parked domain".to_owned());
+            return Ok(());
+        }
+
+        match Security::inspect(&self.client, &self.fetcher).await {
+            Ok(security) => record.security = Some(security),
+            Err(e) => warn!(%e, "Failed to inspect the site's security"),
+        }
+
+        match fingerprint::frameworks(&self.client).await {
+            Ok(frameworks) => record.frameworks = frameworks,
+            Err(e) => warn!(%e, "Failed to detect frameworks"),
+        }
+        match fingerprint::script_origins(&self.client, &url).await {
+            Ok(origins) => {
+                record.third_party_script_origins = origins.third_party;
+                record.trackers = origins.trackers;
+            }
+            Err(e) => warn!(%e, "Failed to classify script origins"),
+        }
+
+        if let Some(dir) = &self.config.snapshot_dir {
+            match snapshot::save(&self.client, &url, dir).await {
+                Ok(path) => record.snapshot = Some(path),
+                Err(e) => warn!(%e, "Failed to save DOM snapshot"),
+            }
+        }
+
+        let started = Instant::now();
+        let walked = self.walk(record).await?;
+        record.timing.walk_ms = Some(Timing::millis(started.elapsed()));
+        if walked {
+            self.measure_page(record).await;
+        }
+        Ok(())
+    }
+
+    /// Hands every element of the page's body, or a sample of them, to the analyzers,
+    /// returning whether it got through them, rather than giving up on the page as too slow.
+    #[tracing::instrument(skip_all)]
+    async fn walk(&mut self, record: &mut SiteRecord) -> Result<bool> {
         let element = self
             .client
             .find(Locator::Css("body"))
             .await
             .wrap_err("No body element found - how?")?;
-        let elements = element
+        let mut elements = element
             .find_all(Locator::Css("*"))
             .await
             .wrap_err("Looks like body element is empty?")?;
 
-        self.state = futures_util::stream::iter(elements)
-            .map(Ok::<_, eyre::Report>)
-            .try_fold(std::mem::take(&mut self.state), State::accept_node)
-            .await?;
+        record.elements = elements.len();
+        if let Some(max) = self.config.max_elements {
+            if elements.len() > max {
+                info!(count = elements.len(), max, "Too many elements - sampling");
+                elements = sample_uniformly(elements, max);
+                record.truncated = true;
+            }
+        }
+
+        let total = elements.len();
+        let started = Instant::now();
+        for (i, element) in elements.into_iter().enumerate() {
+            self.state.accept_node(element).await?;
+            let (walked, elapsed) = (i + 1, started.elapsed());
+            if walked < total && self.config.slow_sites.is_too_slow(walked, elapsed) {
+                #[allow(clippy::cast_precision_loss)]
+                let rate = walked as f64 / elapsed.as_secs_f64();
+                info!(
+                    walked,
+                    total, rate, "Walking the page too slowly - excluding"
+                );
+                record.excluded = Some("too slow".to_owned());
+                self.state.discard_page();
+                return Ok(false);
+            }
+        }
+        self.state.finish_page(record).await;
+        Ok(true)
+    }
+
+    /// Takes the measurements that look at the page as a whole, through scripts run in it.
+    #[tracing::instrument(skip_all)]
+    async fn measure_page(&mut self, record: &mut SiteRecord) {
+        match metrics::class_tokens(&self.client).await {
+            Ok(tokens) => self.state.output.classes.add_all(tokens).await,
+            Err(e) => warn!(%e, "Failed to count class tokens"),
+        }
+        match obsolete::count(&self.client).await {
+            Ok(counts) => {
+                self.state.output.obsolete.add(&record.url, &counts).await;
+                record.obsolete_tags = counts;
+            }
+            Err(e) => warn!(%e, "Failed to count obsolete elements"),
+        }
+        match custom::count(&self.client).await {
+            Ok(counts) => record.set_custom_elements(&self.state.output, counts).await,
+            Err(e) => warn!(%e, "Failed to count custom elements"),
+        }
+        match foreign::count(&self.client).await {
+            Ok(counts) => {
+                record
+                    .set_foreign_elements(&self.state.output, counts)
+                    .await;
+            }
+            Err(e) => warn!(%e, "Failed to count SVG and MathML elements"),
+        }
+        match semantic::semantics(&self.client).await {
+            Ok(semantics) => record.set_semantics(&self.state.output, semantics).await,
+            Err(e) => warn!(%e, "Failed to count semantic elements"),
+        }
+        match forms::forms(&self.client).await {
+            Ok(forms) => record.set_forms(&self.state.output, forms).await,
+            Err(e) => warn!(%e, "Failed to count forms"),
+        }
+        match tables::tables(&self.client).await {
+            Ok(tables) => record.set_tables(&self.state.output, tables).await,
+            Err(e) => warn!(%e, "Failed to classify tables"),
+        }
+        match metrics::parent_child_pairs(&self.client).await {
+            Ok(pairs) => self.state.output.cooccurrence.add_all(pairs).await,
+            Err(e) => warn!(%e, "Failed to count parent-child pairs"),
+        }
+        match metrics::inline_code(&self.client).await {
+            Ok(inline_code) => record.inline_code = Some(inline_code),
+            Err(e) => warn!(%e, "Failed to count inline code"),
+        }
+        match metrics::tree_shape(&self.client).await {
+            Ok(tree) => record.tree = Some(tree),
+            Err(e) => warn!(%e, "Failed to measure element tree"),
+        }
+        match metrics::duplicate_ids(&self.client).await {
+            Ok(n) => record.duplicate_ids = Some(n),
+            Err(e) => warn!(%e, "Failed to look for duplicate ids"),
+        }
+        match metrics::text_content(&self.client).await {
+            Ok(text) => record.text = Some(text),
+            Err(e) => warn!(%e, "Failed to measure text content"),
+        }
+        match language::language(&self.client, self.config.guess_language).await {
+            Ok(language) => record.language = Some(language),
+            Err(e) => warn!(%e, "Failed to detect language"),
+        }
+        match meta::meta(&self.client).await {
+            Ok(meta) => record.meta = Some(meta),
+            Err(e) => warn!(%e, "Failed to extract meta tags"),
+        }
+        match accessibility::media_alternatives(&self.client).await {
+            Ok(media) => record.media = Some(media),
+            Err(e) => warn!(%e, "Failed to measure alt text coverage"),
+        }
+        match accessibility::aria(&self.client).await {
+            Ok(aria) => record.aria = Some(aria),
+            Err(e) => warn!(%e, "Failed to count ARIA usage"),
+        }
+        if let Some(script) = &self.eval_script {
+            match self.client.execute(script, vec![]).await {
+                Ok(value) => record.eval = Some(value),
+                Err(e) => warn!(%e, "Eval script failed"),
+            }
+        }
+    }
+
+    /// Navigates to the URL, retrying transient failures with exponential backoff.
+    #[tracing::instrument(skip_all)]
+    async fn navigate(&self, url: &Url, record: &mut SiteRecord) -> Result<()> {
+        let policy = &self.config.retry;
+        let nav_url = self.config.authenticate(url);
+
+        loop {
+            // sites that went over the bandwidth are waited out before loading more
+            if let Some(limiter) = &self.bandwidth_limiter {
+                limiter.acquire().await;
+            }
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+
+            match self.try_navigate(&nav_url, record).await {
+                Ok(()) => return Ok(()),
+                Err((true, e)) if record.retries + 1 < policy.attempts => {
+                    let backoff = policy.backoff(record.retries);
+                    warn!(%e, ?backoff, "Transient navigation failure - retrying");
+                    record.retries += 1;
+                    tokio::time::sleep(backoff).await;
+                }
+                Err((_, e)) => return Err(e),
+            }
+        }
+    }
+
+    /// Makes a single navigation attempt.
+    /// On failure, also returns whether the failure is worth retrying.
+    async fn try_navigate(
+        &self,
+        url: &Url,
+        record: &mut SiteRecord,
+    ) -> Result<(), (bool, eyre::Report)> {
+        match self.client.goto(url.as_str()).await {
+            Ok(()) => {}
+            // the dialog is dealt with later - the page itself loaded fine
+            Err(e) if is_unexpected_alert(&e) => return Ok(()),
+            Err(e) => {
+                let transient = is_transient(&e);
+                return Err((
+                    transient,
+                    eyre::Report::new(e).wrap_err("Failed to navigate to site"),
+                ));
+            }
+        }
+
+        // browsers happily render error pages, so check the status code ourselves
+        let status = self
+            .client
+            .execute(NAVIGATION_STATUS_SCRIPT, vec![])
+            .await
+            .ok()
+            .and_then(|v| v.as_u64())
+            .and_then(|v| u16::try_from(v).ok())
+            .filter(|&v| v != 0);
+        record.status = status;
+
+        match status {
+            Some(status) if status >= 500 => Err((
+                true,
+                eyre::eyre!("Site responded with server error {status}"),
+            )),
+            _ => Ok(()),
+        }
+    }
 
-        // info!("Crawling complete");
+    /// Dismisses any dialogs the page has opened, so that they don't wedge the session.
+    #[tracing::instrument(skip_all)]
+    async fn dismiss_dialogs(&self, record: &mut SiteRecord) -> Result<()> {
+        // pages can open dialogs in a loop - don't let them keep us here forever
+        const MAX_DIALOGS: u32 = 16;
+
+        while record.dialogs_dismissed < MAX_DIALOGS {
+            match self.client.get_alert_text().await {
+                Ok(text) => {
+                    debug!(text, "Dismissing unexpected dialog");
+                    self.client.dismiss_alert().await?;
+                    record.dialogs_dismissed += 1;
+                }
+                Err(CmdError::NoSuchAlert(_)) => break,
+                Err(e) => return Err(e).wrap_err("Failed to query for open dialogs"),
+            }
+        }
         Ok(())
     }
 }
+
+/// Waits until skipping the current site of the crawler on `port` is requested.
+async fn skip_requested(rx: &mut broadcast::Receiver<Port>, port: Port) {
+    loop {
+        match rx.recv().await {
+            Ok(p) if p == port => return,
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+/// Picks `n` evenly spaced items, so that all parts of the page are represented.
+fn sample_uniformly<T>(items: Vec<T>, n: usize) -> Vec<T> {
+    let len = items.len();
+    let mut next = 0;
+    let mut taken = 0;
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|&(i, _)| {
+            if taken < n && i == next {
+                taken += 1;
+                next = taken * len / n;
+                true
+            } else {
+                false
+            }
+        })
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Whether a navigation error is likely to go away when tried again later.
+fn is_transient(e: &CmdError) -> bool {
+    const PERMANENT: &[&str] = &[
+        // Chrome
+        "ERR_NAME_NOT_RESOLVED",
+        "ERR_CERT_",
+        "ERR_SSL_",
+        // Firefox
+        "dnsNotFound",
+        "nssFailure",
+        "malformedURI",
+    ];
+    const TRANSIENT: &[&str] = &[
+        // Chrome
+        "ERR_TIMED_OUT",
+        "ERR_CONNECTION_RESET",
+        "ERR_CONNECTION_CLOSED",
+        "ERR_NETWORK_CHANGED",
+        "ERR_EMPTY_RESPONSE",
+        // what Chrome makes of server errors without a page of their own
+        "ERR_HTTP_RESPONSE_CODE_FAILURE",
+        // Firefox
+        "netTimeout",
+        "netReset",
+        "netInterrupt",
+    ];
+
+    let CmdError::Standard(wd) = e else {
+        return false;
+    };
+    if PERMANENT.iter().any(|p| wd.message.contains(p)) {
+        return false;
+    }
+    wd.error() == "timeout" || TRANSIENT.iter().any(|t| wd.message.contains(t))
+}
+
+fn is_unexpected_alert(e: &CmdError) -> bool {
+    matches!(e, CmdError::Standard(wd) if wd.error() == "unexpected alert open")
+}
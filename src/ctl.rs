@@ -0,0 +1,145 @@
+//! A control socket for managing headless crawls from shell scripts, without the
+//! [status API](crate::api), e.g. `echo pause | nc -U quotelementa.sock`.
+//!
+//! Each line sent is a command, answered with a line:
+//!
+//! - `status`: how far along the crawl is, as JSON like the status API's `/progress`
+//! - `pause`: has the crawlers finish their current sites, and hold off on new ones
+//! - `resume`: lets paused crawlers carry on
+//! - `shutdown`: shuts down gracefully, as on `SIGTERM`
+//!
+//! Commands other than `status` are answered with `ok`, and anything else with `error: `
+//! and what went wrong.
+
+use std::{
+    future::Future,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use eyre::{bail, Context, Result};
+use strum::EnumString;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+use tracing::*;
+
+use crate::api::Crawl;
+
+#[derive(EnumString, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+enum Command {
+    Status,
+    Pause,
+    Resume,
+    Shutdown,
+}
+
+/// Takes commands for a running crawl.
+pub struct Ctl {
+    crawl: Crawl,
+}
+impl Ctl {
+    /// Takes commands for `crawl`.
+    #[must_use]
+    pub fn new(crawl: Crawl) -> Self {
+        Self { crawl }
+    }
+
+    /// Starts listening at `path` right away, returning the server to run, which removes
+    /// the socket once it's dropped.
+    ///
+    /// A socket left behind by a crawl that's gone is replaced, one still in use isn't.
+    pub fn serve(self, path: &Path) -> Result<impl Future<Output = ()>> {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            bail!("{} is in use by another crawl", path.display());
+        }
+        match std::fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("Failed to replace {}", path.display()))
+            }
+        }
+        let listener = UnixListener::bind(path)
+            .wrap_err_with(|| format!("Failed to listen at {}", path.display()))?;
+        info!(path = %path.display(), "Control socket listening");
+
+        let socket = Socket(path.to_owned());
+        let this = Arc::new(self);
+        Ok(async move {
+            let _socket = socket;
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let this = this.clone();
+                        tokio::spawn(
+                            async move {
+                                if let Err(e) = this.handle(stream).await {
+                                    debug!(%e, "Control connection closed");
+                                }
+                            }
+                            .in_current_span(),
+                        );
+                    }
+                    Err(e) => warn!(%e, "Failed to accept a control connection"),
+                }
+            }
+        })
+    }
+
+    async fn handle(&self, stream: UnixStream) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut reply = match line.parse() {
+                Ok(command) => self.run(command)?,
+                Err(_) => format!(
+                    "error: unknown command `{line}` - try status, pause, resume or shutdown"
+                ),
+            };
+            reply.push('\n');
+            writer.write_all(reply.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    fn run(&self, command: Command) -> Result<String> {
+        match command {
+            Command::Status => {
+                let progress = self.crawl.progress();
+                return Ok(serde_json::to_string(&progress)?);
+            }
+            Command::Pause => {
+                if self.crawl.dashboard.pause() {
+                    info!("Paused through the control socket");
+                }
+            }
+            Command::Resume => {
+                if self.crawl.dashboard.resume() {
+                    info!("Resumed through the control socket");
+                }
+            }
+            Command::Shutdown => {
+                info!("Shutdown requested through the control socket");
+                self.crawl.stop("control socket");
+            }
+        }
+        Ok("ok".to_owned())
+    }
+}
+
+/// Removes the socket file once the server's gone, so that it doesn't look like it's still
+/// listening.
+struct Socket(PathBuf);
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
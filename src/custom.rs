@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::Serialize;
+
+use crate::topk::{TopItem, TopK};
+
+/// Hyphenated names of SVG and MathML elements, which the HTML spec rules out for custom
+/// elements.
+const RESERVED_NAMES: [&str; 8] = [
+    "annotation-xml",
+    "color-profile",
+    "font-face",
+    "font-face-src",
+    "font-face-uri",
+    "font-face-format",
+    "font-face-name",
+    "missing-glyph",
+];
+
+/// Counts how often each custom element (web component) appears on the page.
+/// Only HTML elements can be custom ones, so SVG and MathML elements are left out.
+const CUSTOM_ELEMENTS_SCRIPT: &str = r#"
+const reserved = new Set(arguments[0]);
+const counts = {};
+for (const e of document.querySelectorAll("*")) {
+    if (e.namespaceURI !== "http://www.w3.org/1999/xhtml") continue;
+    if (!e.localName.includes("-") || reserved.has(e.localName)) continue;
+    counts[e.localName] = (counts[e.localName] || 0) + 1;
+}
+return counts;
+"#;
+
+/// Whether the tag name is that of a custom element, which must contain a hyphen and
+/// can't be one of the [reserved names](RESERVED_NAMES).
+#[must_use]
+pub fn is_custom(tag: &str) -> bool {
+    tag.contains('-')
+        && !RESERVED_NAMES
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(tag))
+}
+
+/// Counts the custom elements on the currently loaded page, by name.
+pub async fn count(client: &Client) -> Result<HashMap<String, u64>> {
+    let reserved = serde_json::to_value(RESERVED_NAMES)?;
+    let v = client
+        .execute(CUSTOM_ELEMENTS_SCRIPT, vec![reserved])
+        .await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    /// Number of sites looked at
+    pub sites: u64,
+    /// Number of sites using at least one custom element
+    pub adopting: u64,
+    /// Share of sites using at least one custom element
+    pub adoption: f64,
+    /// The most common custom element names
+    pub top: Vec<TopItem>,
+}
+
+/// Custom element usage, aggregated across all sites.
+#[derive(Clone, Debug)]
+pub struct CustomElements {
+    names: TopK,
+    sites: Arc<AtomicU64>,
+    adopting: Arc<AtomicU64>,
+}
+impl CustomElements {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            names: TopK::new(capacity),
+            sites: Arc::default(),
+            adopting: Arc::default(),
+        }
+    }
+
+    /// Adds a single site's custom element counts.
+    pub async fn add_site(&self, counts: HashMap<String, u64>) {
+        self.sites.fetch_add(1, Ordering::Relaxed);
+        if !counts.is_empty() {
+            self.adopting.fetch_add(1, Ordering::Relaxed);
+        }
+        self.names.add_all(counts).await;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub async fn summary(&self) -> Summary {
+        let sites = self.sites.load(Ordering::Relaxed);
+        let adopting = self.adopting.load(Ordering::Relaxed);
+        Summary {
+            sites,
+            adopting,
+            adoption: if sites == 0 {
+                0.0
+            } else {
+                adopting as f64 / sites as f64
+            },
+            top: self.names.top().await,
+        }
+    }
+}
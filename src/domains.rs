@@ -0,0 +1,163 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::vocabulary::Vocabulary;
+
+#[derive(Clone, Debug, Default)]
+struct DomainStats {
+    pages: u64,
+    /// Tag counts summed over all pages, indexed by [`Tag::index`](crate::vocabulary::Tag::index)
+    freq: Vec<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DomainSummary {
+    pub domain: String,
+    pub pages: u64,
+    /// Elements counted across all of the domain's pages
+    pub elements: u64,
+}
+
+impl DomainStats {
+    /// Adds the domain's average tag counts per page to `totals`.
+    #[allow(clippy::cast_precision_loss)]
+    fn add_average_to(&self, totals: &mut [f64]) {
+        for (total, &n) in totals.iter_mut().zip(&self.freq) {
+            *total += n as f64 / self.pages as f64;
+        }
+    }
+}
+
+/// Tag counts grouped by registrable domain, so that sites crawled through many URLs
+/// don't weigh more than those crawled through a single one.
+///
+/// Only so many domains are kept track of, so that crawls of millions of sites don't run out
+/// of memory. Once full, a new domain takes the place of the one with the fewest pages, which
+/// still weighs as much as any other in the [histogram](Self::freq), but is left out of the
+/// [summary](Self::summary), and counts as another domain if it turns up again.
+#[derive(Clone, Debug)]
+pub struct Domains {
+    vocabulary: Vocabulary,
+    capacity: usize,
+    inner: Arc<Mutex<Inner>>,
+}
+#[derive(Debug)]
+struct Inner {
+    stats: HashMap<String, DomainStats>,
+    /// Domains ordered by their number of pages, so that the one with the fewest can be
+    /// found quickly
+    by_pages: BTreeSet<(u64, String)>,
+    /// The average tag counts per page of the domains no longer kept track of, summed up
+    evicted: Vec<f64>,
+}
+impl Domains {
+    /// Keeps track of up to `capacity` domains at once.
+    #[must_use]
+    pub fn new(vocabulary: Vocabulary, capacity: usize) -> Self {
+        let inner = Inner {
+            stats: HashMap::new(),
+            by_pages: BTreeSet::new(),
+            evicted: vec![0.0; vocabulary.len()],
+        };
+        Self {
+            vocabulary,
+            capacity,
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    /// Adds a single page's tag counts to its domain.
+    pub async fn add(&self, domain: &str, freq: &[u64]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().await;
+        let inner = &mut *inner;
+        if let Some(stats) = inner.stats.get(domain) {
+            inner.by_pages.remove(&(stats.pages, domain.to_owned()));
+        } else if inner.stats.len() >= self.capacity {
+            let (_, evicted) = inner.by_pages.pop_first().expect("domains should be full");
+            let stats = inner
+                .stats
+                .remove(&evicted)
+                .expect("evicted domain is tracked");
+            stats.add_average_to(&mut inner.evicted);
+        }
+
+        let stats = inner
+            .stats
+            .entry(domain.to_owned())
+            .or_insert_with(|| DomainStats {
+                pages: 0,
+                freq: vec![0; self.vocabulary.len()],
+            });
+        stats.pages += 1;
+        for (total, n) in stats.freq.iter_mut().zip(freq) {
+            *total += n;
+        }
+        inner.by_pages.insert((stats.pages, domain.to_owned()));
+    }
+
+    /// Pages and elements per domain, the domains with the most pages first.
+    pub async fn summary(&self) -> Vec<DomainSummary> {
+        let inner = self.inner.lock().await;
+        let mut domains: Vec<_> = inner
+            .stats
+            .iter()
+            .map(|(domain, stats)| DomainSummary {
+                domain: domain.clone(),
+                pages: stats.pages,
+                elements: stats.freq.iter().sum(),
+            })
+            .collect();
+        domains.sort_by(|a, b| b.pages.cmp(&a.pages).then_with(|| a.domain.cmp(&b.domain)));
+        domains
+    }
+
+    /// The tag histogram with every domain weighing the same: the sum over all domains
+    /// of their average tag counts per page.
+    pub async fn freq(&self) -> Vec<(String, f64)> {
+        let inner = self.inner.lock().await;
+        let mut freq = inner.evicted.clone();
+        for stats in inner.stats.values() {
+            stats.add_average_to(&mut freq);
+        }
+        self.vocabulary
+            .names()
+            .map(str::to_owned)
+            .zip(freq)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_only_the_domains_with_the_most_pages() {
+        let vocabulary = Vocabulary::builtin();
+        let page = |n| {
+            let mut freq = vec![0; vocabulary.len()];
+            freq[0] = n;
+            freq
+        };
+        let domains = Domains::new(vocabulary.clone(), 2);
+        domains.add("a.example", &page(2)).await;
+        domains.add("a.example", &page(4)).await;
+        domains.add("b.example", &page(6)).await;
+        domains.add("c.example", &page(8)).await;
+
+        let summary = domains.summary().await;
+        let names: Vec<_> = summary.iter().map(|d| &*d.domain).collect();
+        assert_eq!(names, ["a.example", "c.example"]);
+        // the domain taken out still weighs as much as the others
+        let (_, first) = domains.freq().await.swap_remove(0);
+        assert!((first - (3.0 + 6.0 + 8.0)).abs() < f64::EPSILON);
+    }
+}
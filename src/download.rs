@@ -0,0 +1,229 @@
+use std::{
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+use eyre::{bail, eyre, Context, ContextCompat, Result};
+use hyper::{body::Bytes, header, Body, Method, Request};
+use hyper_tls::HttpsConnector;
+use serde_json::Value;
+use tokio::process::Command;
+use tracing::*;
+use ulid::Ulid;
+
+use crate::{
+    driver::DriverKind,
+    util::{HttpClient, USER_AGENT},
+};
+
+const MAX_REDIRECTS: u32 = 10;
+
+/// Chrome for Testing's list of the newest ChromeDriver for each Chrome build
+const CHROMEDRIVER_VERSIONS: &str = "https://googlechromelabs.github.io/chrome-for-testing/latest-patch-versions-per-build-with-downloads.json";
+const GECKODRIVER_RELEASE: &str =
+    "https://api.github.com/repos/mozilla/geckodriver/releases/latest";
+
+/// Where downloaded WebDrivers are kept between runs.
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("quotelementa")
+        .join("drivers")
+}
+
+/// Finds the WebDriver matching the installed browser, downloading it into the cache first
+/// if it isn't there yet.
+///
+/// ChromeDriver is matched to the installed Chrome's build, while GeckoDriver supports
+/// a range of Firefox versions, and so is simply the latest one when first needed for
+/// a Firefox major version. Either way, the cache is keyed by the browser rather than
+/// the driver, so that a cached driver is found without going online.
+pub async fn driver(kind: DriverKind, cache: &Path) -> Result<PathBuf> {
+    let installed = match kind {
+        DriverKind::Chrome => {
+            browser_version(&["google-chrome", "chromium", "chromium-browser"]).await?
+        }
+        DriverKind::Gecko => browser_version(&["firefox"]).await?,
+        kind => bail!("Don't know where to download {kind} drivers from"),
+    };
+    let browser = match kind {
+        // e.g. `120.0.6099`
+        DriverKind::Chrome => installed.rsplit_once('.').map_or(&*installed, |(b, _)| b),
+        _ => installed.split('.').next().unwrap_or(&installed),
+    };
+
+    let binary = binary_name(kind);
+    let dir = cache.join(format!("{kind}-{browser}"));
+    let path = dir.join(binary);
+    if tokio::fs::try_exists(&path).await? {
+        info!(?path, "Using cached WebDriver");
+        return Ok(path);
+    }
+
+    let http = hyper::Client::builder().build(HttpsConnector::new());
+    let (version, url) = match kind {
+        DriverKind::Chrome => chromedriver(&http, &installed, browser).await?,
+        _ => geckodriver(&http).await?,
+    };
+    info!(%url, %version, "Downloading WebDriver");
+    let archive = get(&http, &url).await?;
+    let exe = tokio::task::spawn_blocking(move || extract(&url, &archive, binary)).await??;
+
+    // written in full next to where it goes first, so that a crawl starting meanwhile or
+    // after an interrupted download never finds half a driver
+    tokio::fs::create_dir_all(&dir).await?;
+    let partial = dir.join(format!(".{binary}.{}", Ulid::new()));
+    tokio::fs::write(&partial, exe).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&partial, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+    if let Err(e) = tokio::fs::rename(&partial, &path).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e).wrap_err_with(|| format!("Failed to move the driver to {}", path.display()));
+    }
+    info!(?path, "WebDriver downloaded");
+    Ok(path)
+}
+
+/// Finds the ChromeDriver for the installed Chrome's build, e.g. `120.0.6099`.
+async fn chromedriver(http: &HttpClient, installed: &str, build: &str) -> Result<(String, String)> {
+    let versions: Value = serde_json::from_slice(&get(http, CHROMEDRIVER_VERSIONS).await?)?;
+    let entry = versions
+        .pointer(&format!("/builds/{build}"))
+        .wrap_err_with(|| format!("No ChromeDriver available for Chrome {installed}"))?;
+    let version = entry["version"].as_str().unwrap_or(build).to_owned();
+
+    let platform = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => "linux64",
+        ("macos", "aarch64") => "mac-arm64",
+        ("macos", _) => "mac-x64",
+        ("windows", "x86") => "win32",
+        ("windows", _) => "win64",
+        (os, arch) => bail!("ChromeDriver isn't built for {os} on {arch}"),
+    };
+    let url = entry
+        .pointer("/downloads/chromedriver")
+        .and_then(Value::as_array)
+        .and_then(|downloads| downloads.iter().find(|d| d["platform"] == platform))
+        .and_then(|d| d["url"].as_str())
+        .wrap_err_with(|| format!("No ChromeDriver {version} download for {platform}"))?;
+
+    Ok((version, url.to_owned()))
+}
+
+/// Finds the latest GeckoDriver.
+async fn geckodriver(http: &HttpClient) -> Result<(String, String)> {
+    let release: Value = serde_json::from_slice(&get(http, GECKODRIVER_RELEASE).await?)?;
+    let version = release["tag_name"]
+        .as_str()
+        .wrap_err("No GeckoDriver release found")?
+        .to_owned();
+
+    let platform = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => "linux64.tar.gz",
+        ("linux", "aarch64") => "linux-aarch64.tar.gz",
+        ("macos", "aarch64") => "macos-aarch64.tar.gz",
+        ("macos", _) => "macos.tar.gz",
+        ("windows", "x86") => "win32.zip",
+        ("windows", "aarch64") => "win-aarch64.zip",
+        ("windows", _) => "win64.zip",
+        (os, arch) => bail!("GeckoDriver isn't built for {os} on {arch}"),
+    };
+    let url = release["assets"]
+        .as_array()
+        .and_then(|assets| {
+            assets.iter().find(|a| {
+                a["name"]
+                    .as_str()
+                    .is_some_and(|name| name.ends_with(&format!("-{platform}")))
+            })
+        })
+        .and_then(|a| a["browser_download_url"].as_str())
+        .wrap_err_with(|| format!("No GeckoDriver {version} download for {platform}"))?;
+
+    Ok((version, url.to_owned()))
+}
+
+/// Asks the first of these browsers that's installed for its version, e.g. `120.0.6099.109`.
+async fn browser_version(commands: &[&str]) -> Result<String> {
+    for command in commands {
+        let Ok(out) = Command::new(command).arg("--version").output().await else {
+            continue;
+        };
+        // e.g. `Google Chrome 120.0.6099.109` or `Mozilla Firefox 115.0`
+        let version = String::from_utf8_lossy(&out.stdout)
+            .split_whitespace()
+            .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+            .map(str::to_owned);
+        if let Some(version) = version {
+            return Ok(version);
+        }
+    }
+    Err(eyre!("None of {commands:?} is installed"))
+}
+
+fn binary_name(kind: DriverKind) -> &'static str {
+    match (kind, cfg!(windows)) {
+        (DriverKind::Gecko, false) => "geckodriver",
+        (DriverKind::Gecko, true) => "geckodriver.exe",
+        (_, false) => "chromedriver",
+        (_, true) => "chromedriver.exe",
+    }
+}
+
+/// Fetches the whole body, following redirects to wherever the file actually is.
+async fn get(http: &HttpClient, url: &str) -> Result<Bytes> {
+    let mut url = url::Url::parse(url)?;
+    for _ in 0..MAX_REDIRECTS {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(url.as_str())
+            .header(header::USER_AGENT, USER_AGENT)
+            .body(Body::empty())?;
+        let res = http.request(req).await?;
+
+        if res.status().is_redirection() {
+            let location = res
+                .headers()
+                .get(header::LOCATION)
+                .wrap_err("Redirected to nowhere")?;
+            url = url.join(location.to_str()?)?;
+            continue;
+        }
+        if !res.status().is_success() {
+            bail!("Failed to fetch {url}: {}", res.status());
+        }
+        return Ok(hyper::body::to_bytes(res.into_body()).await?);
+    }
+    Err(eyre!("Too many redirects fetching {url}"))
+}
+
+/// Pulls the binary out of a `.zip` or `.tar.gz`, wherever in it it is.
+fn extract(url: &str, archive: &[u8], binary: &str) -> Result<Vec<u8>> {
+    let is_binary = |path: &Path| path.file_name().is_some_and(|name| name == binary);
+    let mut exe = Vec::new();
+
+    if archive.starts_with(b"PK") {
+        let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i)?;
+            if file.enclosed_name().is_some_and(is_binary) {
+                file.read_to_end(&mut exe)?;
+                return Ok(exe);
+            }
+        }
+    } else {
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+        for file in tar.entries()? {
+            let mut file = file?;
+            if is_binary(&file.path()?) {
+                file.read_to_end(&mut exe)?;
+                return Ok(exe);
+            }
+        }
+    }
+    Err(eyre!("No {binary} in the archive")).wrap_err_with(|| format!("Failed to extract {url}"))
+}
@@ -0,0 +1,169 @@
+use std::{env, path::Path, time::Duration};
+
+use eyre::{bail, Result};
+use serde::Deserialize;
+use strum::{Display, EnumString};
+use tokio::{process::Child, time::Instant};
+use tracing::*;
+
+use crate::util::{Capabilities, Port};
+
+/// How long a freshly spawned WebDriver gets to start taking sessions
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a single status check may take, since a WebDriver that's still starting up can
+/// accept the connection well before it answers
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether there's a binary to run at the path, or on the `PATH` for a bare name.
+#[must_use]
+pub fn exists(path: &Path) -> bool {
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    let Some(dirs) = env::var_os("PATH") else {
+        return false;
+    };
+    env::split_paths(&dirs).any(|dir| {
+        let candidate = dir.join(path);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+/// The WebDriver implementations we know the quirks of.
+#[derive(EnumString, Display, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DriverKind {
+    /// GeckoDriver, for Firefox
+    Gecko,
+    /// ChromeDriver, for Chrome and Chromium
+    Chrome,
+    /// Microsoft Edge's WebDriver
+    Edge,
+    /// SafariDriver, which has no headless mode
+    Safari,
+    /// Anything else, which is asked for every browser's options at once and hoped to
+    /// make sense of its own
+    #[default]
+    Generic,
+}
+impl DriverKind {
+    /// Guesses the driver from the binary's name, e.g. `geckodriver` or `msedgedriver.exe`.
+    #[must_use]
+    pub fn detect(path: &Path) -> Self {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.contains("gecko") {
+            Self::Gecko
+        } else if name.contains("edge") {
+            Self::Edge
+        } else if name.contains("chrom") {
+            Self::Chrome
+        } else if name.contains("safari") {
+            Self::Safari
+        } else {
+            Self::Generic
+        }
+    }
+
+    /// The arguments that have the driver listen on the port.
+    #[must_use]
+    pub fn args(self, port: Port) -> Vec<String> {
+        match self {
+            Self::Gecko | Self::Safari => vec!["--port".to_owned(), port.to_string()],
+            Self::Chrome | Self::Edge | Self::Generic => vec![format!("--port={port}")],
+        }
+    }
+
+    /// The capabilities every session with this driver starts out with.
+    #[must_use]
+    pub fn capabilities(self, headless: bool) -> Capabilities {
+        let mut caps = Capabilities::new();
+        let browser = match self {
+            Self::Gecko => "firefox",
+            Self::Chrome => "chrome",
+            Self::Edge => "MicrosoftEdge",
+            Self::Safari => "safari",
+            Self::Generic => "",
+        };
+        if !browser.is_empty() {
+            caps.insert("browserName".to_owned(), browser.into());
+        }
+
+        if headless {
+            let firefox = serde_json::json!({ "args": ["--headless"] });
+            let chromium = serde_json::json!({ "args": ["--headless=new", "--disable-gpu"] });
+            match self {
+                Self::Gecko => {
+                    caps.insert("moz:firefoxOptions".to_owned(), firefox);
+                }
+                Self::Chrome => {
+                    caps.insert("goog:chromeOptions".to_owned(), chromium);
+                }
+                Self::Edge => {
+                    caps.insert("ms:edgeOptions".to_owned(), chromium);
+                }
+                Self::Safari => {}
+                Self::Generic => {
+                    caps.insert("moz:firefoxOptions".to_owned(), firefox);
+                    caps.insert("goog:chromeOptions".to_owned(), chromium);
+                }
+            }
+        }
+        caps
+    }
+
+    /// Where to ask the driver whether it's ready for new sessions.
+    #[must_use]
+    pub fn status_path(self) -> &'static str {
+        "/status"
+    }
+
+    /// Whether the driver's answer from its [status endpoint](Self::status_path)
+    /// says that it's ready for new sessions.
+    #[must_use]
+    pub fn is_ready(self, status: &serde_json::Value) -> bool {
+        let ready = status
+            .pointer("/value/ready")
+            .and_then(serde_json::Value::as_bool);
+        match self {
+            Self::Gecko | Self::Chrome | Self::Edge => ready.unwrap_or(false),
+            // SafariDriver doesn't say, and neither might others - being up at all has to do
+            Self::Safari | Self::Generic => ready.unwrap_or(true),
+        }
+    }
+
+    /// Polls the status of a WebDriver that was just spawned, until it's ready for new sessions.
+    ///
+    /// Connecting right away races with the driver starting up, which loses on slow machines.
+    pub async fn wait_until_ready(self, url: &str, driver: &mut Child) -> Result<()> {
+        let http = hyper::Client::new();
+        let status_url: hyper::Uri = format!("{url}{}", self.status_path()).parse()?;
+        let deadline = Instant::now() + READY_TIMEOUT;
+
+        loop {
+            if let Some(status) = driver.try_wait()? {
+                bail!("WebDriver exited with {status} before it was ready");
+            }
+            // refused or unanswered requests just mean it's not ready yet
+            let check = async {
+                let res = http.get(status_url.clone()).await?;
+                hyper::body::to_bytes(res.into_body()).await
+            };
+            if let Ok(Ok(body)) = tokio::time::timeout(STATUS_TIMEOUT, check).await {
+                let status = serde_json::from_slice(&body).unwrap_or_default();
+                if self.is_ready(&status) {
+                    return Ok(());
+                }
+                trace!(%status, "WebDriver not ready yet");
+            }
+            if Instant::now() >= deadline {
+                bail!("WebDriver wasn't ready after {}s", READY_TIMEOUT.as_secs());
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+    }
+}
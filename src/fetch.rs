@@ -0,0 +1,94 @@
+//! The requests crawlers make to sites on the side, besides loading them in the browser.
+
+use std::{sync::Arc, time::Duration};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use eyre::{Context, Result};
+use hyper::{header, Body, Method, Request, Response};
+use hyper_tls::HttpsConnector;
+use url::Url;
+
+use crate::{
+    config::Config,
+    limiter::RateLimiter,
+    util::{HttpClient, USER_AGENT},
+};
+
+/// Makes requests to the sites being crawled under the same rate limit and with the same
+/// credentials as the browser, so that looking closer at a site doesn't hit it any harder.
+#[derive(Clone)]
+pub struct Fetcher {
+    http: HttpClient,
+    limiter: Option<Arc<RateLimiter>>,
+    config: Arc<Config>,
+}
+
+impl Fetcher {
+    #[must_use]
+    pub fn new(limiter: Option<Arc<RateLimiter>>, config: Arc<Config>) -> Self {
+        Self {
+            http: hyper::Client::builder().build(HttpsConnector::new()),
+            limiter,
+            config,
+        }
+    }
+
+    /// Makes a request without a body once the rate limit allows, giving up after `timeout`.
+    pub async fn request(
+        &self,
+        method: Method,
+        url: &Url,
+        timeout: Duration,
+    ) -> Result<Response<Body>> {
+        let req = self.build(method.clone(), url)?;
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+        let res = tokio::time::timeout(timeout, self.http.request(req))
+            .await
+            .wrap_err_with(|| format!("{method} request timed out"))??;
+        Ok(res)
+    }
+
+    fn build(&self, method: Method, url: &Url) -> Result<Request<Body>> {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(url.as_str())
+            .header(header::USER_AGENT, USER_AGENT);
+        if let Some(creds) = self.config.credentials_for(url) {
+            let token = BASE64_STANDARD.encode(format!("{}:{}", creds.username, creds.password));
+            req = req.header(header::AUTHORIZATION, format!("Basic {token}"));
+        }
+        Ok(req.body(Body::empty())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Credentials;
+
+    #[test]
+    fn sends_the_credentials_for_the_site() {
+        let mut config = Config::default();
+        config.credentials.insert(
+            "example.com".to_owned(),
+            Credentials {
+                username: "aladdin".to_owned(),
+                password: "open sesame".to_owned(),
+            },
+        );
+        let fetcher = Fetcher::new(None, Arc::new(config));
+
+        let url = Url::parse("https://www.example.com/").unwrap();
+        let req = fetcher.build(Method::HEAD, &url).unwrap();
+        assert_eq!(
+            req.headers()[header::AUTHORIZATION],
+            "Basic YWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+
+        let url = Url::parse("https://example.org/").unwrap();
+        let req = fetcher.build(Method::HEAD, &url).unwrap();
+        assert!(!req.headers().contains_key(header::AUTHORIZATION));
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crate::state::SiteRecord;
+
+/// How many of the most recent findings are kept around.
+const MAX_FINDINGS: usize = 50;
+
+/// Pages with at least this many elements are worth pointing out.
+const LARGE_PAGE_ELEMENTS: usize = 10_000;
+/// Element trees at least this deep are worth pointing out.
+const DEEP_TREE: u64 = 64;
+/// Pages reusing at least this many `id`s are worth pointing out.
+const MANY_DUPLICATE_IDS: u64 = 100;
+/// Pages loading scripts from at least this many known trackers are worth pointing out.
+const MANY_TRACKERS: usize = 10;
+
+/// Something remarkable about a single site, for watching a crawl go by.
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub url: String,
+    pub message: String,
+}
+
+/// The most recent notable findings across all sites, newest last.
+#[derive(Clone, Debug, Default)]
+pub struct Findings {
+    inner: Arc<Mutex<VecDeque<Finding>>>,
+}
+impl Findings {
+    /// Looks for anything notable about the site.
+    pub fn add(&self, record: &SiteRecord) {
+        if record.error.is_some() || record.skipped {
+            return;
+        }
+
+        let mut messages = vec![];
+        if record.elements >= LARGE_PAGE_ELEMENTS {
+            messages.push(format!("had {} elements", record.elements));
+        }
+        if !record.obsolete_tags.is_empty() {
+            let tags: Vec<_> = record
+                .obsolete_tags
+                .keys()
+                .map(|tag| format!("<{tag}>"))
+                .collect();
+            messages.push(format!("uses {}", tags.join(", ")));
+        }
+        if let Some(tree) = record.tree.as_ref().filter(|t| t.max_depth >= DEEP_TREE) {
+            messages.push(format!("nests elements {} levels deep", tree.max_depth));
+        }
+        if let Some(n) = record.duplicate_ids.filter(|&n| n >= MANY_DUPLICATE_IDS) {
+            messages.push(format!("reuses {n} ids"));
+        }
+        if record.trackers >= MANY_TRACKERS {
+            messages.push(format!("loads scripts from {} trackers", record.trackers));
+        }
+        if messages.is_empty() {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        for message in messages {
+            if inner.len() == MAX_FINDINGS {
+                inner.pop_front();
+            }
+            inner.push_back(Finding {
+                url: record.url.clone(),
+                message,
+            });
+        }
+    }
+
+    #[must_use]
+    pub fn recent(&self) -> Vec<Finding> {
+        self.inner.lock().unwrap().iter().cloned().collect()
+    }
+}
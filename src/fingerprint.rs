@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use eyre::Result;
+use fantoccini::Client;
+use url::Url;
+
+use crate::{psl::PublicSuffixList, util::is_same_or_subdomain};
+
+const TRACKERS: &str = include_str!("trackers.txt");
+
+/// Collects the URLs of all scripts the page has included or loaded.
+const SCRIPTS_SCRIPT: &str = r#"
+const resources = performance.getEntriesByType("resource")
+    .filter(e => e.initiatorType === "script")
+    .map(e => e.name);
+return [...document.scripts].map(s => s.src).filter(s => s).concat(resources);
+"#;
+
+/// Checks for telltale globals, attributes and element properties left behind by
+/// popular front-end frameworks, returning the names of the ones found.
+const FRAMEWORKS_SCRIPT: &str = r##"
+const has = check => { try { return !!check(); } catch { return false; } };
+const q = sel => document.querySelector(sel);
+const elems = [...document.querySelectorAll("body *")].slice(0, 2000);
+const hasKey = prefix => elems.some(e => Object.keys(e).some(k => k.startsWith(prefix)));
+
+const checks = {
+    "react": () => q("[data-reactroot], [data-reactid]") || hasKey("__react"),
+    "next.js": () => window.__NEXT_DATA__ || q("#__next"),
+    "gatsby": () => q("#___gatsby"),
+    "vue": () => window.__VUE__ || q("[data-v-app]") || hasKey("__vue"),
+    "nuxt": () => window.__NUXT__ || q("#__nuxt"),
+    "angular": () => q("[ng-version]"),
+    "angularjs": () => window.angular || q("[ng-app], [data-ng-app]"),
+    "svelte": () => q("[class*='svelte-']"),
+    "ember": () => window.Ember,
+    "jquery": () => window.jQuery,
+};
+return Object.entries(checks).filter(([_, check]) => has(check)).map(([name, _]) => name);
+"##;
+
+/// Detects which front-end frameworks the currently loaded page uses.
+pub async fn frameworks(client: &Client) -> Result<Vec<String>> {
+    let v = client.execute(FRAMEWORKS_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScriptOrigins {
+    /// Distinct hosts serving scripts that don't belong to the site itself
+    pub third_party: usize,
+    /// Distinct known trackers among them
+    pub trackers: usize,
+}
+
+/// Classifies the origins of the scripts on the currently loaded page.
+pub async fn script_origins(client: &Client, page: &Url) -> Result<ScriptOrigins> {
+    let v = client.execute(SCRIPTS_SCRIPT, vec![]).await?;
+    let scripts: Vec<String> = serde_json::from_value(v)?;
+    Ok(classify(&scripts, page))
+}
+
+/// Tells the scripts served from the page's own registrable domain, subdomains and all, from
+/// those served from elsewhere.
+fn classify(scripts: &[String], page: &Url) -> ScriptOrigins {
+    let psl = PublicSuffixList::get();
+    // hosts that are public suffixes themselves only have their own name to go by
+    let domain = |url: &Url| {
+        psl.site_domain(url)
+            .or_else(|| url.host_str().map(str::to_owned))
+    };
+    let site = domain(page);
+
+    let third_party: HashSet<_> = scripts
+        .iter()
+        .filter_map(|s| Url::parse(s).ok())
+        .filter(|url| domain(url) != site)
+        .filter_map(|url| url.host_str().map(str::to_owned))
+        .collect();
+    let trackers: HashSet<_> = third_party
+        .iter()
+        .filter_map(|host| tracker_domains().find(|&tracker| is_same_or_subdomain(host, tracker)))
+        .collect();
+
+    ScriptOrigins {
+        third_party: third_party.len(),
+        trackers: trackers.len(),
+    }
+}
+
+fn tracker_domains() -> impl Iterator<Item = &'static str> {
+    TRACKERS
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(scripts: &[&str], page: &str) -> ScriptOrigins {
+        let scripts: Vec<_> = scripts.iter().map(|&s| s.to_owned()).collect();
+        super::classify(&scripts, &page.parse().unwrap())
+    }
+
+    #[test]
+    fn counts_sibling_subdomains_as_first_party() {
+        let origins = classify(
+            &[
+                "https://example.com/app.js",
+                "https://static.example.com/vendor.js",
+                "https://shop.example.com/cart.js",
+            ],
+            "https://shop.example.com/",
+        );
+        assert_eq!(origins.third_party, 0);
+    }
+
+    #[test]
+    fn counts_other_domains_as_third_party() {
+        let origins = classify(
+            &[
+                "https://cdn.example.net/lib.js",
+                "https://someone.github.io/widget.js",
+                "https://www.google-analytics.com/analytics.js",
+            ],
+            "https://other.github.io/",
+        );
+        assert_eq!(origins.third_party, 3);
+        assert_eq!(origins.trackers, 1);
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use tokio::sync::RwLock;
+
+/// A non-HTML vocabulary that can be embedded in HTML documents.
+#[derive(
+    EnumString,
+    Display,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Namespace {
+    Svg,
+    MathMl,
+}
+impl Namespace {
+    #[must_use]
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        match uri {
+            "http://www.w3.org/2000/svg" => Some(Self::Svg),
+            "http://www.w3.org/1998/Math/MathML" => Some(Self::MathMl),
+            _ => None,
+        }
+    }
+}
+
+/// The elements of SVG, including the ones since dropped from it, as they're named in the DOM.
+#[rustfmt::skip]
+const SVG_ELEMENTS: [&str; 80] = [
+    "a", "altGlyph", "altGlyphDef", "altGlyphItem", "animate", "animateColor",
+    "animateMotion", "animateTransform", "circle", "clipPath", "cursor", "defs", "desc",
+    "discard", "ellipse", "feBlend", "feColorMatrix", "feComponentTransfer", "feComposite",
+    "feConvolveMatrix", "feDiffuseLighting", "feDisplacementMap", "feDistantLight",
+    "feDropShadow", "feFlood", "feFuncA", "feFuncB", "feFuncG", "feFuncR", "feGaussianBlur",
+    "feImage", "feMerge", "feMergeNode", "feMorphology", "feOffset", "fePointLight",
+    "feSpecularLighting", "feSpotLight", "feTile", "feTurbulence", "filter", "font",
+    "font-face", "font-face-format", "font-face-name", "font-face-src", "font-face-uri",
+    "foreignObject", "g", "glyph", "glyphRef", "hkern", "image", "line", "linearGradient",
+    "marker", "mask", "metadata", "missing-glyph", "mpath", "path", "pattern", "polygon",
+    "polyline", "radialGradient", "rect", "script", "set", "stop", "style", "svg", "switch",
+    "symbol", "text", "textPath", "title", "tref", "tspan", "use", "view",
+];
+
+/// The elements of MathML, including the ones since dropped from it.
+#[rustfmt::skip]
+const MATHML_ELEMENTS: [&str; 43] = [
+    "annotation", "annotation-xml", "maction", "malignmark", "math", "menclose", "merror",
+    "mfenced", "mfrac", "mglyph", "mi", "mlabeledtr", "mlongdiv", "mmultiscripts", "mn", "mo",
+    "mover", "mpadded", "mphantom", "mprescripts", "mroot", "mrow", "ms", "mscarries",
+    "mscarry", "msgroup", "msline", "mspace", "msqrt", "msrow", "mstack", "mstyle", "msub",
+    "msubsup", "msup", "mtable", "mtd", "mtext", "mtr", "munder", "munderover", "none",
+    "semantics",
+];
+
+/// Whether the tag name is that of an SVG or MathML element, which is all that's known of
+/// elements walked one by one, rather than their namespace.
+#[must_use]
+pub fn is_foreign(tag: &str) -> bool {
+    SVG_ELEMENTS
+        .iter()
+        .chain(&MATHML_ELEMENTS)
+        .any(|name| name.eq_ignore_ascii_case(tag))
+}
+
+/// Element counts by namespace, then by tag name.
+pub type ForeignCounts = BTreeMap<Namespace, HashMap<String, u64>>;
+
+/// Counts the SVG and MathML elements on the page, by namespace URI and tag name.
+const FOREIGN_ELEMENTS_SCRIPT: &str = r#"
+const counts = {};
+for (const e of document.querySelectorAll("*")) {
+    const ns = e.namespaceURI;
+    if (ns === "http://www.w3.org/1999/xhtml") continue;
+    counts[ns] ??= {};
+    counts[ns][e.localName] = (counts[ns][e.localName] || 0) + 1;
+}
+return counts;
+"#;
+
+/// Counts the SVG and MathML elements on the currently loaded page.
+pub async fn count(client: &Client) -> Result<ForeignCounts> {
+    let v = client.execute(FOREIGN_ELEMENTS_SCRIPT, vec![]).await?;
+    let counts: HashMap<String, HashMap<String, u64>> = serde_json::from_value(v)?;
+
+    Ok(counts
+        .into_iter()
+        .filter_map(|(uri, tags)| Some((Namespace::from_uri(&uri)?, tags)))
+        .collect())
+}
+
+/// Total number of elements per namespace.
+#[must_use]
+pub fn totals(counts: &ForeignCounts) -> BTreeMap<Namespace, u64> {
+    counts
+        .iter()
+        .map(|(&ns, tags)| (ns, tags.values().sum()))
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ForeignTag {
+    pub namespace: Namespace,
+    pub tag: String,
+    pub count: u64,
+}
+
+/// SVG and MathML element counts, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct ForeignElements {
+    inner: Arc<RwLock<HashMap<(Namespace, String), u64>>>,
+}
+impl ForeignElements {
+    pub async fn add_all(&self, counts: ForeignCounts) {
+        let mut inner = self.inner.write().await;
+        for (ns, tags) in counts {
+            for (tag, n) in tags {
+                *inner.entry((ns, tag)).or_default() += n;
+            }
+        }
+    }
+
+    /// All tags seen, most common first.
+    pub async fn tags(&self) -> Vec<ForeignTag> {
+        let mut tags: Vec<_> = self
+            .inner
+            .read()
+            .await
+            .iter()
+            .map(|((namespace, tag), &count)| ForeignTag {
+                namespace: *namespace,
+                tag: tag.clone(),
+                count,
+            })
+            .collect();
+        // ties broken by name, so that they don't come out in whatever order the map hashed
+        // them into, which differs from run to run
+        tags.sort_by(|a, b| {
+            (Reverse(a.count), a.namespace, &a.tag).cmp(&(Reverse(b.count), b.namespace, &b.tag))
+        });
+        tags
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// The `type`s an `<input>` can have. Anything else makes it a text field.
+const INPUT_TYPES: &[&str] = &[
+    "button",
+    "checkbox",
+    "color",
+    "date",
+    "datetime-local",
+    "email",
+    "file",
+    "hidden",
+    "image",
+    "month",
+    "number",
+    "password",
+    "radio",
+    "range",
+    "reset",
+    "search",
+    "submit",
+    "tel",
+    "text",
+    "time",
+    "url",
+    "week",
+];
+
+/// Counts forms and their controls. `input.type` is already normalized by the browser.
+const FORMS_SCRIPT: &str = r#"
+const inputs = {};
+for (const e of document.querySelectorAll("input")) {
+    inputs[e.type] = (inputs[e.type] || 0) + 1;
+}
+return {
+    forms: document.querySelectorAll("form").length,
+    inputs,
+    selects: document.querySelectorAll("select").length,
+    textareas: document.querySelectorAll("textarea").length,
+    buttons: document.querySelectorAll("button").length,
+};
+"#;
+
+/// Forms and form controls on a page.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+// the field names are what the records call them
+#[allow(clippy::struct_field_names)]
+pub struct Forms {
+    pub forms: u64,
+    /// `<input>` elements, by `type`
+    pub inputs: BTreeMap<String, u64>,
+    pub selects: u64,
+    pub textareas: u64,
+    pub buttons: u64,
+}
+impl Forms {
+    /// Counts an element, given its tag name and `type` attribute.
+    pub fn add_element(&mut self, tag: &str, input_type: Option<&str>) {
+        match tag {
+            "form" => self.forms += 1,
+            "select" => self.selects += 1,
+            "textarea" => self.textareas += 1,
+            "button" => self.buttons += 1,
+            "input" => {
+                let ty = input_type
+                    .map(str::to_ascii_lowercase)
+                    .filter(|ty| INPUT_TYPES.contains(&ty.as_str()))
+                    .unwrap_or_else(|| "text".to_owned());
+                *self.inputs.entry(ty).or_default() += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.forms += other.forms;
+        for (ty, n) in &other.inputs {
+            *self.inputs.entry(ty.clone()).or_default() += n;
+        }
+        self.selects += other.selects;
+        self.textareas += other.textareas;
+        self.buttons += other.buttons;
+    }
+}
+
+/// Counts the forms and form controls on the currently loaded page.
+pub async fn forms(client: &Client) -> Result<Forms> {
+    let v = client.execute(FORMS_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    /// Number of sites with at least one `<form>`
+    pub sites_with_forms: u64,
+    #[serde(flatten)]
+    pub totals: Forms,
+}
+
+/// Form usage, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct FormUsage {
+    inner: Arc<Mutex<Summary>>,
+}
+impl FormUsage {
+    pub async fn add(&self, forms: &Forms) {
+        let mut inner = self.inner.lock().await;
+        if forms.forms > 0 {
+            inner.sites_with_forms += 1;
+        }
+        inner.totals.merge(forms);
+    }
+
+    pub async fn summary(&self) -> Summary {
+        self.inner.lock().await.clone()
+    }
+}
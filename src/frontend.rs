@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+use eyre::Result;
+use strum::{Display, EnumString};
+use tokio::sync::oneshot;
+
+use crate::tui::{App, Tui};
+
+/// How often a progress line is printed when running without the TUI.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(EnumString, Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum ProgressFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// What the user sees while a crawl runs.
+pub enum Frontend {
+    /// The interactive terminal UI
+    Tui(Tui),
+    /// Periodic progress lines on stderr, for when there's no terminal to draw on
+    Plain(Plain),
+}
+impl Frontend {
+    pub async fn run(self, close_rx: oneshot::Receiver<()>) -> Result<()> {
+        match self {
+            Self::Tui(tui) => tui.run(close_rx).await,
+            Self::Plain(plain) => plain.run(close_rx).await,
+        }
+    }
+}
+
+pub struct Plain {
+    app: App,
+    format: ProgressFormat,
+    started: Instant,
+}
+impl Plain {
+    #[must_use]
+    pub fn new(app: App, format: ProgressFormat) -> Self {
+        Self {
+            app,
+            format,
+            started: Instant::now(),
+        }
+    }
+
+    pub async fn run(mut self, mut close_rx: oneshot::Receiver<()>) -> Result<()> {
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        let mut signals = self.app.signals()?;
+
+        loop {
+            tokio::select! {
+                _ = &mut close_rx => {
+                    self.app.update();
+                    self.print("done");
+                    return Ok(());
+                }
+                signal = signals.recv() => {
+                    self.app.on_signal(signal);
+                    self.print("shutting_down");
+                }
+                _ = ticker.tick() => {
+                    self.app.update();
+                    let state = if self.app.is_shutting_down() {
+                        "shutting_down"
+                    } else if self.app.is_paused() {
+                        "paused"
+                    } else {
+                        "running"
+                    };
+                    self.print(state);
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn print(&self, state: &str) {
+        let (crawled, total) = (self.app.crawled_sites(), self.app.total_sites());
+        let elapsed = self.started.elapsed().as_secs();
+
+        match self.format {
+            ProgressFormat::Text => {
+                let percent = if total == 0 {
+                    100.0
+                } else {
+                    crawled as f64 / total as f64 * 100.0
+                };
+                eprintln!(
+                    "[{:02}:{:02}:{:02}] {state}: {crawled}/{total} sites ({percent:.1}%), {} active crawlers",
+                    elapsed / 3600,
+                    elapsed / 60 % 60,
+                    elapsed % 60,
+                    self.app.active_crawlers(),
+                );
+            }
+            ProgressFormat::Json => {
+                let event = serde_json::json!({
+                    "state": state,
+                    "elapsed_secs": elapsed,
+                    "crawled": crawled,
+                    "total": total,
+                    "active_crawlers": self.app.active_crawlers(),
+                });
+                eprintln!("{event}");
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Keeping track of the sites already seen, in memory that doesn't grow with every site.
+
+use growable_bloom_filter::GrowableBloom;
+
+/// A Bloom filter of URLs, sized up front for the number of URLs expected, so that millions
+/// of them fit in a few megabytes.
+///
+/// It never forgets a URL, but may mistake one it hasn't seen for one it has, about as often
+/// as it was sized for. Given more URLs than expected, it grows rather than getting worse.
+#[derive(Clone, Debug)]
+pub struct Visited {
+    bloom: GrowableBloom,
+}
+impl Visited {
+    /// Room for `expected` URLs to start with, of which about a `false_positive_rate` share
+    /// of the ones never seen are mistaken for seen.
+    #[must_use]
+    pub fn new(expected: usize, false_positive_rate: f64) -> Self {
+        Self {
+            bloom: GrowableBloom::new(false_positive_rate, expected.max(1)),
+        }
+    }
+
+    /// Marks the URL as seen, returning whether it (probably) already was.
+    pub fn insert(&mut self, url: &str) -> bool {
+        self.bloom.check_and_set(url)
+    }
+
+    /// Whether the URL was (probably) seen before.
+    #[must_use]
+    pub fn contains(&self, url: &str) -> bool {
+        self.bloom.contains(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_every_url() {
+        let mut visited = Visited::new(100, 1e-6);
+        for i in 0..1000 {
+            assert!(!visited.insert(&format!("https://{i}.example/")));
+        }
+        for i in 0..1000 {
+            assert!(visited.contains(&format!("https://{i}.example/")));
+            assert!(visited.insert(&format!("https://{i}.example/")));
+        }
+    }
+
+    #[test]
+    fn stays_accurate_past_the_expected_size() {
+        let mut visited = Visited::new(1000, 1e-4);
+        for i in 0..100_000 {
+            visited.insert(&format!("https://{i}.example/"));
+        }
+        let mistaken = (0..100_000)
+            .filter(|i| visited.contains(&format!("https://{i}.example.org/")))
+            .count();
+        assert!(
+            mistaken < 100,
+            "{mistaken} URLs never seen were mistaken for seen"
+        );
+    }
+}
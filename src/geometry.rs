@@ -0,0 +1,91 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use crate::vocabulary::Tag;
+
+/// Upper bounds of the area buckets, as fractions of the viewport area.
+/// Bucket 0 holds elements without any area, i.e. hidden ones,
+/// and the last bucket holds elements larger than the viewport.
+pub const AREA_BOUNDS: [f64; 6] = [0.0, 1e-4, 1e-3, 1e-2, 1e-1, 1.0];
+pub const AREA_BUCKETS: usize = AREA_BOUNDS.len() + 1;
+
+/// The viewport is split into a grid of thirds, plus an extra row for everything below the fold.
+pub const COLUMNS: usize = 3;
+pub const ROWS: usize = 4;
+
+/// Where elements of a single tag sit on the page, and how large they are.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TagGeometry {
+    pub count: u64,
+    /// Histogram of element areas, bucketed by [`AREA_BOUNDS`]
+    pub area: [u64; AREA_BUCKETS],
+    /// Histogram of element centers, as `position[row][column]`
+    pub position: [[u64; COLUMNS]; ROWS],
+}
+
+impl TagGeometry {
+    /// Records an element, with its rectangle given relative to the viewport size.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn add(&mut self, (x, y, w, h): (f64, f64, f64, f64)) {
+        self.count += 1;
+
+        let area = w * h;
+        let bucket = AREA_BOUNDS
+            .iter()
+            .position(|&bound| area <= bound)
+            .unwrap_or(AREA_BUCKETS - 1);
+        self.area[bucket] += 1;
+
+        let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+        let column = ((cx * COLUMNS as f64).max(0.0) as usize).min(COLUMNS - 1);
+        let row = ((cy * (ROWS - 1) as f64).max(0.0) as usize).min(ROWS - 1);
+        self.position[row][column] += 1;
+    }
+}
+
+/// Geometry histograms for the tags of interest, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct Geometry {
+    tags: Arc<HashSet<Tag>>,
+    inner: Arc<RwLock<HashMap<Tag, TagGeometry>>>,
+}
+
+impl Geometry {
+    #[must_use]
+    pub fn new(tags: impl IntoIterator<Item = Tag>) -> Self {
+        Self {
+            tags: Arc::new(tags.into_iter().collect()),
+            inner: Arc::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn tracks(&self, tag: Tag) -> bool {
+        self.tags.contains(&tag)
+    }
+
+    pub async fn get(&self) -> RwLockReadGuard<'_, HashMap<Tag, TagGeometry>> {
+        self.inner.read().await
+    }
+
+    /// Records an element, with its rectangle given relative to the viewport size.
+    pub async fn add(&self, tag: Tag, rect: (f64, f64, f64, f64)) {
+        self.inner.write().await.entry(tag).or_default().add(rect);
+    }
+
+    pub async fn add_all(&self, rects: impl IntoIterator<Item = (Tag, (f64, f64, f64, f64))>) {
+        let mut inner = self.inner.write().await;
+        for (tag, rect) in rects {
+            inner.entry(tag).or_default().add(rect);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+
+/// How much of the page's text is looked at when guessing its language.
+const SAMPLE_CHARS: u64 = 4096;
+
+/// Reads the declared language and the document's character set, plus a sample of its text.
+const LANGUAGE_SCRIPT: &str = r#"
+const meta = document.querySelector("meta[http-equiv='content-language' i]");
+return {
+    lang: document.documentElement.lang || null,
+    content_language: meta?.content || null,
+    charset: document.characterSet,
+    sample: arguments[0] ? document.body.innerText.slice(0, arguments[0]) : null,
+};
+"#;
+
+/// A few of the most common words of each language, which are short and frequent enough
+/// to tell the languages apart on a page's worth of text.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "for", "with", "you", "that",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "mit", "sie", "ein", "für",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "des", "est", "une", "pour", "dans", "vous",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "y", "que", "del", "las", "para", "con", "una",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "di", "che", "e", "della", "per", "una", "sono", "gli", "non",
+        ],
+    ),
+    (
+        "pt",
+        &["o", "de", "que", "e", "do", "da", "em", "um", "para", "não"],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "voor", "op",
+        ],
+    ),
+    (
+        "pl",
+        &["i", "w", "nie", "na", "się", "jest", "do", "że", "to", "z"],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "som", "en", "är", "på", "för", "med", "inte",
+        ],
+    ),
+    (
+        "tr",
+        &[
+            "ve", "bir", "bu", "için", "ile", "da", "de", "olarak", "çok", "daha",
+        ],
+    ),
+];
+
+/// What a page says about its language and encoding.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+// the field names are what the records call them
+#[allow(clippy::struct_field_names)]
+pub struct Language {
+    /// The `lang` attribute of the root element
+    pub lang: Option<String>,
+    /// The `Content-Language` declared in a `<meta http-equiv>`
+    pub content_language: Option<String>,
+    /// The character encoding the browser decoded the page with
+    pub charset: String,
+    /// The language guessed from the page's text, if guessing is enabled
+    #[serde(skip_deserializing)]
+    pub guess: Option<String>,
+    #[serde(skip_serializing)]
+    sample: Option<String>,
+}
+
+/// Reads the language and character set of the currently loaded page,
+/// optionally guessing the language from its text too.
+pub async fn language(client: &Client, guess: bool) -> Result<Language> {
+    let sample = if guess { SAMPLE_CHARS } else { 0 };
+    let v = client.execute(LANGUAGE_SCRIPT, vec![sample.into()]).await?;
+    let mut language: Language = serde_json::from_value(v)?;
+    language.guess = language.sample.take().as_deref().and_then(guess_language);
+    Ok(language)
+}
+
+/// Guesses the language of a text by which language's common words it uses the most.
+/// Only a rough guess, and only for a handful of languages written in the Latin script.
+#[must_use]
+pub fn guess_language(text: &str) -> Option<String> {
+    let words: Vec<_> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let (lang, hits) = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|w| stopwords.contains(&w.as_str()))
+                .count();
+            (lang, hits)
+        })
+        .max_by_key(|&(_, hits)| hits)?;
+
+    // too few hits means the text is probably in none of these languages
+    (hits * 20 >= words.len() && hits > 0).then(|| (*lang).to_owned())
+}
@@ -0,0 +1,97 @@
+//! A census of the HTML elements used across the web.
+//!
+//! Most of the work happens in a [`CrawlSession`], which drives a pool of WebDriver sessions
+//! through a list of sites and aggregates what it finds into an [`Output`](state::Output):
+//!
+//! ```no_run
+//! # async fn example() -> eyre::Result<()> {
+//! use quotelementa::CrawlSession;
+//!
+//! let output = CrawlSession::builder()
+//!     .driver("chromedriver")
+//!     .sites("sites.csv")
+//!     .workers(4)
+//!     .run()
+//!     .await?;
+//!
+//! let freq = output.freq.get();
+//! for (tag, count) in output.vocabulary.names().zip(freq.iter()) {
+//!     println!("{tag}: {count}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#![deny(rust_2018_idioms)]
+#![warn(clippy::pedantic)]
+#![allow(
+    missing_docs,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::doc_markdown,
+    clippy::wildcard_imports
+)]
+
+pub mod accessibility;
+pub mod analyzer;
+pub mod api;
+pub mod assigner;
+pub mod audit;
+pub mod autoscale;
+pub mod bandwidth;
+pub mod bench;
+pub mod config;
+pub mod cooccurrence;
+pub mod coordinator;
+pub mod crawler;
+#[cfg(unix)]
+pub mod ctl;
+pub mod custom;
+pub mod domains;
+pub mod download;
+pub mod driver;
+mod fetch;
+pub mod findings;
+mod fingerprint;
+pub mod foreign;
+pub mod forms;
+pub mod frontend;
+mod frontier;
+pub mod geometry;
+pub mod language;
+mod limiter;
+pub mod logs;
+pub mod meta;
+pub mod metrics;
+pub mod notify;
+pub mod obsolete;
+pub mod offline;
+pub mod parked;
+pub mod plugin;
+mod psl;
+mod queue;
+pub mod redirect;
+pub mod report;
+mod resolve;
+pub mod resources;
+mod robots;
+pub mod rundir;
+pub mod schedule;
+pub mod script;
+pub mod security;
+pub mod semantic;
+mod session;
+pub mod signals;
+pub mod sink;
+mod snapshot;
+pub mod state;
+pub mod tables;
+pub mod telemetry;
+pub mod timing;
+pub mod topk;
+pub mod tui;
+pub mod util;
+pub mod vocabulary;
+
+pub use session::{CrawlSession, CrawlSessionBuilder};
+pub use util::ShutdownRx;
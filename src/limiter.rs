@@ -0,0 +1,142 @@
+use std::{sync::Mutex, time::Duration};
+
+use eyre::{ensure, Result};
+use tokio::time::Instant;
+
+/// A token bucket shared by all crawlers, bounding the total request rate.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum number of tokens, i.e. the largest permitted burst
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+impl Bucket {
+    fn refill(&mut self, rate: f64, capacity: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.last_refill = now;
+    }
+}
+
+impl RateLimiter {
+    /// Lets through `rate` requests per second, which has to be a positive number.
+    pub fn new(rate: f64) -> Result<Self> {
+        // anything else would have requests wait forever, or for NaN seconds
+        ensure!(
+            rate.is_finite() && rate > 0.0,
+            "The maximum request rate must be positive, not {rate}"
+        );
+        let capacity = rate.max(1.0);
+        Ok(Self {
+            rate,
+            capacity,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        })
+    }
+
+    /// Waits until a request may be made.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill(self.rate, self.capacity);
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                // a rate small enough has the wait overflow, which is as good as forever
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate)
+                    .unwrap_or(Duration::MAX)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A budget of bytes per second shared by all crawlers, bounding the bandwidth they use.
+///
+/// What a site transfers is only known once it's loaded, so sites are let through while
+/// there's budget left, and what they transferred is taken out afterwards. Going over puts
+/// the budget in debt, which the next sites wait out.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    /// Bytes added per second
+    rate: f64,
+    /// Maximum number of bytes saved up, a second's worth
+    capacity: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl BandwidthLimiter {
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let rate = bytes_per_sec as f64;
+        Self {
+            rate,
+            capacity: rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until the budget is out of debt.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill(self.rate, self.capacity);
+
+                if bucket.tokens >= 0.0 {
+                    return;
+                }
+                Duration::try_from_secs_f64(-bucket.tokens / self.rate).unwrap_or(Duration::MAX)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes what a site transferred out of the budget.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn spend(&self, bytes: u64) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill(self.rate, self.capacity);
+        bucket.tokens -= bytes as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_takes_positive_rates() {
+        assert!(RateLimiter::new(0.5).is_ok());
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(RateLimiter::new(rate).is_err(), "{rate} was taken");
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_out_tiny_rates_without_overflowing() {
+        let limiter = RateLimiter::new(1e-20).unwrap();
+        limiter.acquire().await;
+        let next = tokio::time::timeout(Duration::from_millis(10), limiter.acquire());
+        assert!(next.await.is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use strum::{Display, EnumString};
+use tokio::sync::mpsc;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+use ulid::Ulid;
+
+/// What the log files are called, before the date the appender adds
+pub const LOG_FILE: &str = "quotelementa.log";
+
+/// How the log file is written.
+#[derive(EnumString, Display, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[strum(serialize_all = "lowercase")]
+pub enum LogFormat {
+    /// One human-readable line per event
+    #[default]
+    Text,
+    /// One JSON object per event, with its fields and spans
+    Json,
+}
+
+/// A single log event, as shown in the UI.
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// Forwards log events at or above a level to the UI, alongside the regular log file.
+pub struct LogLayer {
+    level: Level,
+    tx: mpsc::UnboundedSender<LogLine>,
+}
+impl LogLayer {
+    #[must_use]
+    pub fn new(level: Level) -> (Self, mpsc::UnboundedReceiver<LogLine>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { level, tx }, rx)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if *meta.level() > self.level || self.tx.is_closed() {
+            return;
+        }
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        // nobody to show it to anymore - that's fine
+        let _ = self.tx.send(LogLine {
+            level: *meta.level(),
+            target: meta.target().to_owned(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// Formats the message of an event, followed by its other fields as `key=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.message.is_empty() {
+            self.message.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.message, "{}={value:?}", field.name());
+        }
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.record_debug(field, &format_args!("{value}"));
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
+
+/// Where WebDrivers of a single run write their logs, within the log directory.
+#[must_use]
+pub fn driver_log_dir(log_dir: &Path, run: &str) -> PathBuf {
+    log_dir.join("webdriver").join(run)
+}
+
+/// Deletes the oldest log files and WebDriver log directories in `log_dir`
+/// until they take up at most `max_bytes`, always keeping the newest one.
+///
+/// Only what [`LOG_FILE`] and [`driver_log_dir`] name is looked at, so that a log directory
+/// shared with other files, like `--log-dir .`, keeps them.
+pub fn prune(log_dir: &Path, max_bytes: u64) -> std::io::Result<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(log_dir)? {
+        let entry = entry?;
+        let is_log = entry.file_name().to_string_lossy().starts_with(LOG_FILE);
+        if is_log && entry.file_type()?.is_file() {
+            entries.push(entry.path());
+        }
+    }
+    if let Ok(runs) = fs::read_dir(log_dir.join("webdriver")) {
+        for run in runs {
+            let run = run?;
+            let is_run = run
+                .file_name()
+                .to_str()
+                .is_some_and(|name| Ulid::from_string(name).is_ok());
+            if is_run && run.file_type()?.is_dir() {
+                entries.push(run.path());
+            }
+        }
+    }
+
+    let mut entries: Vec<(SystemTime, u64, PathBuf)> = entries
+        .into_iter()
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((modified, size(&path), path))
+        })
+        .collect();
+    entries.sort();
+
+    let mut total: u64 = entries.iter().map(|&(_, size, _)| size).sum();
+    for (_, size, path) in &entries[..entries.len().saturating_sub(1)] {
+        if total <= max_bytes {
+            break;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+        total -= size;
+    }
+    Ok(())
+}
+
+/// The size of a file, or of everything in a directory.
+fn size(path: &Path) -> u64 {
+    let Ok(meta) = fs::metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .map(|e| size(&e.path()))
+            .sum()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prunes_only_its_own_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let run = driver_log_dir(dir, &Ulid::new().to_string());
+        fs::create_dir_all(&run).unwrap();
+        fs::create_dir_all(dir.join("webdriver").join("mine")).unwrap();
+        fs::write(run.join("webdriver-4444.log"), "driver").unwrap();
+        fs::write(dir.join("results.jsonl"), "results").unwrap();
+        fs::write(dir.join("main.rs"), "source").unwrap();
+        fs::write(dir.join(format!("{LOG_FILE}.2026-10-15")), "older").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        fs::write(dir.join(format!("{LOG_FILE}.2026-10-16")), "newest").unwrap();
+
+        prune(dir, 0).unwrap();
+
+        assert!(dir.join("results.jsonl").exists());
+        assert!(dir.join("main.rs").exists());
+        assert!(dir.join("webdriver").join("mine").exists());
+        assert!(!run.exists());
+        assert!(!dir.join(format!("{LOG_FILE}.2026-10-15")).exists());
+        assert!(dir.join(format!("{LOG_FILE}.2026-10-16")).exists());
+    }
+}
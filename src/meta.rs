@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+
+/// Collects the `name`d and `property`'d `<meta>` tags of the page, with their contents.
+const META_SCRIPT: &str = r#"
+const meta = {};
+for (const e of document.querySelectorAll("meta[name], meta[property]")) {
+    const key = (e.getAttribute("property") || e.getAttribute("name")).trim().toLowerCase();
+    if (key && !(key in meta)) meta[key] = e.content;
+}
+return meta;
+"#;
+
+/// The metadata a page declares through `<meta>` tags.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Meta {
+    /// The names of all `<meta>` tags, sorted
+    pub names: Vec<String>,
+    pub viewport: Option<String>,
+    pub description: Option<String>,
+    /// `og:*` properties, without the prefix
+    pub open_graph: BTreeMap<String, String>,
+    /// `twitter:*` properties, without the prefix
+    pub twitter: BTreeMap<String, String>,
+}
+impl Meta {
+    #[must_use]
+    pub fn from_tags(mut tags: BTreeMap<String, String>) -> Self {
+        let prefixed = |prefix: &str| {
+            tags.iter()
+                .filter_map(|(k, v)| Some((k.strip_prefix(prefix)?.to_owned(), v.clone())))
+                .collect()
+        };
+        let open_graph = prefixed("og:");
+        let twitter = prefixed("twitter:");
+
+        Self {
+            names: tags.keys().cloned().collect(),
+            viewport: tags.remove("viewport"),
+            description: tags.remove("description"),
+            open_graph,
+            twitter,
+        }
+    }
+}
+
+/// Extracts the `<meta>` tags of the currently loaded page.
+pub async fn meta(client: &Client) -> Result<Meta> {
+    let v = client.execute(META_SCRIPT, vec![]).await?;
+    Ok(Meta::from_tags(serde_json::from_value(v)?))
+}
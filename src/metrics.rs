@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, HashMap};
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+
+/// Counts how often each token appears in `class` attributes across the page.
+const CLASS_TOKENS_SCRIPT: &str = r#"
+const counts = {};
+for (const e of document.querySelectorAll("[class]")) {
+    for (const token of e.classList) {
+        counts[token] = (counts[token] || 0) + 1;
+    }
+}
+return counts;
+"#;
+
+/// Counts elements with inline styles and inline event handlers.
+const INLINE_CODE_SCRIPT: &str = r#"
+let styles = 0, eventHandlers = 0;
+for (const e of document.querySelectorAll("*")) {
+    if (e.hasAttribute("style")) styles++;
+    if (e.getAttributeNames().some(a => a.startsWith("on"))) eventHandlers++;
+}
+return { styles, event_handlers: eventHandlers };
+"#;
+
+/// Number of elements carrying code in their attributes rather than in stylesheets and scripts.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InlineCode {
+    /// Elements with a `style` attribute
+    pub styles: u64,
+    /// Elements with an `onclick`, `onload`, etc. attribute
+    pub event_handlers: u64,
+}
+
+/// Counts the inline styles and event handlers on the currently loaded page.
+pub async fn inline_code(client: &Client) -> Result<InlineCode> {
+    let v = client.execute(INLINE_CODE_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+/// Walks the whole element tree, measuring its depth and branching.
+const TREE_SHAPE_SCRIPT: &str = r"
+const children = [0, 0, 0, 0, 0, 0, 0];
+let nodes = 0, maxDepth = 0, depthSum = 0;
+const stack = [[document.documentElement, 0]];
+while (stack.length) {
+    const [e, depth] = stack.pop();
+    nodes++;
+    depthSum += depth;
+    maxDepth = Math.max(maxDepth, depth);
+
+    const n = e.children.length;
+    children[n === 0 ? 0 : Math.min(6, Math.floor(Math.log2(n)) + 1)]++;
+    for (const c of e.children) stack.push([c, depth + 1]);
+}
+return { nodes, max_depth: maxDepth, mean_depth: nodes ? depthSum / nodes : 0, children };
+";
+
+/// The shape of a page's element tree.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TreeShape {
+    /// Total number of elements, including `<html>` and `<head>`
+    pub nodes: u64,
+    pub max_depth: u64,
+    pub mean_depth: f64,
+    /// Histogram of child counts per element, bucketed as
+    /// 0, 1, 2–3, 4–7, 8–15, 16–31 and 32 or more
+    pub children: [u64; 7],
+}
+
+/// Measures the element tree of the currently loaded page.
+pub async fn tree_shape(client: &Client) -> Result<TreeShape> {
+    let v = client.execute(TREE_SHAPE_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+/// Counts how often each tag directly contains each other tag, keyed by `parent>child`.
+const PARENT_CHILD_SCRIPT: &str = r#"
+const counts = {};
+for (const e of document.querySelectorAll("*")) {
+    if (!e.parentElement) continue;
+    const pair = `${e.parentElement.localName}>${e.localName}`;
+    counts[pair] = (counts[pair] || 0) + 1;
+}
+return counts;
+"#;
+
+/// Counts the parent-child tag pairs on the currently loaded page.
+pub async fn parent_child_pairs(client: &Client) -> Result<HashMap<String, u64>> {
+    let v = client.execute(PARENT_CHILD_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+/// Counts the distinct `id` values used by more than one element.
+const DUPLICATE_IDS_SCRIPT: &str = r#"
+const seen = new Set(), duplicates = new Set();
+for (const e of document.querySelectorAll("[id]")) {
+    if (seen.has(e.id)) duplicates.add(e.id);
+    seen.add(e.id);
+}
+return duplicates.size;
+"#;
+
+/// Counts the duplicated `id`s on the currently loaded page.
+pub async fn duplicate_ids(client: &Client) -> Result<u64> {
+    let v = client.execute(DUPLICATE_IDS_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+/// Measures the visible text of the page, and how much of it sits inside some key tags, each
+/// text node going to the nearest of them it's in. Both are measured the same way, so that
+/// they can be compared.
+const TEXT_CONTENT_SCRIPT: &str = r#"
+const TAGS = ["p", "span", "div", "li"];
+const by_tag = Object.fromEntries(TAGS.map(t => [t, 0]));
+const range = document.createRange();
+// text that isn't rendered has no boxes, and hidden text is laid out but not painted
+const rendered = node => {
+    range.selectNodeContents(node);
+    return range.getClientRects().length > 0
+        && getComputedStyle(node.parentElement).visibility === "visible";
+};
+let visible = 0;
+const walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT);
+while (walker.nextNode()) {
+    const node = walker.currentNode;
+    if (!node.parentElement || !rendered(node)) continue;
+    const length = node.data.replace(/\s+/g, " ").trim().length;
+    visible += length;
+    const tag = node.parentElement.closest(TAGS.join(","))?.localName;
+    if (tag) by_tag[tag] += length;
+}
+return { visible, by_tag };
+"#;
+
+/// The amount of text on a page, in characters.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TextContent {
+    /// All rendered text in the body, i.e. without hidden elements and with whitespace collapsed
+    pub visible: u64,
+    /// The part of it inside `p`, `span`, `div` and `li` elements, each bit of text counting
+    /// towards the innermost of them it's in
+    pub by_tag: BTreeMap<String, u64>,
+}
+
+/// Measures the text content of the currently loaded page.
+pub async fn text_content(client: &Client) -> Result<TextContent> {
+    let v = client.execute(TEXT_CONTENT_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+/// Counts the `class` attribute tokens on the currently loaded page.
+pub async fn class_tokens(client: &Client) -> Result<HashMap<String, u64>> {
+    let v = client.execute(CLASS_TOKENS_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
@@ -0,0 +1,97 @@
+//! Desktop notifications for when a crawl is over, for long crawls left running in a terminal
+//! out of sight.
+
+use std::time::{Duration, SystemTime};
+
+use notify_rust::Notification;
+use tracing::*;
+
+use crate::{crawler::Dashboard, tui::format_duration};
+
+/// How a crawl ended.
+pub enum Outcome<'a> {
+    /// Done with every site, or as many as allowed
+    Completed {
+        crawled: usize,
+        total: usize,
+        took: Duration,
+    },
+    /// Shut down before that, e.g. on Ctrl-C
+    ShutDown {
+        crawled: usize,
+        total: usize,
+        took: Duration,
+    },
+    /// Stopped by an error
+    Failed(&'a eyre::Report),
+}
+impl Outcome<'_> {
+    /// How the crawl reported on by `dashboard`, started at `started`, ended.
+    #[must_use]
+    pub fn of(dashboard: &Dashboard, total: usize, started: SystemTime, shut_down: bool) -> Self {
+        let crawled = dashboard.get().crawled();
+        let took = started.elapsed().unwrap_or_default();
+        if shut_down {
+            Self::ShutDown {
+                crawled,
+                total,
+                took,
+            }
+        } else {
+            Self::Completed {
+                crawled,
+                total,
+                took,
+            }
+        }
+    }
+
+    fn notification(&self) -> Notification {
+        let (summary, body) = match self {
+            Self::Completed {
+                crawled,
+                total,
+                took,
+            } => (
+                "Crawl complete",
+                format!(
+                    "Crawled {crawled} of {total} sites in {}",
+                    format_duration(*took)
+                ),
+            ),
+            Self::ShutDown {
+                crawled,
+                total,
+                took,
+            } => (
+                "Crawl stopped",
+                format!(
+                    "Crawled {crawled} of {total} sites in {} before shutting down",
+                    format_duration(*took)
+                ),
+            ),
+            Self::Failed(e) => ("Crawl failed", format!("{e:#}")),
+        };
+        let mut notification = Notification::new();
+        notification
+            .appname("quotelementa")
+            .summary(summary)
+            .body(&body);
+        notification
+    }
+}
+
+/// Shows a notification on the desktop about how the crawl ended.
+///
+/// Not being able to, e.g. on a server without a desktop, is only logged, since the crawl
+/// itself is over and done with either way.
+pub async fn send(outcome: &Outcome<'_>) {
+    let notification = outcome.notification();
+    // talking to the notification daemon blocks
+    let res = tokio::task::spawn_blocking(move || notification.show().map(drop)).await;
+    match res {
+        Ok(Ok(())) => debug!("Desktop notification sent"),
+        Ok(Err(e)) => warn!(%e, "Failed to send desktop notification"),
+        Err(e) => warn!(%e, "Failed to send desktop notification"),
+    }
+}
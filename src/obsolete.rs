@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use tokio::sync::Mutex;
+
+use crate::util::ObsoleteTag;
+
+/// How many example sites are kept per obsolete tag.
+const MAX_EXAMPLES: usize = 50;
+
+/// Counts the elements matching the selector passed in, by tag name.
+const COUNT_SCRIPT: &str = r"
+const counts = {};
+for (const e of document.querySelectorAll(arguments[0])) {
+    counts[e.localName] = (counts[e.localName] || 0) + 1;
+}
+return counts;
+";
+
+/// Counts the obsolete elements on the currently loaded page.
+pub async fn count(client: &Client) -> Result<BTreeMap<ObsoleteTag, u64>> {
+    let selector = ObsoleteTag::iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let v = client.execute(COUNT_SCRIPT, vec![selector.into()]).await?;
+    let counts: BTreeMap<String, u64> = serde_json::from_value(v)?;
+
+    Ok(counts
+        .into_iter()
+        .filter_map(|(tag, n)| Some((ObsoleteTag::from_str(&tag).ok()?, n)))
+        .collect())
+}
+
+/// Whether the tag name is that of an obsolete element.
+#[must_use]
+pub fn is_obsolete(tag: &str) -> bool {
+    ObsoleteTag::from_str(tag).is_ok()
+}
+
+/// Counts the obsolete elements among the given tag names.
+pub fn count_names<'a>(tags: impl IntoIterator<Item = &'a str>) -> BTreeMap<ObsoleteTag, u64> {
+    let mut counts = BTreeMap::new();
+    for tag in tags {
+        if let Ok(tag) = ObsoleteTag::from_str(tag) {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    counts
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Usage {
+    /// Total number of elements across all sites
+    pub count: u64,
+    /// Number of sites using the tag at least once
+    pub sites: u64,
+    /// Some of the sites using the tag
+    pub examples: Vec<String>,
+}
+
+/// Which obsolete elements are still in use, and where, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct Obsolete {
+    inner: Arc<Mutex<BTreeMap<ObsoleteTag, Usage>>>,
+}
+
+impl Obsolete {
+    pub async fn add(&self, site: &str, counts: &BTreeMap<ObsoleteTag, u64>) {
+        let mut inner = self.inner.lock().await;
+        for (&tag, &n) in counts {
+            let usage = inner.entry(tag).or_default();
+            usage.count += n;
+            usage.sites += 1;
+            if usage.examples.len() < MAX_EXAMPLES {
+                usage.examples.push(site.to_owned());
+            }
+        }
+    }
+
+    pub async fn usage(&self) -> BTreeMap<ObsoleteTag, Usage> {
+        self.inner.lock().await.clone()
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use eyre::Result;
+use scraper::{ElementRef, Html, Selector};
+use tracing::*;
+
+use crate::{
+    crawler::{CrawlerState, Dashboard},
+    custom,
+    foreign::{ForeignCounts, Namespace},
+    forms::Forms,
+    obsolete,
+    semantic::Semantics,
+    sink::Recorded,
+    state::{Node, Output, SiteRecord, State},
+    timing::Timing,
+    util::Port,
+    ShutdownRx,
+};
+
+/// The snapshot engine shows up as a single worker in the UI.
+const WORKER_ID: Port = 0;
+
+/// An element parsed out of a saved snapshot.
+struct SnapshotNode {
+    tag: String,
+}
+impl Node for SnapshotNode {
+    async fn tag_name(&self) -> Result<String> {
+        Ok(self.tag.clone())
+    }
+    async fn rectangle(&self) -> Result<Option<(f64, f64, f64, f64)>> {
+        Ok(None)
+    }
+}
+
+/// A saved snapshot to analyze.
+enum Snapshot {
+    /// A snapshot found in the directory
+    Found(PathBuf),
+    /// A site as the run being [replayed](Snapshots::replay) recorded it, along with its
+    /// snapshot, unless it didn't get that far
+    Recorded {
+        path: Option<PathBuf>,
+        record: Recorded,
+    },
+}
+
+/// Re-analyzes DOM snapshots saved by a previous crawl, without any network or WebDriver.
+pub struct Snapshots {
+    files: Vec<Snapshot>,
+    /// Sites that were already analyzed, as their URLs
+    skip: HashSet<String>,
+    /// How many snapshots to analyze at most
+    limit: Option<usize>,
+    /// When to stop analyzing snapshots
+    deadline: Option<Instant>,
+}
+impl Snapshots {
+    pub async fn new(dir: &Path) -> Result<Self> {
+        let mut files = vec![];
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "html") {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        Ok(Self::with_files(
+            files.into_iter().map(Snapshot::Found).collect(),
+        ))
+    }
+
+    /// Analyzes the snapshots of a previous run again, found by name in `dir`, in the order
+    /// that run recorded them and as if crawled when and as fast as they were, so that the
+    /// results come out the same.
+    ///
+    /// Sites that were left out of the statistics, e.g. because they failed, are recorded
+    /// as left out again the same way, without analyzing their snapshot if they have one.
+    #[must_use]
+    pub fn replay(dir: &Path, recorded: Vec<Recorded>) -> Self {
+        let files = recorded
+            .into_iter()
+            .map(|record| Snapshot::Recorded {
+                path: record
+                    .snapshot
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .map(|name| dir.join(name)),
+                record,
+            })
+            .collect();
+        Self::with_files(files)
+    }
+
+    fn with_files(files: Vec<Snapshot>) -> Self {
+        Self {
+            files,
+            skip: HashSet::new(),
+            limit: None,
+            deadline: None,
+        }
+    }
+
+    /// Leaves out the snapshots of these URLs, e.g. because a previous run already analyzed them.
+    #[must_use]
+    pub fn skip(mut self, skip: HashSet<String>) -> Self {
+        self.skip = skip;
+        self
+    }
+
+    /// Stops after analyzing this many snapshots.
+    #[must_use]
+    pub fn limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Stops analyzing snapshots at this point in time.
+    #[must_use]
+    pub fn deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// The number of snapshots, or the [limit](Self::limit) on top of the [skipped](Self::skip)
+    /// ones if that's fewer.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.limit.map_or(self.files.len(), |limit| {
+            self.files.len().min(self.skip.len() + limit)
+        })
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn run(
+        self,
+        mut state: State,
+        dashboard: Dashboard,
+        mut shutdown_rx: ShutdownRx,
+    ) -> Result<()> {
+        let report = |state| dashboard.report(WORKER_ID, state);
+
+        let mut analyzed = 0;
+        for snapshot in self.files {
+            tokio::select! {
+                () = dashboard.unpaused() => {}
+                // marks the shutdown as seen, so the check below would miss it
+                _ = shutdown_rx.changed() => {
+                    info!("Shutdown received - exiting");
+                    break;
+                }
+            }
+            if shutdown_rx.has_changed()? {
+                info!("Shutdown received - exiting");
+                break;
+            }
+            if self.limit.is_some_and(|limit| analyzed >= limit) {
+                info!(analyzed, "Analyzed as many snapshots as allowed - stopping");
+                break;
+            }
+            if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                info!(analyzed, "Out of time - stopping");
+                break;
+            }
+
+            let (path, recorded) = match snapshot {
+                Snapshot::Found(path) => (path, None),
+                Snapshot::Recorded {
+                    path: Some(path),
+                    record,
+                } if !record.left_out() => (path, Some(record)),
+                Snapshot::Recorded { record, .. } => {
+                    if !self.skip.contains(&record.url) {
+                        analyzed += 1;
+                        state.output.record(record.outcome());
+                    }
+                    continue;
+                }
+            };
+
+            let started = Instant::now();
+            let html = tokio::fs::read_to_string(&path)
+                .instrument(info_span!("read"))
+                .await?;
+            let Parsed {
+                url,
+                nodes,
+                all_tags,
+                custom,
+                foreign,
+                forms,
+                classes,
+                pairs,
+            } = parse(&html);
+            let url = url.unwrap_or_else(|| path.display().to_string());
+            if self.skip.contains(&url) {
+                continue;
+            }
+
+            analyzed += 1;
+            report(CrawlerState::InProgress(url.clone()));
+
+            let mut record = SiteRecord::new(url);
+            if let Some(recorded) = &recorded {
+                record.crawled_at = recorded.crawled_at;
+            }
+            record.elements = nodes.len();
+            count_elements(
+                &state.output,
+                &mut record,
+                &all_tags,
+                custom,
+                foreign,
+                forms,
+            )
+            .await;
+            record.snapshot = Some(path);
+
+            let walking = Instant::now();
+            walk(&mut state, nodes, &mut record).await?;
+            record.timing.walk_ms = Some(Timing::millis(walking.elapsed()));
+            state.output.classes.add_all(classes).await;
+            state.output.cooccurrence.add_all(pairs).await;
+            record.timing.total_ms = Timing::millis(started.elapsed());
+            if let Some(recorded) = recorded {
+                record.timing = recorded.timing;
+                record.status = recorded.status;
+                record.retries = recorded.retries;
+            }
+            dashboard.busy(&record.timing);
+            state.output.record(record);
+
+            report(CrawlerState::Complete);
+        }
+
+        info!("All snapshots analyzed");
+        report(CrawlerState::Terminated);
+        Ok(())
+    }
+}
+
+/// Takes the counts that look at the page as a whole, which the WebDriver engine gets from
+/// scripts run in the page.
+async fn count_elements(
+    output: &Output,
+    record: &mut SiteRecord,
+    all_tags: &[String],
+    custom: HashMap<String, u64>,
+    foreign: ForeignCounts,
+    forms: Forms,
+) {
+    let names = || all_tags.iter().map(String::as_str);
+    record.obsolete_tags = obsolete::count_names(names());
+    output
+        .obsolete
+        .add(&record.url, &record.obsolete_tags)
+        .await;
+    record.set_custom_elements(output, custom).await;
+    record.set_foreign_elements(output, foreign).await;
+    record
+        .set_semantics(output, Semantics::from_names(names()))
+        .await;
+    record.set_forms(output, forms).await;
+}
+
+/// Hands every element of the snapshot's body to the analyzers.
+#[tracing::instrument(skip_all)]
+async fn walk(state: &mut State, nodes: Vec<SnapshotNode>, record: &mut SiteRecord) -> Result<()> {
+    for node in nodes {
+        state.accept_node(node).await?;
+    }
+    state.finish_page(record).await;
+    Ok(())
+}
+
+struct Parsed {
+    /// The URL the snapshot was saved from
+    url: Option<String>,
+    /// The elements in the page's body
+    nodes: Vec<SnapshotNode>,
+    /// The names of all elements in the page, including those outside the body
+    all_tags: Vec<String>,
+    /// How often each custom element appears, which can only be an HTML one
+    custom: HashMap<String, u64>,
+    /// How often each SVG and MathML element appears
+    foreign: ForeignCounts,
+    forms: Forms,
+    /// How often each `class` token appears
+    classes: HashMap<String, u64>,
+    /// How often each tag directly contains each other tag, keyed by `parent>child`
+    pairs: HashMap<String, u64>,
+}
+
+#[tracing::instrument(skip_all)]
+fn parse(html: &str) -> Parsed {
+    let url = html
+        .lines()
+        .next()
+        .and_then(|l| l.strip_prefix("<!-- saved from url=("))
+        .and_then(|l| l.split_once(')'))
+        .and_then(|(_, l)| l.strip_suffix(" -->"))
+        .map(str::to_owned);
+
+    let html = Html::parse_document(html);
+
+    let selector = Selector::parse("body *").expect("selector should be valid");
+    let nodes = html
+        .select(&selector)
+        .map(|e| SnapshotNode {
+            tag: e.value().name().to_owned(),
+        })
+        .collect();
+
+    let mut all_tags = vec![];
+    let mut custom = HashMap::new();
+    let mut foreign = ForeignCounts::new();
+    let mut forms = Forms::default();
+    let mut classes = HashMap::new();
+    let mut pairs = HashMap::new();
+    for e in html
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+    {
+        all_tags.push(e.value().name().to_owned());
+        forms.add_element(e.value().name(), e.value().attr("type"));
+        let name = e.value().name();
+        match Namespace::from_uri(&e.value().name.ns) {
+            Some(ns) => {
+                *foreign
+                    .entry(ns)
+                    .or_default()
+                    .entry(name.to_owned())
+                    .or_default() += 1;
+            }
+            None if custom::is_custom(name) => *custom.entry(name.to_owned()).or_default() += 1,
+            None => {}
+        }
+        for class in e.value().classes() {
+            *classes.entry(class.to_owned()).or_default() += 1;
+        }
+        if let Some(parent) = e.parent().and_then(ElementRef::wrap) {
+            let pair = format!("{}>{}", parent.value().name(), e.value().name());
+            *pairs.entry(pair).or_default() += 1;
+        }
+    }
+
+    Parsed {
+        url,
+        nodes,
+        all_tags,
+        custom,
+        foreign,
+        forms,
+        classes,
+        pairs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::{mpsc, watch};
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::{config::Config, sink};
+
+    const PAGES: [(&str, &str); 2] = [
+        (
+            "https://a.example/",
+            "<html><body><div><p>one</p><my-widget></my-widget></div></body></html>",
+        ),
+        (
+            "https://b.example/",
+            "<html><body><main><svg><circle/></svg><form><input type=text></form></main></body></html>",
+        ),
+    ];
+
+    /// Runs the snapshot engine as part of `run`, returning the results it writes.
+    async fn analyze(snapshots: Snapshots, run: Ulid, results: &Path) -> String {
+        let (records_tx, records_rx) = mpsc::unbounded_channel();
+        let mut output = Output::load(records_tx, &Config::default()).await.unwrap();
+        output.run = Some(run);
+        let path = results.to_owned();
+        let writer =
+            tokio::spawn(async move { sink::write_records(&path, false, records_rx).await });
+
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let state = State::offline(output).unwrap();
+        snapshots
+            .run(state, Dashboard::default(), shutdown_rx)
+            .await
+            .unwrap();
+        writer.await.unwrap().unwrap();
+        std::fs::read_to_string(results).unwrap()
+    }
+
+    #[tokio::test]
+    async fn replays_a_run_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        for (i, (url, html)) in PAGES.iter().enumerate() {
+            let contents = format!("<!-- saved from url=({:04}){url} -->\n{html}", url.len());
+            std::fs::write(dir.join(format!("{i}.html")), contents).unwrap();
+        }
+        let run = Ulid::new();
+
+        // as if a WebDriver crawl had failed on a site in between, leaving no snapshot
+        let analyzed = analyze(
+            Snapshots::new(dir).await.unwrap(),
+            run,
+            &dir.join("a.jsonl"),
+        )
+        .await;
+        let mut failed = SiteRecord::new("https://down.example/".to_owned());
+        failed.run = Some(run);
+        failed.crawled_at = 1;
+        failed.error = Some("Failed to navigate to site".to_owned());
+        let (first, rest) = analyzed.split_once('\n').unwrap();
+        let recorded = format!(
+            "{first}\n{}\n{rest}",
+            serde_json::to_string(&failed).unwrap()
+        );
+        std::fs::write(dir.join("recorded.jsonl"), &recorded).unwrap();
+
+        let records = sink::read_records(&dir.join("recorded.jsonl"))
+            .await
+            .unwrap();
+        let replayed = analyze(Snapshots::replay(dir, records), run, &dir.join("b.jsonl")).await;
+
+        assert_eq!(replayed.lines().count(), 3);
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    fn counts_only_html_elements_as_custom() {
+        let parsed = parse(concat!(
+            "<html><body><my-widget></my-widget><my-widget></my-widget>",
+            "<svg><font-face></font-face><missing-glyph/><x-icon/></svg>",
+            "<math><annotation-xml></annotation-xml></math></body></html>",
+        ));
+        assert_eq!(parsed.custom, HashMap::from([("my-widget".to_owned(), 2)]));
+    }
+}
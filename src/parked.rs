@@ -0,0 +1,238 @@
+//! Telling parked and placeholder domains apart from actual sites, since top lists are full
+//! of them, and their near-empty, templated pages skew the statistics.
+
+use std::sync::LazyLock;
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use url::Url;
+
+use crate::util::{is_same_or_subdomain, site_host};
+
+/// Domains of parking services, from `parking.txt`
+static PARKING: LazyLock<Vec<&str>> = LazyLock::new(|| {
+    include_str!("parking.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+});
+
+/// Pages with no more elements than this are sparse enough for an ad frame to give them away.
+const SPARSE_ELEMENTS: u64 = 50;
+
+/// Title phrases of the landers parking services and registrars put up.
+const PARKING_TITLES: [&str; 7] = [
+    "domain is for sale",
+    "domain may be for sale",
+    "domain for sale",
+    "buy this domain",
+    "this domain is parked",
+    "parked domain",
+    "domain parking",
+];
+
+/// Title phrases of pages put up until there's a site, or once there isn't one anymore.
+const PLACEHOLDER_TITLES: [&str; 8] = [
+    "coming soon",
+    "under construction",
+    "future home of",
+    "welcome to nginx",
+    "apache2 ubuntu default page",
+    "test page for the apache",
+    "iis windows server",
+    "account suspended",
+];
+
+/// Collects what gives parked pages away: their title, how little there is to them, and the
+/// frames and scripts they load.
+const PARKED_SCRIPT: &str = r#"
+const sources = sel => [...document.querySelectorAll(sel)].map(e => e.src).filter(s => s);
+return {
+    title: document.title,
+    elements: document.body ? document.body.getElementsByTagName("*").length : 0,
+    frames: sources("iframe, frame"),
+    scripts: sources("script"),
+};
+"#;
+
+/// Something that gives a page away as parked or a placeholder.
+#[derive(Display, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Sign {
+    /// The site redirected to a parking service
+    ParkingRedirect,
+    /// The page loads frames or scripts from a parking service
+    ParkingService,
+    /// The title offers the domain for sale, or says it's parked
+    ParkingTitle,
+    /// The title says the site is yet to come, or is a web server's default page
+    PlaceholderTitle,
+    /// There's next to nothing on the page but a frame from elsewhere, usually ads
+    SparseWithFrame,
+}
+impl Sign {
+    /// Whether the sign gives a page away as parked on its own, rather than only along with
+    /// others, e.g. as a sparse page with a video embedded could well be an actual site.
+    #[must_use]
+    pub fn is_conclusive(self) -> bool {
+        matches!(
+            self,
+            Self::ParkingRedirect | Self::ParkingService | Self::ParkingTitle
+        )
+    }
+}
+
+/// Whether the signs found add up to the page being parked or a placeholder: any one
+/// [conclusive](Sign::is_conclusive) sign, or at least two others.
+#[must_use]
+pub fn is_parked(signs: &[Sign]) -> bool {
+    signs.iter().any(|sign| sign.is_conclusive()) || signs.len() >= 2
+}
+
+#[derive(Deserialize)]
+struct Page {
+    title: String,
+    elements: u64,
+    frames: Vec<String>,
+    scripts: Vec<String>,
+}
+
+/// Looks for signs that the currently loaded page, `landed` on after crawling `site`, is a
+/// parked domain or a placeholder, returning the ones found.
+pub async fn signs(client: &Client, site: &Url, landed: &Url) -> Result<Vec<Sign>> {
+    let v = client.execute(PARKED_SCRIPT, vec![]).await?;
+    let page: Page = serde_json::from_value(v)?;
+    Ok(signs_of(&page, site, landed))
+}
+
+fn signs_of(page: &Page, site: &Url, landed: &Url) -> Vec<Sign> {
+    let mut signs = vec![];
+    if is_parking(site_host(landed)) {
+        signs.push(Sign::ParkingRedirect);
+    }
+    if hosts(&page.frames)
+        .chain(hosts(&page.scripts))
+        .any(|host| is_parking(&host))
+    {
+        signs.push(Sign::ParkingService);
+    }
+
+    let title = page.title.to_lowercase();
+    if PARKING_TITLES.iter().any(|&phrase| title.contains(phrase)) {
+        signs.push(Sign::ParkingTitle);
+    }
+    if PLACEHOLDER_TITLES
+        .iter()
+        .any(|&phrase| title.contains(phrase))
+    {
+        signs.push(Sign::PlaceholderTitle);
+    }
+
+    let site = site_host(site);
+    let foreign_frame = hosts(&page.frames).any(|host| !is_same_or_subdomain(&host, site));
+    if page.elements <= SPARSE_ELEMENTS && foreign_frame {
+        signs.push(Sign::SparseWithFrame);
+    }
+    signs
+}
+
+fn hosts(urls: &[String]) -> impl Iterator<Item = String> + '_ {
+    urls.iter()
+        .filter_map(|s| Url::parse(s).ok()?.host_str().map(str::to_owned))
+}
+
+fn is_parking(host: &str) -> bool {
+    PARKING
+        .iter()
+        .any(|domain| is_same_or_subdomain(host, domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(title: &str, elements: u64, frames: &[&str], scripts: &[&str]) -> Page {
+        Page {
+            title: title.to_owned(),
+            elements,
+            frames: frames.iter().map(|&s| s.to_owned()).collect(),
+            scripts: scripts.iter().map(|&s| s.to_owned()).collect(),
+        }
+    }
+
+    fn signs_on(page: &Page, landed: &str) -> Vec<Sign> {
+        let site = Url::parse("https://example.com/").unwrap();
+        signs_of(page, &site, &Url::parse(landed).unwrap())
+    }
+
+    #[test]
+    fn reads_titles() {
+        let for_sale = page("Example.com Is For Sale | Buy This Domain", 200, &[], &[]);
+        assert_eq!(
+            signs_on(&for_sale, "https://example.com/"),
+            [Sign::ParkingTitle]
+        );
+
+        let nginx = page("Welcome to nginx!", 10, &[], &[]);
+        assert_eq!(
+            signs_on(&nginx, "https://example.com/"),
+            [Sign::PlaceholderTitle]
+        );
+
+        let shop = page("Example Shop - Domains and Hosting", 500, &[], &[]);
+        assert!(signs_on(&shop, "https://example.com/").is_empty());
+    }
+
+    #[test]
+    fn recognizes_parking_services_and_their_subdomains() {
+        let lander = page("example.com", 20, &[], &[]);
+        assert_eq!(
+            signs_on(&lander, "https://www.sedo.com/search/?domain=example.com"),
+            [Sign::ParkingRedirect]
+        );
+
+        let ads = page(
+            "example.com",
+            300,
+            &[],
+            &["https://ads.parkingcrew.net/lander.js"],
+        );
+        assert_eq!(
+            signs_on(&ads, "https://example.com/"),
+            [Sign::ParkingService]
+        );
+
+        // not a subdomain, just a similar name
+        let lookalike = page("example.com", 300, &[], &["https://notsedo.com/app.js"]);
+        assert!(signs_on(&lookalike, "https://example.com/").is_empty());
+    }
+
+    #[test]
+    fn looks_for_frames_on_sparse_pages_only() {
+        let embed = ["https://www.youtube.com/embed/abc"];
+        let sparse = page("Our video", 12, &embed, &[]);
+        assert_eq!(
+            signs_on(&sparse, "https://example.com/"),
+            [Sign::SparseWithFrame]
+        );
+
+        let full = page("Our video", 400, &embed, &[]);
+        assert!(signs_on(&full, "https://example.com/").is_empty());
+
+        let own_frame = page("Our video", 12, &["https://cdn.example.com/player"], &[]);
+        assert!(signs_on(&own_frame, "https://example.com/").is_empty());
+    }
+
+    #[test]
+    fn needs_a_conclusive_sign_or_two_others() {
+        assert!(!is_parked(&[]));
+        assert!(!is_parked(&[Sign::SparseWithFrame]));
+        assert!(!is_parked(&[Sign::PlaceholderTitle]));
+        assert!(is_parked(&[Sign::ParkingService]));
+        assert!(is_parked(&[Sign::PlaceholderTitle, Sign::SparseWithFrame]));
+    }
+}
@@ -0,0 +1,191 @@
+//! Measurements of your own, as sandboxed WebAssembly modules run on every page.
+//!
+//! Plugins can only compute: they can't import anything, and unlike [scripts](crate::script),
+//! every page gets a fresh instance with limited memory and fuel, so they're safe to take
+//! from strangers. A plugin is a `.wasm` binary or `.wat` text exporting
+//!
+//! - `memory`
+//! - `alloc(len: i32) -> i32`: where to put `len` bytes of input
+//! - `analyze(ptr: i32, len: i32) -> i64`: reads the page from `ptr`, and returns where its
+//!   metrics are as `ptr << 32 | len`, or 0 if there's nothing to say
+//!
+//! The page is a JSON object of `url`, `domain`, `status`, `elements` and `tags`, as
+//! [scripts](crate::script) get it, and the metrics are a JSON object of named numbers.
+//! They're recorded with each site as `plugin.metric`, and added up across sites in the summary.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use async_trait::async_trait;
+use eyre::{ensure, eyre, Context, ContextCompat, Result};
+use serde::Serialize;
+use tracing::*;
+use wasmtime::{
+    Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+use crate::{
+    analyzer::{Analyzer, PageContext, PageTags},
+    script::ScriptMetrics,
+    state::{Output, SiteRecord},
+};
+
+/// How much memory a plugin may use per page
+const MAX_MEMORY: usize = 64 << 20;
+/// Roughly how many instructions a plugin may run per page
+const FUEL_PER_PAGE: u64 = 1_000_000_000;
+
+/// What a plugin gets to see of a page.
+#[derive(Serialize)]
+struct Page<'a> {
+    url: &'a str,
+    domain: Option<&'a str>,
+    status: Option<u16>,
+    elements: u64,
+    tags: &'a HashMap<String, u64>,
+}
+
+/// A plugin, compiled and checked but not yet running.
+#[derive(Clone)]
+pub struct Plugin {
+    /// The file name without its extension, which prefixes the plugin's metrics
+    name: String,
+    pre: InstancePre<StoreLimits>,
+}
+impl Plugin {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let bytes = tokio::fs::read(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read plugin {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map_or_else(|| "plugin".into(), |s| s.to_string_lossy())
+            .into_owned();
+
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(wasm_error)?;
+        let module = Module::new(&engine, bytes)
+            .map_err(wasm_error)
+            .wrap_err_with(|| format!("Failed to compile plugin {name}"))?;
+        for export in ["memory", "alloc", "analyze"] {
+            ensure!(
+                module.get_export(export).is_some(),
+                "Plugin {name} doesn't export `{export}`"
+            );
+        }
+        // with nothing defined, anything the module imports is missing
+        let pre = Linker::new(&engine)
+            .instantiate_pre(&module)
+            .map_err(wasm_error)
+            .wrap_err_with(|| format!("Plugin {name} imports something, which isn't allowed"))?;
+
+        Ok(Self { name, pre })
+    }
+
+    pub async fn load_all(paths: &[impl AsRef<Path>]) -> Result<Vec<Self>> {
+        let mut plugins = Vec::with_capacity(paths.len());
+        for path in paths {
+            plugins.push(Self::load(path.as_ref()).await?);
+        }
+        Ok(plugins)
+    }
+
+    /// An analyzer running the plugin, for a single crawler.
+    #[must_use]
+    pub fn analyzer(&self, output: &Output) -> Box<dyn Analyzer> {
+        Box::new(PluginAnalyzer {
+            plugin: self.clone(),
+            page: PageTags::default(),
+            metrics: output.script_metrics.clone(),
+        })
+    }
+
+    /// Runs the plugin on a page, given as JSON, in a fresh instance.
+    fn run(&self, page: &[u8]) -> Result<BTreeMap<String, f64>> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(self.pre.module().engine(), limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_PER_PAGE).map_err(wasm_error)?;
+
+        let instance = self.pre.instantiate(&mut store).map_err(wasm_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .wrap_err("`memory` isn't a memory")?;
+        let alloc: TypedFunc<i32, i32> = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(wasm_error)?;
+        let analyze: TypedFunc<(i32, i32), i64> = instance
+            .get_typed_func(&mut store, "analyze")
+            .map_err(wasm_error)?;
+
+        let len = i32::try_from(page.len())?;
+        let ptr = alloc.call(&mut store, len).map_err(wasm_error)?;
+        memory.write(&mut store, usize::try_from(ptr)?, page)?;
+
+        let res = analyze.call(&mut store, (ptr, len)).map_err(wasm_error)?;
+        if res == 0 {
+            return Ok(BTreeMap::new());
+        }
+        // the two halves of the result, reinterpreted as unsigned
+        #[allow(clippy::cast_sign_loss)]
+        let res = res as u64;
+        let ptr = usize::try_from(res >> 32)?;
+        let len = usize::try_from(res & 0xFFFF_FFFF)?;
+        let metrics = memory
+            .data(&store)
+            .get(ptr..ptr + len)
+            .wrap_err("Metrics out of bounds")?;
+        serde_json::from_slice(metrics).wrap_err("Metrics aren't a JSON object of numbers")
+    }
+}
+
+/// wasmtime's errors don't implement [`std::error::Error`], so they're passed on as text.
+#[allow(clippy::needless_pass_by_value)]
+fn wasm_error(e: wasmtime::Error) -> eyre::Report {
+    eyre!("{e:#}")
+}
+
+struct PluginAnalyzer {
+    plugin: Plugin,
+    page: PageTags,
+    metrics: ScriptMetrics,
+}
+#[async_trait]
+impl Analyzer for PluginAnalyzer {
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        self.page.add(page_ctx.name);
+        Ok(())
+    }
+
+    async fn finalize(&mut self, record: &mut SiteRecord) -> Result<()> {
+        let tags = std::mem::take(&mut self.page);
+        let page = serde_json::to_vec(&Page {
+            url: &record.url,
+            domain: record.domain.as_deref(),
+            status: record.status,
+            elements: tags.elements,
+            tags: &tags.tags,
+        })?;
+
+        // running out of fuel takes a while, which shouldn't hold up everything else
+        let plugin = self.plugin.clone();
+        let res = tokio::task::spawn_blocking(move || plugin.run(&page)).await?;
+        // a broken plugin shouldn't lose us the rest of the site
+        match res {
+            Ok(metrics) => {
+                self.metrics
+                    .record(&self.plugin.name, metrics, record)
+                    .await;
+            }
+            Err(e) => warn!(%e, plugin = self.plugin.name, url = record.url, "Plugin failed"),
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.page = PageTags::default();
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::OnceLock;
+
+use publicsuffix::{List, Psl};
+use url::{Host, Url};
+
+/// The Public Suffix List, from https://publicsuffix.org/list/public_suffix_list.dat
+const LIST: &str = include_str!("public_suffix_list.dat");
+
+/// The suffixes under which anyone can register a domain, like `com` or `co.uk`.
+#[derive(Debug)]
+pub struct PublicSuffixList(List);
+
+impl PublicSuffixList {
+    /// The list bundled with quotelementa, parsed on first use.
+    pub fn get() -> &'static Self {
+        static LIST_CELL: OnceLock<PublicSuffixList> = OnceLock::new();
+        LIST_CELL.get_or_init(|| Self(LIST.parse().expect("the bundled list should parse")))
+    }
+
+    /// The registrable domain of a host, i.e. its public suffix plus one more label,
+    /// or `None` if the host is itself a public suffix.
+    #[must_use]
+    pub fn registrable_domain(&self, host: &str) -> Option<String> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let domain = self.0.domain(host.as_bytes())?;
+        Some(String::from_utf8_lossy(domain.as_bytes()).into_owned())
+    }
+
+    /// The domain a site is grouped under: the registrable domain of the URL's host, or the
+    /// address itself for an IP address, which has no suffix to look up.
+    #[must_use]
+    pub fn site_domain(&self, url: &Url) -> Option<String> {
+        match url.host()? {
+            Host::Domain(host) => self.registrable_domain(host),
+            Host::Ipv4(addr) => Some(addr.to_string()),
+            Host::Ipv6(addr) => Some(format!("[{addr}]")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(host: &str) -> Option<String> {
+        PublicSuffixList::get().registrable_domain(host)
+    }
+
+    #[test]
+    fn finds_the_registrable_domain() {
+        assert_eq!(domain("www.example.com").as_deref(), Some("example.com"));
+        assert_eq!(
+            domain("a.b.example.co.uk").as_deref(),
+            Some("example.co.uk")
+        );
+        assert_eq!(domain("WWW.Example.COM.").as_deref(), Some("example.com"));
+        // private suffixes count too
+        assert_eq!(
+            domain("someone.github.io").as_deref(),
+            Some("someone.github.io")
+        );
+        // and so does the implicit `*` rule
+        assert_eq!(
+            domain("www.example.unlisted").as_deref(),
+            Some("example.unlisted")
+        );
+    }
+
+    #[test]
+    fn follows_wildcards_and_their_exceptions() {
+        // `*.ck`, except `www.ck`
+        assert_eq!(
+            domain("shop.example.ck").as_deref(),
+            Some("shop.example.ck")
+        );
+        assert_eq!(domain("a.www.ck").as_deref(), Some("www.ck"));
+        assert_eq!(domain("example.ck"), None);
+    }
+
+    #[test]
+    fn groups_ip_addresses_by_themselves() {
+        let site_domain = |url: &str| PublicSuffixList::get().site_domain(&url.parse().unwrap());
+        assert_eq!(
+            site_domain("http://127.0.0.1:8080/").as_deref(),
+            Some("127.0.0.1")
+        );
+        assert_eq!(site_domain("http://[::1]/").as_deref(), Some("[::1]"));
+        assert_eq!(
+            site_domain("https://www.example.com/").as_deref(),
+            Some("example.com")
+        );
+    }
+
+    #[test]
+    fn has_none_for_public_suffixes() {
+        assert_eq!(domain("com"), None);
+        assert_eq!(domain("co.uk"), None);
+        assert_eq!(domain("github.io"), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! The handoff of sites from the [assigner](crate::assigner) to the crawlers.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use deadqueue::limited::Queue;
+use tokio::{sync::watch, time::Instant};
+use url::Url;
+
+/// A site, along with when it was added to the queue.
+type Queued = (Url, Instant);
+
+/// A bounded queue of sites to crawl, which the assigner closes once it has no more to add.
+///
+/// Finding the queue empty only means the assigner hasn't caught up yet, so crawlers
+/// [wait](Self::pop) for the next site, and only stop once the queue is closed as well.
+#[derive(Debug)]
+pub struct SiteQueue {
+    queue: Queue<Queued>,
+    /// Sites crawlers couldn't finish, which go first and aren't bound by the queue's capacity
+    returned: Mutex<Vec<Queued>>,
+    closed: watch::Sender<bool>,
+    /// Sites handed to crawlers, and not put back
+    assigned: AtomicUsize,
+    /// How many sites were taken since the last [`take_waits`](Self::take_waits), and how
+    /// long they waited in the queue in total
+    waits: Mutex<(u32, Duration)>,
+}
+impl SiteQueue {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: Queue::new(capacity),
+            returned: Mutex::default(),
+            closed: watch::channel(false).0,
+            assigned: AtomicUsize::new(0),
+            waits: Mutex::default(),
+        }
+    }
+
+    /// Adds a site, waiting for room if the queue is full.
+    pub async fn push(&self, url: Url) {
+        self.queue.push((url, Instant::now())).await;
+    }
+    /// Puts back a site a crawler couldn't finish, for another one to take, whether or not
+    /// there's room.
+    pub fn put_back(&self, url: Url) {
+        self.returned.lock().unwrap().push((url, Instant::now()));
+        self.assigned.fetch_sub(1, Ordering::Relaxed);
+        // wakes up the crawlers waiting for a site, without closing the queue
+        self.closed.send_modify(|_| {});
+    }
+
+    /// The next site, once there is one, or `None` once the queue is closed and empty.
+    pub async fn pop(&self) -> Option<Url> {
+        let (url, queued) = self.next().await?;
+        self.assigned.fetch_add(1, Ordering::Relaxed);
+        let mut waits = self.waits.lock().unwrap();
+        waits.0 += 1;
+        waits.1 += queued.elapsed();
+        Some(url)
+    }
+    async fn next(&self) -> Option<Queued> {
+        let mut closed = self.closed.subscribe();
+        loop {
+            // checked before the queue, since every site added before closing is in it by then
+            let is_closed = *closed.borrow_and_update();
+            if let Some(site) = self.returned.lock().unwrap().pop() {
+                return Some(site);
+            }
+            if let Some(site) = self.queue.try_pop() {
+                return Some(site);
+            }
+            if is_closed {
+                return None;
+            }
+            tokio::select! {
+                site = self.queue.pop() => return Some(site),
+                // the sender lives as long as the queue, so this can't fail
+                _ = closed.changed() => {}
+            }
+        }
+    }
+
+    /// How long the sites taken since the last call waited for a crawler on average, or `None`
+    /// if none were taken.
+    pub fn take_waits(&self) -> Option<Duration> {
+        let (count, total) = std::mem::take(&mut *self.waits.lock().unwrap());
+        (count > 0).then(|| total / count)
+    }
+
+    /// Marks the end of the input: crawlers stop once they've emptied the queue.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// The number of sites waiting for a crawler.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.queue.len() + self.returned.lock().unwrap().len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// How many sites crawlers took, and didn't put back.
+    #[must_use]
+    pub fn assigned(&self) -> usize {
+        self.assigned.load(Ordering::Relaxed)
+    }
+    /// How many sites the assigner can add before waiting for room.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+}
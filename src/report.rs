@@ -0,0 +1,123 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{state::Output, vocabulary::Vocabulary};
+
+/// The tag counts of a summary written by [`write_summary`](crate::sink::write_summary).
+///
+/// Unlike the rest of the summary, these can be added up and compared across runs
+/// without going back to the individual sites.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Census {
+    pub freq: BTreeMap<String, u64>,
+    pub categories: BTreeMap<String, u64>,
+}
+
+/// How much of the total a tag made up before and after.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub tag: String,
+    pub before: f64,
+    pub after: f64,
+}
+impl Change {
+    #[must_use]
+    pub fn delta(&self) -> f64 {
+        self.after - self.before
+    }
+}
+
+impl Census {
+    /// The tag counts so far of a crawl still in progress, leaving out the
+    /// [baseline](Output::with_baseline) it started from, if any.
+    #[must_use]
+    pub fn of(output: &Output) -> Self {
+        let mut counts = output.freq.get();
+        if let Some(baseline) = &output.baseline {
+            for (count, base) in counts.iter_mut().zip(baseline.iter()) {
+                *count = count.saturating_sub(*base);
+            }
+        }
+        Self::count(&output.vocabulary, &counts)
+    }
+
+    /// Tag counts indexed by [`Tag::index`](crate::vocabulary::Tag::index), by name.
+    #[must_use]
+    pub fn count(vocabulary: &Vocabulary, counts: &[u64]) -> Self {
+        Self {
+            freq: vocabulary
+                .names()
+                .zip(counts.iter())
+                .map(|(tag, &count)| (tag.to_owned(), count))
+                .collect(),
+            categories: vocabulary
+                .group_totals(counts)
+                .map(|(group, total)| (group.to_owned(), total))
+                .collect(),
+        }
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        let s = tokio::fs::read(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read summary {}", path.display()))?;
+        serde_json::from_slice(&s)
+            .wrap_err_with(|| format!("Failed to parse summary {}", path.display()))
+    }
+
+    /// The number of elements counted across all tags.
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.freq.values().sum()
+    }
+
+    /// The fraction of all elements that have this tag.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn share(&self, tag: &str) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.freq.get(tag).copied().unwrap_or(0) as f64 / total as f64,
+        }
+    }
+
+    /// The most common tags, most common first.
+    #[must_use]
+    pub fn top(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut tags: Vec<_> = self.freq.iter().map(|(k, &v)| (k.as_str(), v)).collect();
+        tags.sort_by(|(_, v1), (_, v2)| v2.cmp(v1));
+        tags.truncate(n);
+        tags
+    }
+
+    /// Adds the counts of another run to these.
+    pub fn merge(&mut self, other: &Self) {
+        for (tag, count) in &other.freq {
+            *self.freq.entry(tag.clone()).or_default() += count;
+        }
+        for (group, count) in &other.categories {
+            *self.categories.entry(group.clone()).or_default() += count;
+        }
+    }
+
+    /// How each tag's share changed from this run to the other, biggest changes first.
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> Vec<Change> {
+        let tags: BTreeSet<_> = self.freq.keys().chain(other.freq.keys()).collect();
+        let mut changes: Vec<_> = tags
+            .into_iter()
+            .map(|tag| Change {
+                tag: tag.clone(),
+                before: self.share(tag),
+                after: other.share(tag),
+            })
+            .collect();
+        changes.sort_by(|a, b| b.delta().abs().total_cmp(&a.delta().abs()));
+        changes
+    }
+}
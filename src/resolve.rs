@@ -0,0 +1,150 @@
+//! Looking up the domains of upcoming sites ahead of time, so that the ones that no longer
+//! exist are left out instead of costing a crawler a navigation that's bound to fail.
+
+use std::time::Duration;
+
+use futures_util::{future::BoxFuture, stream::FuturesOrdered, FutureExt, StreamExt};
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::op::ResponseCode,
+    TokioAsyncResolver,
+};
+use tracing::*;
+use url::Url;
+
+/// How long a lookup may take before the site is given the benefit of the doubt
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether a site's domain resolves.
+#[derive(Debug)]
+pub enum Resolution {
+    Resolves,
+    /// The domain doesn't exist, or has no addresses
+    Missing(String),
+}
+
+/// Sites being looked up, a number of them at once, handed back in the order they came in.
+pub struct Resolver {
+    dns: TokioAsyncResolver,
+    ahead: usize,
+    pending: FuturesOrdered<BoxFuture<'static, (Url, Resolution)>>,
+}
+impl Resolver {
+    /// Looks up as many as `ahead` sites at once.
+    #[must_use]
+    pub fn new(ahead: usize) -> Self {
+        let dns = TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|e| {
+            warn!(%e, "Couldn't read the system's DNS settings - using the defaults");
+            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())
+        });
+        Self {
+            dns,
+            ahead: ahead.max(1),
+            pending: FuturesOrdered::new(),
+        }
+    }
+
+    /// Whether there's room to start looking up another site.
+    #[must_use]
+    pub fn wants_more(&self) -> bool {
+        self.pending.len() < self.ahead
+    }
+
+    /// Starts looking up the site's domain.
+    pub fn push(&mut self, url: Url) {
+        self.pending
+            .push_back(resolve(self.dns.clone(), url).boxed());
+    }
+
+    /// The site that came in first, once it's been looked up, or `None` if there are none left.
+    pub async fn next(&mut self) -> Option<(Url, Resolution)> {
+        self.pending.next().await
+    }
+}
+
+async fn resolve(dns: TokioAsyncResolver, url: Url) -> (Url, Resolution) {
+    let Some(host) = url.host_str() else {
+        return (url, Resolution::Resolves);
+    };
+    let lookup = dns.lookup_ip(host);
+    let resolution = match tokio::time::timeout(LOOKUP_TIMEOUT, lookup).await {
+        Ok(Ok(addrs)) => match addrs.iter().next() {
+            Some(_) => Resolution::Resolves,
+            None => Resolution::Missing("No addresses".to_owned()),
+        },
+        Ok(Err(e)) if is_temporary(&e) => {
+            debug!(%url, %e, "Couldn't look up the domain for now - keeping it");
+            Resolution::Resolves
+        }
+        Ok(Err(e)) => Resolution::Missing(e.to_string()),
+        Err(_) => {
+            debug!(%url, "Looking up the domain timed out - keeping it");
+            Resolution::Resolves
+        }
+    };
+    (url, resolution)
+}
+
+/// Whether the lookup failed for reasons that may go away, like an unreachable DNS server,
+/// rather than because the domain doesn't exist, or has no addresses.
+///
+/// A server that fails or refuses to answer is reported as having found no records too,
+/// so only its answers that there are none count.
+fn is_temporary(e: &ResolveError) -> bool {
+    match e.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => !matches!(
+            response_code,
+            ResponseCode::NXDomain | ResponseCode::NoError
+        ),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_resolver::proto::{
+        op::Query,
+        rr::{Name, RecordType},
+    };
+
+    use super::*;
+
+    fn no_records(response_code: ResponseCode) -> ResolveError {
+        let name = Name::from_ascii("gone.example.").unwrap();
+        ResolveErrorKind::NoRecordsFound {
+            query: Box::new(Query::query(name, RecordType::A)),
+            soa: None,
+            negative_ttl: None,
+            response_code,
+            trusted: true,
+        }
+        .into()
+    }
+
+    #[test]
+    fn domains_that_dont_exist_are_missing() {
+        assert!(!is_temporary(&no_records(ResponseCode::NXDomain)));
+    }
+
+    #[test]
+    fn domains_without_addresses_are_missing() {
+        assert!(!is_temporary(&no_records(ResponseCode::NoError)));
+    }
+
+    #[test]
+    fn server_failures_are_temporary() {
+        assert!(is_temporary(&no_records(ResponseCode::ServFail)));
+    }
+
+    #[test]
+    fn refusals_are_temporary() {
+        assert!(is_temporary(&no_records(ResponseCode::Refused)));
+    }
+
+    #[test]
+    fn unreachable_servers_are_temporary() {
+        assert!(is_temporary(&ResolveErrorKind::Timeout.into()));
+        assert!(is_temporary(&ResolveErrorKind::NoConnections.into()));
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::util::Port;
+
+pub type Pid = u32;
+
+/// The process IDs of the WebDriver each crawler spawned.
+#[derive(Clone, Debug, Default)]
+pub struct DriverPids {
+    /// Along with when each started, to tell it apart from a later process given the same ID
+    inner: Arc<Mutex<BTreeMap<Port, (Pid, u64)>>>,
+}
+impl DriverPids {
+    pub fn insert(&self, port: Port, pid: Pid) {
+        let mut system = System::new();
+        refresh_processes(&mut system);
+        if let Some(started) = start_time(&system, pid) {
+            self.inner.lock().unwrap().insert(port, (pid, started));
+        }
+    }
+    pub fn remove(&self, port: Port) {
+        self.inner.lock().unwrap().remove(&port);
+    }
+    #[must_use]
+    pub fn get(&self) -> BTreeMap<Port, Pid> {
+        let inner = self.inner.lock().unwrap();
+        inner.iter().map(|(&port, &(pid, _))| (port, pid)).collect()
+    }
+
+    /// Kills whatever's left of the WebDrivers that never got shut down properly,
+    /// e.g. because the run was aborted, along with their browsers.
+    ///
+    /// WebDrivers that have exited since are left alone, as their IDs may belong to someone else by now.
+    pub fn sweep(&self) {
+        let pids = std::mem::take(&mut *self.inner.lock().unwrap());
+        let mut system = System::new();
+        refresh_processes(&mut system);
+        for (pid, started) in pids.into_values() {
+            if start_time(&system, pid) == Some(started) {
+                ProcessTree::of(pid).signal(Signal::Kill);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// Ask nicely
+    Terminate,
+    /// Don't
+    Kill,
+}
+
+/// A WebDriver's process group, along with every process descended from it,
+/// since browsers can move into process groups of their own.
+///
+/// Killing just the WebDriver leaves its browser running, reparented to init.
+#[derive(Clone, Debug)]
+pub struct ProcessTree {
+    /// The root and every process in the tree, each with when it started
+    processes: Vec<(Pid, u64)>,
+}
+impl ProcessTree {
+    /// Takes note of the processes as they are now, since they can't be told apart
+    /// from unrelated ones anymore once the root has exited.
+    #[must_use]
+    pub fn of(root: Pid) -> Self {
+        let mut system = System::new();
+        refresh_processes(&mut system);
+        let processes = descendants(&system, root)
+            .into_iter()
+            .filter_map(|pid| Some((pid, start_time(&system, pid)?)))
+            .collect();
+        Self { processes }
+    }
+
+    /// Sends the signal to every process in the tree that's still running, and to the
+    /// whole group as long as the root hasn't been reaped.
+    ///
+    /// Processes that exited since are skipped rather than signalled by ID, since the ID
+    /// may have been handed to an unrelated process in the meantime.
+    ///
+    /// Does nothing except on Unix.
+    pub fn signal(&self, signal: Signal) {
+        #[cfg(unix)]
+        {
+            let signal = match signal {
+                Signal::Terminate => libc::SIGTERM,
+                Signal::Kill => libc::SIGKILL,
+            };
+            let mut system = System::new();
+            refresh_processes(&mut system);
+            for &(pid, started) in &self.processes {
+                if start_time(&system, pid) != Some(started) {
+                    continue;
+                }
+                let Ok(id) = i32::try_from(pid) else {
+                    continue;
+                };
+                // the group is only safe to signal while its leader still holds on to the ID
+                if self.processes.first().is_some_and(|&(root, _)| root == pid) {
+                    // SAFETY: kill has no memory safety requirements
+                    unsafe { libc::kill(-id, signal) };
+                }
+                // SAFETY: as above
+                unsafe { libc::kill(id, signal) };
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = signal;
+    }
+}
+
+/// Memory and CPU used by a process together with all of its descendants,
+/// which for a WebDriver includes the browser it started.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessUsage {
+    /// Resident memory, in bytes
+    pub memory: u64,
+    /// CPU time used since the previous sample, in percent of a single core
+    pub cpu: f64,
+}
+
+/// Samples the resource usage of process trees.
+///
+/// On platforms `sysinfo` doesn't support, every sample comes back empty.
+#[derive(Debug, Default)]
+pub struct Sampler {
+    /// Kept between samples, since CPU usage is measured from one refresh to the next
+    system: System,
+}
+impl Sampler {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sample(&mut self, roots: &BTreeMap<Port, Pid>) -> BTreeMap<Port, ProcessUsage> {
+        refresh_processes(&mut self.system);
+        let processes = self.system.processes();
+
+        let mut usage = BTreeMap::new();
+        for (&port, &root) in roots {
+            if !processes.contains_key(&sysinfo::Pid::from_u32(root)) {
+                continue;
+            }
+
+            // descendants that exited take their CPU time with them
+            let (mut memory, mut cpu) = (0, 0.0);
+            for pid in descendants(&self.system, root) {
+                if let Some(process) = processes.get(&sysinfo::Pid::from_u32(pid)) {
+                    memory += process.memory();
+                    cpu += f64::from(process.cpu_usage());
+                }
+            }
+            usage.insert(port, ProcessUsage { memory, cpu });
+        }
+
+        usage
+    }
+}
+
+/// How busy the whole machine is.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemUsage {
+    /// Share of CPU time spent busy since the previous sample, across all cores
+    pub cpu: f64,
+    /// Share of memory that's still available
+    pub memory_available: f64,
+}
+
+/// Samples the usage of the whole machine.
+///
+/// On platforms `sysinfo` doesn't support, every sample comes back empty.
+#[derive(Debug, Default)]
+pub struct SystemSampler {
+    /// Kept between samples, since CPU usage is measured from one refresh to the next
+    system: System,
+}
+impl SystemSampler {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&mut self) -> Option<SystemUsage> {
+        if !sysinfo::IS_SUPPORTED_SYSTEM {
+            return None;
+        }
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+
+        let total = self.system.total_memory();
+        if total == 0 {
+            return None;
+        }
+        Some(SystemUsage {
+            cpu: f64::from(self.system.global_cpu_usage()) / 100.0,
+            memory_available: self.system.available_memory() as f64 / total as f64,
+        })
+    }
+}
+
+fn refresh_processes(system: &mut System) {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_memory().with_cpu(),
+    );
+}
+
+/// When the process started, in seconds since the epoch, if it's still around.
+fn start_time(system: &System, pid: Pid) -> Option<u64> {
+    system
+        .process(sysinfo::Pid::from_u32(pid))
+        .map(sysinfo::Process::start_time)
+}
+
+/// The root followed by every process descended from it.
+fn descendants(system: &System, root: Pid) -> Vec<Pid> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (pid, process) in system.processes() {
+        if let Some(parent) = process.parent() {
+            children
+                .entry(parent.as_u32())
+                .or_default()
+                .push(pid.as_u32());
+        }
+    }
+
+    let mut pids = vec![];
+    let mut stack = vec![root];
+    while let Some(pid) = stack.pop() {
+        pids.push(pid);
+        stack.extend(children.get(&pid).into_iter().flatten());
+    }
+    pids
+}
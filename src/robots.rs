@@ -0,0 +1,227 @@
+//! Honouring robots.txt, for crawls that are asked to, by leaving the sites that disallow
+//! crawling their front page out.
+
+use std::time::Duration;
+
+use eyre::Result;
+use hyper::{header, Method};
+use url::Url;
+
+use crate::{fetch::Fetcher, util::USER_AGENT};
+
+/// Why sites [disallowing](allows) us are excluded
+pub const EXCLUSION: &str = "robots.txt";
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Redirects followed to get to robots.txt, as many as the standard asks for, after which
+/// it's taken to be missing
+const MAX_REDIRECTS: u32 = 5;
+
+/// Whether the site's robots.txt lets us crawl the URL.
+///
+/// Sites without one, or whose robots.txt can't be had for any reason other than a server
+/// error, allow everything.
+pub async fn allows(fetcher: &Fetcher, url: &Url) -> Result<bool> {
+    let mut robots = url.join("/robots.txt")?;
+    let mut redirects = 0;
+    // robots.txt is often only on the site's HTTPS or `www.` version, which others redirect to
+    let res = loop {
+        let res = fetcher.request(Method::GET, &robots, FETCH_TIMEOUT).await?;
+        match res.headers().get(header::LOCATION) {
+            Some(location) if res.status().is_redirection() && redirects < MAX_REDIRECTS => {
+                robots = robots.join(location.to_str()?)?;
+                redirects += 1;
+            }
+            _ => break res,
+        }
+    };
+
+    let status = res.status();
+    // as the standard has it: a server that can't say what's allowed allows nothing
+    if status.is_server_error() {
+        return Ok(false);
+    }
+    if !status.is_success() {
+        return Ok(true);
+    }
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    };
+    Ok(allows_path(
+        &String::from_utf8_lossy(&body),
+        USER_AGENT,
+        &path,
+    ))
+}
+
+/// Whether `robots` lets `agent` crawl `path`, going by the group for the agent if there is
+/// one, or else the one for everyone, where the longest matching rule wins, and `Allow` wins
+/// over an equally long `Disallow`.
+#[must_use]
+pub fn allows_path(robots: &str, agent: &str, path: &str) -> bool {
+    let agent = agent.to_ascii_lowercase();
+    let mut ours = Vec::new();
+    let mut everyone = Vec::new();
+
+    // which of the two the current group is for, if either
+    let (mut for_us, mut for_everyone) = (false, false);
+    let mut in_agents = false;
+    for line in robots.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match &*key.trim().to_ascii_lowercase() {
+            "user-agent" => {
+                // consecutive user-agent lines share the rules that follow them
+                if !in_agents {
+                    (for_us, for_everyone) = (false, false);
+                    in_agents = true;
+                }
+                let value = value.to_ascii_lowercase();
+                // an empty name is part of every agent's, but doesn't name any of them
+                for_us |= !value.is_empty() && value != "*" && agent.contains(&value);
+                for_everyone |= value == "*";
+            }
+            rule @ ("allow" | "disallow") => {
+                in_agents = false;
+                // an empty `Disallow` disallows nothing
+                if value.is_empty() {
+                    continue;
+                }
+                let rule = (rule == "allow", value.to_owned());
+                if for_us {
+                    ours.push(rule.clone());
+                }
+                if for_everyone {
+                    everyone.push(rule);
+                }
+            }
+            _ => in_agents = false,
+        }
+    }
+
+    let rules = if ours.is_empty() { everyone } else { ours };
+    rules
+        .iter()
+        .filter(|(_, pattern)| matches(pattern, path))
+        .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+        .is_none_or(|(allow, _)| *allow)
+}
+
+/// Whether the path starts with the pattern, where `*` stands for anything, and a `$` at
+/// the end means that the path has to end there too.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(rest) = parts.next().and_then(|first| path.strip_prefix(first)) else {
+        return false;
+    };
+    let mut rest = rest;
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        // the last part has to match at the very end when anchored, not just anywhere
+        if anchored && parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, StatusCode,
+    };
+
+    use super::*;
+    use crate::config::Config;
+
+    const ROBOTS: &str = "
+        # keep out, mostly
+        User-agent: *
+        Disallow: /private/
+        Allow: /private/press/
+
+        User-agent: BadBot
+        User-agent: Quotelementa-Crawler
+        Disallow: /
+        Allow: /$
+    ";
+
+    #[test]
+    fn uses_the_group_for_the_agent_over_the_one_for_everyone() {
+        assert!(allows_path(ROBOTS, "Quotelementa-Crawler", "/"));
+        assert!(!allows_path(ROBOTS, "Quotelementa-Crawler", "/about"));
+        assert!(allows_path(ROBOTS, "OtherBot", "/about"));
+        assert!(!allows_path(ROBOTS, "OtherBot", "/private/diary"));
+    }
+
+    #[test]
+    fn ignores_groups_for_no_agent_in_particular() {
+        let robots = "User-agent: *\nDisallow: /\n\nUser-agent:\nAllow: /\n";
+        assert!(!allows_path(robots, "Quotelementa-Crawler", "/about"));
+    }
+
+    #[test]
+    fn lets_the_longest_rule_win() {
+        assert!(allows_path(ROBOTS, "OtherBot", "/private/press/release"));
+        let tie = "User-agent: *\nDisallow: /page\nAllow: /page\n";
+        assert!(allows_path(tie, "OtherBot", "/page"));
+    }
+
+    #[test]
+    fn allows_everything_without_rules() {
+        assert!(allows_path("", USER_AGENT, "/"));
+        assert!(allows_path("User-agent: *\nDisallow:\n", USER_AGENT, "/"));
+        assert!(allows_path("garbage\n\n<html>", USER_AGENT, "/"));
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_to_robots_txt() {
+        let service = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                let res = match req.uri().path() {
+                    "/robots.txt" => Response::builder()
+                        .status(StatusCode::MOVED_PERMANENTLY)
+                        .header(header::LOCATION, "/moved/robots.txt"),
+                    _ => Response::builder(),
+                };
+                Ok::<_, Infallible>(
+                    res.body(Body::from("User-agent: *\nDisallow: /\n"))
+                        .unwrap(),
+                )
+            }))
+        });
+        let server = hyper::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(service);
+        let url = Url::parse(&format!("http://{}/", server.local_addr())).unwrap();
+        let server = tokio::spawn(server);
+
+        let fetcher = Fetcher::new(None, Arc::new(Config::default()));
+        assert!(!allows(&fetcher, &url).await.unwrap());
+        server.abort();
+    }
+
+    #[test]
+    fn matches_wildcards_and_anchors() {
+        assert!(matches("/*.pdf$", "/files/report.pdf"));
+        assert!(!matches("/*.pdf$", "/files/report.pdf?download"));
+        assert!(matches("/search*q=", "/search?lang=en&q=x"));
+        assert!(matches("/", "/anything"));
+        assert!(!matches("/$", "/anything"));
+        assert!(!matches("/private", "/public"));
+    }
+}
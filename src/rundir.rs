@@ -0,0 +1,159 @@
+//! Where a crawl keeps its files: a directory per run, e.g. `runs/2026-10-16_09-30-00`,
+//! holding its results, summary and exports, with `runs/latest` pointing to the newest one.
+//!
+//! Logs are kept apart from the runs, so that the oldest can be pruned whichever run wrote them,
+//! with a link to them as `logs` in each run directory.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Context, Result};
+
+use crate::schedule::civil_from_days;
+
+/// Where the run directories go, unless told otherwise
+pub const RUNS_DIR: &str = "runs";
+/// The link to the newest run directory, next to it
+pub const LATEST: &str = "latest";
+/// The link to the log directory, in each run directory
+pub const LOGS: &str = "logs";
+
+/// The directory a run's files go into.
+///
+/// Nothing is created until the run has [started](Self::create), so that one that fails to
+/// start doesn't leave an empty directory behind, or take [`LATEST`] away from the one before.
+#[derive(Clone, Debug)]
+pub struct RunDir {
+    path: PathBuf,
+    /// Where to point [`LATEST`] to the directory from, if it's a new one
+    runs: Option<PathBuf>,
+    /// Where the logs are, to link to as [`LOGS`]
+    logs: Option<PathBuf>,
+}
+impl RunDir {
+    /// A new directory in `runs`, named after the time, which [`LATEST`] then points to.
+    #[must_use]
+    pub fn new(runs: &Path, now: SystemTime) -> Self {
+        let name = timestamp(now);
+        // runs started within the same second get a number on top
+        let mut attempt = 1;
+        let path = loop {
+            let path = match attempt {
+                1 => runs.join(&name),
+                n => runs.join(format!("{name}-{n}")),
+            };
+            if !path.exists() {
+                break path;
+            }
+            attempt += 1;
+        };
+        Self {
+            path,
+            runs: Some(runs.to_owned()),
+            logs: None,
+        }
+    }
+
+    /// The directory given, e.g. with `--run-dir`.
+    #[must_use]
+    pub fn open(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            runs: None,
+            logs: None,
+        }
+    }
+
+    /// Links to the log directory from the run directory once it's created.
+    #[must_use]
+    pub fn with_logs(mut self, logs: PathBuf) -> Self {
+        self.logs = Some(logs);
+        self
+    }
+
+    /// The directory [`LATEST`] in `runs` points to, to pick up where that run left off.
+    pub fn latest(runs: &Path) -> Result<Self> {
+        let link = runs.join(LATEST);
+        let path = std::fs::canonicalize(&link).wrap_err_with(|| {
+            format!(
+                "No run to resume in {} - pass the one to resume with --run-dir",
+                runs.display()
+            )
+        })?;
+        Ok(Self::open(&path))
+    }
+
+    /// Creates the directory if need be, points [`LATEST`] to it if it's a new one,
+    /// and links to the logs from it.
+    pub fn create(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.path)
+            .wrap_err_with(|| format!("Failed to create {}", self.path.display()))?;
+        if let Some(runs) = &self.runs {
+            let name = self.path.file_name().expect("run directories are named");
+            link(&runs.join(LATEST), Path::new(name))?;
+        }
+        if let Some(logs) = &self.logs {
+            // the run directory may be anywhere, so the link can't be relative
+            let logs = std::fs::canonicalize(logs)
+                .wrap_err_with(|| format!("Failed to find {}", logs.display()))?;
+            link(&self.path.join(LOGS), &logs)?;
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Where the site records are written, which is also what a resumed run picks up from
+    #[must_use]
+    pub fn results(&self) -> PathBuf {
+        self.path.join("results.jsonl")
+    }
+    #[must_use]
+    pub fn summary(&self) -> PathBuf {
+        self.path.join("summary.json")
+    }
+    #[must_use]
+    pub fn timeseries(&self) -> PathBuf {
+        self.path.join("timeseries.jsonl")
+    }
+}
+
+/// The time in UTC as e.g. `2026-10-16_09-30-00`, which sorts the same as the time does.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let secs = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}_{:02}-{:02}-{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Points the link to the target, replacing whatever it pointed to before.
+///
+/// [`LATEST`] points to the run directory by its name, so that `runs` can be moved.
+#[cfg(unix)]
+fn link(link: &Path, target: &Path) -> Result<()> {
+    match std::fs::remove_file(link) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e).wrap_err_with(|| format!("Failed to replace {}", link.display())),
+    }
+    std::os::unix::fs::symlink(target, link)
+        .wrap_err_with(|| format!("Failed to link {}", link.display()))
+}
+/// Without symlinks, runs are only told apart by their names, and the logs are found by
+/// `--log-dir`.
+#[cfg(not(unix))]
+fn link(_link: &Path, _target: &Path) -> Result<()> {
+    Ok(())
+}
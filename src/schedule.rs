@@ -0,0 +1,303 @@
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{bail, ensure, Result};
+
+use crate::util::parse_duration;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+/// How far ahead to look for a time matching a cron expression, as e.g. `0 0 30 2 *` never does
+const MAX_LOOKAHEAD_DAYS: u64 = 4 * 366;
+
+/// When to re-run a crawl: either at a fixed interval, or at the times a cron expression matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Every so often, counted from the start of the previous run, e.g. `24h`
+    Every(Duration),
+    /// At the times matching a cron expression, e.g. `0 3 * * 1` for Mondays at 3:00
+    Cron(Cron),
+}
+impl Schedule {
+    /// The next time to start a run, after one started at `last`.
+    #[must_use]
+    pub fn next(&self, last: SystemTime) -> Option<SystemTime> {
+        match self {
+            Self::Every(interval) => Some(last + *interval),
+            Self::Cron(cron) => cron.next_after(last),
+        }
+    }
+}
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.split_whitespace().count() == 5 {
+            let cron = s.parse().map_err(|e: eyre::Report| e.to_string())?;
+            return Ok(Self::Cron(cron));
+        }
+        let interval = parse_duration(s)?;
+        if interval.is_zero() {
+            return Err("The interval between runs can't be zero".to_owned());
+        }
+        Ok(Self::Every(interval))
+    }
+}
+
+/// A cron expression of minute, hour, day of month, month and day of week, in UTC.
+///
+/// Each field is `*`, a number, a range like `1-5`, a step like `*/15` or `0-30/10`,
+/// or a comma-separated list of those. As with cron, a day matches if either the day of month
+/// or the day of week does, unless one of them is `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+impl Cron {
+    /// The first whole minute matching the expression, strictly after `time`.
+    #[must_use]
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let mut t = (secs / MINUTE + 1) * MINUTE;
+        let end = t + MAX_LOOKAHEAD_DAYS * DAY;
+
+        while t < end {
+            let (_, month, day) = civil_from_days(t / DAY);
+            // 1970-01-01 was a Thursday
+            let weekday = (t / DAY + 4) % 7;
+            if !self.matches_day(month, day, weekday) {
+                t = (t / DAY + 1) * DAY;
+                continue;
+            }
+            if !bit(self.hours, t / HOUR % 24) {
+                t = (t / HOUR + 1) * HOUR;
+                continue;
+            }
+            if !bit(self.minutes, t / MINUTE % 60) {
+                t += MINUTE;
+                continue;
+            }
+            return Some(UNIX_EPOCH + Duration::from_secs(t));
+        }
+        None
+    }
+
+    fn matches_day(&self, month: u64, day: u64, weekday: u64) -> bool {
+        if !bit(self.months, month) {
+            return false;
+        }
+        let day_ok = bit(self.days, day);
+        let weekday_ok = bit(self.weekdays, weekday);
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday_ok,
+            (false, true) => day_ok,
+            (false, false) => day_ok || weekday_ok,
+        }
+    }
+}
+impl FromStr for Cron {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!("A cron expression has 5 fields, not {}", fields.len());
+        };
+        let mut weekdays_mask = field(weekdays, 0, 7)?;
+        // both 0 and 7 are Sunday
+        if bit(weekdays_mask, 7) {
+            weekdays_mask |= 1;
+        }
+        Ok(Self {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekdays_mask,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+fn bit(mask: u64, n: u64) -> bool {
+    mask & (1 << n) != 0
+}
+
+/// Parses one field of a cron expression into a bit mask of the values it matches.
+fn field(s: &str, min: u64, max: u64) -> Result<u64> {
+    let mut mask = 0;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse()?),
+            None => (part, 1),
+        };
+        ensure!(step > 0, "Step of zero in cron field {s:?}");
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (from.parse()?, to.parse()?),
+                // `5/15` means from 5 onwards
+                None if step > 1 => (range.parse()?, max),
+                None => {
+                    let n = range.parse()?;
+                    (n, n)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            bail!("{part:?} is out of range {min}-{max} in cron field {s:?}");
+        }
+        for n in (from..=to).step_by(usize::try_from(step)?) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// The year, month and day of a number of days since 1970-01-01.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+pub(crate) fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01T00:00:00Z, a Monday
+    const NEW_YEAR_2024: u64 = 1_704_067_200;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn next(cron: &str, after: u64) -> Option<u64> {
+        let cron: Cron = cron.parse().unwrap();
+        let next = cron.next_after(at(after))?;
+        Some(next.duration_since(UNIX_EPOCH).unwrap().as_secs())
+    }
+
+    fn values(mask: u64) -> Vec<u64> {
+        (0..64).filter(|&n| bit(mask, n)).collect()
+    }
+
+    #[test]
+    fn parses_ranges_steps_and_lists() {
+        assert_eq!(values(field("1-5", 0, 59).unwrap()), [1, 2, 3, 4, 5]);
+        assert_eq!(values(field("*/15", 0, 59).unwrap()), [0, 15, 30, 45]);
+        assert_eq!(values(field("0-30/10", 0, 59).unwrap()), [0, 10, 20, 30]);
+        assert_eq!(values(field("50/5", 0, 59).unwrap()), [50, 55]);
+        assert_eq!(values(field("1,3,10-11", 1, 12).unwrap()), [1, 3, 10, 11]);
+        assert_eq!(
+            values(field("*", 1, 12).unwrap()),
+            (1..=12).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        assert!(field("60", 0, 59).is_err());
+        assert!(field("0", 1, 31).is_err());
+        assert!(field("5-1", 0, 59).is_err());
+        assert!(field("*/0", 0, 59).is_err());
+        assert!(field("a", 0, 59).is_err());
+        assert!("0 0 * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn finds_the_next_matching_minute() {
+        assert_eq!(
+            next("*/15 * * * *", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 15 * MINUTE)
+        );
+        assert_eq!(
+            next("30 2 * * *", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 2 * HOUR + 30 * MINUTE)
+        );
+        // strictly after, so not the same time the next day
+        assert_eq!(next("0 0 * * *", NEW_YEAR_2024), Some(NEW_YEAR_2024 + DAY));
+        assert_eq!(
+            next("0 0 1 3 *", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 60 * DAY)
+        );
+    }
+
+    #[test]
+    fn matches_days_of_the_week() {
+        // Mondays at 3:00, the first one being the same day
+        assert_eq!(
+            next("0 3 * * 1", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 3 * HOUR)
+        );
+        assert_eq!(
+            next("0 3 * * 1", NEW_YEAR_2024 + 3 * HOUR),
+            Some(NEW_YEAR_2024 + 7 * DAY + 3 * HOUR)
+        );
+        // Sunday is both 0 and 7
+        assert_eq!(
+            next("0 0 * * 0", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 6 * DAY)
+        );
+        assert_eq!(
+            next("0 0 * * 7", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 6 * DAY)
+        );
+        assert_eq!(
+            next("0 0 * * 1-5", NEW_YEAR_2024 + 4 * DAY),
+            Some(NEW_YEAR_2024 + 7 * DAY)
+        );
+    }
+
+    #[test]
+    fn matches_either_day_field_when_both_are_given() {
+        // the next Monday comes before the 15th
+        assert_eq!(
+            next("0 0 15 * 1", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 7 * DAY)
+        );
+        // and the 3rd before the next Monday
+        assert_eq!(
+            next("0 0 3 * 1", NEW_YEAR_2024),
+            Some(NEW_YEAR_2024 + 2 * DAY)
+        );
+    }
+
+    #[test]
+    fn gives_up_on_days_that_never_come() {
+        assert_eq!(next("0 0 30 2 *", NEW_YEAR_2024), None);
+    }
+
+    #[test]
+    fn parses_intervals_and_cron_expressions() {
+        assert_eq!(
+            "24h".parse::<Schedule>(),
+            Ok(Schedule::Every(Duration::from_secs(DAY)))
+        );
+        assert!(matches!(
+            "0 3 * * 1".parse::<Schedule>(),
+            Ok(Schedule::Cron(_))
+        ));
+        assert!("0s".parse::<Schedule>().is_err());
+    }
+}
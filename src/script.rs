@@ -0,0 +1,339 @@
+//! Measurements of your own, as Lua scripts run on every page.
+//!
+//! A script defines a global `analyze(page)` function, which gets a table of
+//!
+//! - `url`, `domain` and `status`, as in the site's record
+//! - `elements`: the number of elements analyzed
+//! - `tags`: how often each tag name appears, including ones missing from the vocabulary
+//!
+//! and returns a table of named numbers, or `nil` if there's nothing to say, e.g.
+//!
+//! ```lua
+//! function analyze(page)
+//!     if page.elements == 0 then return nil end
+//!     return { div_share = (page.tags.div or 0) / page.elements }
+//! end
+//! ```
+//!
+//! Metrics are recorded with each site as `script.metric`, and added up across sites in the summary.
+//!
+//! Scripts only get the parts of the standard library that compute, without `io`, `os`,
+//! `package` or `debug`, and run with limited memory and a limited number of instructions per page.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use eyre::{eyre, Context, Result};
+use rlua::{Function, HookTriggers, Lua, StdLib, Table};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tracing::*;
+
+use crate::{
+    analyzer::{Analyzer, PageContext, PageTags},
+    state::{Output, SiteRecord},
+};
+
+/// The function every script has to define
+const ENTRY_POINT: &str = "analyze";
+/// The parts of the standard library scripts get, none of which reach outside the interpreter
+const SANDBOX: StdLib = StdLib::BASE
+    .union(StdLib::COROUTINE)
+    .union(StdLib::TABLE)
+    .union(StdLib::STRING)
+    .union(StdLib::UTF8)
+    .union(StdLib::MATH);
+/// Functions of the base library that read files
+const UNSAFE_BASE: [&str; 2] = ["dofile", "loadfile"];
+/// How much memory a script may use
+const MAX_MEMORY: usize = 64 << 20;
+/// How many instructions a script may run per page, and when loaded
+const INSTRUCTIONS_PER_PAGE: u64 = 100_000_000;
+/// How often the instruction count is checked
+const INSTRUCTIONS_PER_HOOK: u32 = 10_000;
+
+/// A script, loaded and checked but not yet running.
+#[derive(Clone, Debug)]
+pub struct Script {
+    /// The file name without its extension, which prefixes the script's metrics
+    name: String,
+    source: Arc<str>,
+}
+impl Script {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let source = tokio::fs::read_to_string(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read script {}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map_or_else(|| "script".into(), |s| s.to_string_lossy());
+        let script = Self {
+            name: name.into_owned(),
+            source: source.into(),
+        };
+        // better to find out about mistakes now than once the crawlers start
+        script.start()?;
+        Ok(script)
+    }
+
+    pub async fn load_all(paths: &[impl AsRef<Path>]) -> Result<Vec<Self>> {
+        let mut scripts = Vec::with_capacity(paths.len());
+        for path in paths {
+            scripts.push(Self::load(path.as_ref()).await?);
+        }
+        Ok(scripts)
+    }
+
+    /// Runs the script in a fresh, sandboxed interpreter, leaving it ready to analyze pages.
+    fn start(&self) -> Result<Interpreter> {
+        let lua = Lua::new_with(SANDBOX);
+        lua.set_memory_limit(Some(MAX_MEMORY));
+        let budget = Arc::new(AtomicU64::new(INSTRUCTIONS_PER_PAGE));
+        let remaining = budget.clone();
+        lua.set_hook(
+            HookTriggers {
+                every_nth_instruction: Some(INSTRUCTIONS_PER_HOOK),
+                ..HookTriggers::default()
+            },
+            move |_, _| {
+                let left = remaining.load(Ordering::Relaxed);
+                if left < u64::from(INSTRUCTIONS_PER_HOOK) {
+                    return Err(rlua::Error::RuntimeError(
+                        "ran out of instructions".to_owned(),
+                    ));
+                }
+                remaining.store(left - u64::from(INSTRUCTIONS_PER_HOOK), Ordering::Relaxed);
+                Ok(())
+            },
+        );
+
+        lua.context(|ctx| {
+            for name in UNSAFE_BASE {
+                ctx.globals().set(name, rlua::Nil)?;
+            }
+            ctx.load(&*self.source).set_name(&self.name)?.exec()?;
+            // fails unless the script defines it
+            ctx.globals().get::<_, Function<'_>>(ENTRY_POINT)?;
+            Ok::<_, rlua::Error>(())
+        })
+        .map_err(|e| eyre!("{e}"))
+        .wrap_err_with(|| format!("Failed to start script {}", self.name))?;
+        Ok(Interpreter { lua, budget })
+    }
+
+    /// An analyzer running the script, for a single crawler.
+    pub fn analyzer(&self, output: &Output) -> Result<Box<dyn Analyzer>> {
+        Ok(Box::new(ScriptAnalyzer {
+            name: self.name.clone(),
+            interpreter: Arc::new(Mutex::new(self.start()?)),
+            page: PageTags::default(),
+            metrics: output.script_metrics.clone(),
+        }))
+    }
+}
+
+/// A running script, along with how many more instructions it may run.
+struct Interpreter {
+    lua: Lua,
+    budget: Arc<AtomicU64>,
+}
+impl Interpreter {
+    fn analyze(&self, page: &Page) -> rlua::Result<BTreeMap<String, f64>> {
+        self.budget.store(INSTRUCTIONS_PER_PAGE, Ordering::Relaxed);
+        self.lua.context(|ctx| {
+            let table = ctx.create_table()?;
+            table.set("url", page.url.as_str())?;
+            table.set("domain", page.domain.as_deref())?;
+            table.set("status", page.status)?;
+            table.set("elements", page.tags.elements)?;
+            table.set(
+                "tags",
+                ctx.create_table_from(page.tags.tags.iter().map(|(k, &v)| (k.as_str(), v)))?,
+            )?;
+
+            let analyze: Function<'_> = ctx.globals().get(ENTRY_POINT)?;
+            let Some(metrics) = analyze.call::<_, Option<Table<'_>>>(table)? else {
+                return Ok(BTreeMap::new());
+            };
+            metrics.pairs::<String, f64>().collect()
+        })
+    }
+}
+
+/// What a script gets to see of a page.
+struct Page {
+    url: String,
+    domain: Option<String>,
+    status: Option<u16>,
+    tags: PageTags,
+}
+
+struct ScriptAnalyzer {
+    name: String,
+    /// Only ever used by one crawler at a time, but it's handed to a blocking task for each page
+    interpreter: Arc<Mutex<Interpreter>>,
+    page: PageTags,
+    metrics: ScriptMetrics,
+}
+#[async_trait]
+impl Analyzer for ScriptAnalyzer {
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        self.page.add(page_ctx.name);
+        Ok(())
+    }
+
+    async fn finalize(&mut self, record: &mut SiteRecord) -> Result<()> {
+        let page = Page {
+            url: record.url.clone(),
+            domain: record.domain.clone(),
+            status: record.status,
+            tags: std::mem::take(&mut self.page),
+        };
+
+        // running out of instructions takes a while, which shouldn't hold up everything else
+        let interpreter = self.interpreter.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let interpreter = interpreter.lock().expect("script interpreter poisoned");
+            interpreter.analyze(&page)
+        })
+        .await?;
+
+        // a broken script shouldn't lose us the rest of the site
+        let metrics = match res {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                warn!(%e, script = self.name, url = record.url, "Script failed");
+                return Ok(());
+            }
+        };
+        self.metrics.record(&self.name, metrics, record).await;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.page = PageTags::default();
+    }
+}
+
+/// A script metric, across all sites it was given for.
+#[derive(Clone, Debug, Serialize)]
+pub struct MetricSummary {
+    pub sites: u64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Clone, Debug)]
+struct MetricTotal {
+    sites: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+/// Script and [plugin](crate::plugin) metrics, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptMetrics {
+    inner: Arc<RwLock<BTreeMap<String, MetricTotal>>>,
+}
+impl ScriptMetrics {
+    /// Records the metrics `source` came up with for a site, in its record and here.
+    pub async fn record(
+        &self,
+        source: &str,
+        metrics: BTreeMap<String, f64>,
+        record: &mut SiteRecord,
+    ) {
+        let metrics: Vec<_> = metrics
+            .into_iter()
+            .map(|(metric, value)| (format!("{source}.{metric}"), value))
+            .collect();
+        self.add(&metrics).await;
+        record.script_metrics.extend(metrics);
+    }
+
+    pub async fn add(&self, metrics: &[(String, f64)]) {
+        let mut inner = self.inner.write().await;
+        for (metric, value) in metrics {
+            let total = inner.entry(metric.clone()).or_insert(MetricTotal {
+                sites: 0,
+                sum: 0.0,
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+            });
+            total.sites += 1;
+            total.sum += value;
+            total.min = total.min.min(*value);
+            total.max = total.max.max(*value);
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub async fn summary(&self) -> BTreeMap<String, MetricSummary> {
+        let inner = self.inner.read().await;
+        inner
+            .iter()
+            .map(|(metric, total)| {
+                let summary = MetricSummary {
+                    sites: total.sites,
+                    mean: total.sum / total.sites as f64,
+                    min: total.min,
+                    max: total.max,
+                };
+                (metric.clone(), summary)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(source: &str) -> Script {
+        Script {
+            name: "test".to_owned(),
+            source: source.into(),
+        }
+    }
+
+    fn page() -> Page {
+        Page {
+            url: "https://example.com/".to_owned(),
+            domain: Some("example.com".to_owned()),
+            status: Some(200),
+            tags: PageTags::default(),
+        }
+    }
+
+    #[test]
+    fn leaves_out_the_unsafe_libraries() {
+        let interpreter = script(
+            "function analyze(page)
+                return { io = io and 1 or 0, os = os and 1 or 0, dofile = dofile and 1 or 0 }
+            end",
+        )
+        .start()
+        .unwrap();
+        let metrics = interpreter.analyze(&page()).unwrap();
+        assert!(metrics.values().all(|&v| v == 0.0), "{metrics:?}");
+    }
+
+    #[test]
+    fn stops_scripts_that_run_forever() {
+        assert!(script("while true do end").start().is_err());
+
+        let interpreter = script("function analyze(page) while true do end end")
+            .start()
+            .unwrap();
+        assert!(interpreter.analyze(&page()).is_err());
+    }
+}
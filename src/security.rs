@@ -0,0 +1,149 @@
+use std::{net::TcpStream, time::Duration};
+
+use eyre::{Context, ContextCompat, Result};
+use fantoccini::Client;
+use hyper::{header, Method};
+use openssl::{
+    nid::Nid,
+    ssl::{SslConnector, SslMethod, SslVerifyMode},
+    x509::X509VerifyResult,
+};
+use serde::Serialize;
+use tracing::*;
+use url::Url;
+
+use crate::fetch::Fetcher;
+
+const TLS_TIMEOUT: Duration = Duration::from_secs(10);
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Counts the distinct plain-HTTP resources a page has loaded or refers to.
+const MIXED_CONTENT_SCRIPT: &str = r#"
+const insecure = u => typeof u === "string" && u.startsWith("http:");
+const resources = performance.getEntriesByType("resource").map(e => e.name);
+const refs = [...document.querySelectorAll("[src], link[href]")].map(e => e.src || e.href);
+return new Set([...resources, ...refs].filter(insecure)).size;
+"#;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Security {
+    /// Whether the page was ultimately served over HTTPS
+    pub https: bool,
+    pub cert_issuer: Option<String>,
+    /// Whether the certificate chain verified against the system trust store
+    pub cert_valid: Option<bool>,
+    /// Number of distinct plain-HTTP resources on an HTTPS page
+    pub mixed_content: u64,
+    pub headers: Option<SecurityHeaders>,
+}
+
+/// Presence of the commonly recommended security response headers.
+#[derive(Clone, Debug, Default, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SecurityHeaders {
+    pub content_security_policy: bool,
+    pub strict_transport_security: bool,
+    pub x_frame_options: bool,
+    pub referrer_policy: bool,
+    /// Number of the above headers that are present, out of 4
+    pub score: u8,
+}
+
+impl SecurityHeaders {
+    /// Issues a `HEAD` request to the URL and checks its response headers.
+    pub async fn fetch(fetcher: &Fetcher, url: &Url) -> Result<Self> {
+        let res = fetcher.request(Method::HEAD, url, HEAD_TIMEOUT).await?;
+        let headers = res.headers();
+
+        let mut this = Self {
+            content_security_policy: headers.contains_key(header::CONTENT_SECURITY_POLICY),
+            // HSTS is ignored over plain HTTP anyway
+            strict_transport_security: url.scheme() == "https"
+                && headers.contains_key(header::STRICT_TRANSPORT_SECURITY),
+            x_frame_options: headers.contains_key(header::X_FRAME_OPTIONS),
+            referrer_policy: headers.contains_key(header::REFERRER_POLICY),
+            score: 0,
+        };
+        this.score = [
+            this.content_security_policy,
+            this.strict_transport_security,
+            this.x_frame_options,
+            this.referrer_policy,
+        ]
+        .into_iter()
+        .map(u8::from)
+        .sum();
+        Ok(this)
+    }
+}
+
+impl Security {
+    /// Inspects the page currently loaded in the client.
+    /// Anything that cannot be determined is logged and left empty.
+    pub async fn inspect(client: &Client, fetcher: &Fetcher) -> Result<Self> {
+        let url = client.current_url().await?;
+        let mut security = Self {
+            https: url.scheme() == "https",
+            ..Default::default()
+        };
+
+        match SecurityHeaders::fetch(fetcher, &url).await {
+            Ok(headers) => security.headers = Some(headers),
+            Err(e) => warn!(%e, "Failed to fetch security headers"),
+        }
+
+        if !security.https {
+            return Ok(security);
+        }
+
+        match client.execute(MIXED_CONTENT_SCRIPT, vec![]).await {
+            Ok(v) => security.mixed_content = v.as_u64().unwrap_or_default(),
+            Err(e) => warn!(%e, "Failed to count mixed content"),
+        }
+
+        match tokio::task::spawn_blocking(move || peer_certificate(&url)).await? {
+            Ok((issuer, valid)) => {
+                security.cert_issuer = issuer;
+                security.cert_valid = Some(valid);
+            }
+            Err(e) => warn!(%e, "Failed to inspect TLS certificate"),
+        }
+
+        Ok(security)
+    }
+}
+
+/// Performs a separate TLS handshake with the host to get at its certificate,
+/// since WebDriver doesn't expose it.
+fn peer_certificate(url: &Url) -> Result<(Option<String>, bool)> {
+    let host = url.host_str().wrap_err("URL has no host")?;
+    let addr = url
+        .socket_addrs(|| Some(443))?
+        .into_iter()
+        .next()
+        .wrap_err("Host did not resolve to any address")?;
+
+    let stream = TcpStream::connect_timeout(&addr, TLS_TIMEOUT)?;
+    stream.set_read_timeout(Some(TLS_TIMEOUT))?;
+    stream.set_write_timeout(Some(TLS_TIMEOUT))?;
+
+    // we want to know about invalid certificates too, so don't bail on them
+    let mut connector = SslConnector::builder(SslMethod::tls())?;
+    connector.set_verify(SslVerifyMode::NONE);
+    let ssl = connector
+        .build()
+        .connect(host, stream)
+        .wrap_err("TLS handshake failed")?;
+    let ssl = ssl.ssl();
+
+    let issuer = ssl.peer_certificate().and_then(|cert| {
+        let name = cert.issuer_name();
+        let entry = name
+            .entries_by_nid(Nid::ORGANIZATIONNAME)
+            .chain(name.entries_by_nid(Nid::COMMONNAME))
+            .next()?;
+        entry.data().as_utf8().ok().map(|s| s.to_string())
+    });
+
+    Ok((issuer, ssl.verify_result() == X509VerifyResult::OK))
+}
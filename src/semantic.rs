@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// Elements that describe the structure of a page.
+const SEMANTIC: &[&str] = &["header", "nav", "main", "article", "section", "footer"];
+/// Elements that carry no meaning of their own.
+const GENERIC: &[&str] = &["div", "span"];
+
+/// Number of buckets the `[0, 1]` score range is divided into in the summary.
+const SCORE_BUCKETS: usize = 10;
+
+/// Counts the semantic and generic elements on the page.
+const SEMANTICS_SCRIPT: &str = r"
+return {
+    semantic: document.querySelectorAll(arguments[0]).length,
+    generic: document.querySelectorAll(arguments[1]).length,
+};
+";
+
+/// How much a page relies on semantic elements rather than generic containers.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Semantics {
+    /// `header`, `nav`, `main`, `article`, `section` and `footer` elements
+    pub semantic: u64,
+    /// `div` and `span` elements
+    pub generic: u64,
+}
+impl Semantics {
+    /// Counts the semantic and generic elements among the given tag names.
+    pub fn from_names<'a>(tags: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut semantics = Self::default();
+        for tag in tags {
+            if SEMANTIC.contains(&tag) {
+                semantics.semantic += 1;
+            } else if GENERIC.contains(&tag) {
+                semantics.generic += 1;
+            }
+        }
+        semantics
+    }
+
+    /// The share of semantic elements among semantic and generic ones,
+    /// or `None` if the page has neither.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn score(&self) -> Option<f64> {
+        let total = self.semantic + self.generic;
+        (total > 0).then(|| self.semantic as f64 / total as f64)
+    }
+}
+
+/// Counts the semantic and generic elements on the currently loaded page.
+pub async fn semantics(client: &Client) -> Result<Semantics> {
+    let args = vec![SEMANTIC.join(", ").into(), GENERIC.join(", ").into()];
+    let v = client.execute(SEMANTICS_SCRIPT, args).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    /// Number of sites with a score
+    pub sites: u64,
+    pub mean_score: f64,
+    /// Histogram of per-site scores, in buckets of equal width
+    pub scores: [u64; SCORE_BUCKETS],
+    pub semantic: u64,
+    pub generic: u64,
+}
+
+/// Semantic element usage, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct SemanticUsage {
+    inner: Arc<Mutex<Summary>>,
+}
+impl SemanticUsage {
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub async fn add(&self, semantics: &Semantics) {
+        let mut inner = self.inner.lock().await;
+        inner.semantic += semantics.semantic;
+        inner.generic += semantics.generic;
+
+        if let Some(score) = semantics.score() {
+            let bucket = ((score * SCORE_BUCKETS as f64) as usize).min(SCORE_BUCKETS - 1);
+            inner.scores[bucket] += 1;
+            inner.sites += 1;
+            inner.mean_score += (score - inner.mean_score) / inner.sites as f64;
+        }
+    }
+
+    pub async fn summary(&self) -> Summary {
+        self.inner.lock().await.clone()
+    }
+}
@@ -0,0 +1,821 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    net::{Ipv4Addr, TcpListener},
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use eyre::{bail, ensure, Context, ContextCompat, Result};
+use tokio::{
+    sync::{broadcast, mpsc, watch},
+    task::{JoinHandle, JoinSet},
+    time::Instant,
+};
+use tracing::*;
+use url::Url;
+
+use crate::{
+    assigner::Assigner,
+    autoscale::{self, Autoscaler},
+    config::{AutoscalePolicy, Config, Profile},
+    coordinator::{CoordinatorClient, RemoteSites},
+    crawler::{Control, Crawler, Dashboard, Shared, WebDriver},
+    driver::{self, DriverKind},
+    frontier::Visited,
+    limiter::{BandwidthLimiter, RateLimiter},
+    queue::SiteQueue,
+    resources::DriverPids,
+    state::Output,
+    util::{deadline_after, Capabilities, Port, ShutdownRx},
+};
+
+/// How many of the sites listed only once [deduplication](CrawlSessionBuilder::dedup) may
+/// mistake for ones listed before
+const DEDUP_FALSE_POSITIVE_RATE: f64 = 1e-6;
+
+/// A crawl of a list of sites through a pool of WebDriver sessions.
+///
+/// Built with [`CrawlSession::builder`], and then either [run](Self::run) right away,
+/// or first hooked up to a UI through its [dashboard](Self::dashboard) and [controls](Self::control).
+pub struct CrawlSession {
+    crawlers: Crawlers,
+    assigner: Assigner,
+    sites_count: usize,
+    max_duration: Option<Duration>,
+    control_tx: mpsc::UnboundedSender<Control>,
+    control_rx: mpsc::UnboundedReceiver<Control>,
+    /// Kept around when nobody else can shut the session down, as dropping it would do just that
+    _shutdown_tx: Option<watch::Sender<()>>,
+}
+impl CrawlSession {
+    #[must_use]
+    pub fn builder() -> CrawlSessionBuilder {
+        CrawlSessionBuilder::default()
+    }
+
+    /// Crawls every site, returning the statistics aggregated across all of them.
+    pub async fn run(self) -> Result<Output> {
+        let Self {
+            mut crawlers,
+            assigner,
+            max_duration,
+            control_rx,
+            _shutdown_tx,
+            ..
+        } = self;
+
+        // the clock starts with the crawl, not with whatever came before building it
+        let deadline = deadline_after(max_duration);
+        crawlers.shared.deadline = deadline.map(Instant::into_std);
+        let assigner = assigner.deadline(deadline);
+
+        for seat in 0..crawlers.seats.len() {
+            crawlers.spawn(seat);
+        }
+        if crawlers.shared.config.respawn.warm_spare {
+            crawlers.warm_spare();
+        }
+        tokio::spawn(assigner.run(crawlers.shutdown_rx.clone()).in_current_span());
+
+        let output = crawlers.shared.output.clone();
+        crawlers.run(control_rx).await?;
+        Ok(output)
+    }
+
+    /// The number of sites in the list, going by its last index, or the
+    /// [maximum](CrawlSessionBuilder::max_sites) on top of the
+    /// [skipped](CrawlSessionBuilder::skip_sites) ones if that's fewer.
+    #[must_use]
+    pub fn sites_count(&self) -> usize {
+        self.sites_count
+    }
+    #[must_use]
+    pub fn output(&self) -> &Output {
+        &self.crawlers.shared.output
+    }
+    /// What each crawler is currently up to.
+    #[must_use]
+    pub fn dashboard(&self) -> &Dashboard {
+        &self.crawlers.shared.dashboard
+    }
+    /// The process IDs of the running WebDrivers.
+    #[must_use]
+    pub fn driver_pids(&self) -> &DriverPids {
+        &self.crawlers.shared.driver_pids
+    }
+    /// Where to ask for crawlers to be added, retired, or to skip their current site.
+    #[must_use]
+    pub fn control(&self) -> mpsc::UnboundedSender<Control> {
+        self.control_tx.clone()
+    }
+}
+
+/// Settings for a [`CrawlSession`].
+///
+/// Only the list of sites, or a [coordinator](Self::coordinator) to get them from,
+/// and either a WebDriver binary, some remote WebDrivers or a [grid](Config::grid) are required.
+pub struct CrawlSessionBuilder {
+    driver: Option<PathBuf>,
+    remotes: Vec<Url>,
+    sites: Option<PathBuf>,
+    coordinator: Option<CoordinatorClient>,
+    workers: Port,
+    base_port: Port,
+    headless: bool,
+    driver_kind: Option<DriverKind>,
+    config: Config,
+    output: Option<Output>,
+    dashboard: Dashboard,
+    shutdown_rx: Option<ShutdownRx>,
+    skip_sites: HashSet<String>,
+    max_sites: Option<usize>,
+    max_duration: Option<Duration>,
+    autoscale: Option<AutoscalePolicy>,
+    dedup: bool,
+    resolve_ahead: Option<usize>,
+}
+impl Default for CrawlSessionBuilder {
+    fn default() -> Self {
+        Self {
+            driver: None,
+            remotes: Vec::new(),
+            sites: None,
+            coordinator: None,
+            workers: 3,
+            base_port: 4444,
+            headless: true,
+            driver_kind: None,
+            config: Config::default(),
+            output: None,
+            dashboard: Dashboard::default(),
+            shutdown_rx: None,
+            skip_sites: HashSet::new(),
+            max_sites: None,
+            max_duration: None,
+            autoscale: None,
+            dedup: false,
+            resolve_ahead: None,
+        }
+    }
+}
+impl CrawlSessionBuilder {
+    /// The WebDriver binary each crawler runs, e.g. `chromedriver` or `geckodriver`.
+    #[must_use]
+    pub fn driver(mut self, driver: impl Into<PathBuf>) -> Self {
+        self.driver = Some(driver.into());
+        self
+    }
+    /// A WebDriver that's already running, e.g. in a container, to connect to instead of spawning one.
+    ///
+    /// Crawlers are spread across all the remote WebDrivers given, and no binary is spawned at all.
+    #[must_use]
+    pub fn remote(mut self, url: Url) -> Self {
+        self.remotes.push(url);
+        self
+    }
+    /// A file listing the sites to crawl, as `index,domain` lines.
+    #[must_use]
+    pub fn sites(mut self, sites: impl Into<PathBuf>) -> Self {
+        self.sites = Some(sites.into());
+        self
+    }
+    /// A [coordinator](crate::coordinator) to get the sites to crawl from, instead of a list,
+    /// as one of many workers.
+    #[must_use]
+    pub fn coordinator(mut self, client: CoordinatorClient) -> Self {
+        self.coordinator = Some(client);
+        self
+    }
+    /// How many crawlers run at once (default: 3).
+    #[must_use]
+    pub fn workers(mut self, workers: Port) -> Self {
+        self.workers = workers;
+        self
+    }
+    /// The port of the first crawler's WebDriver, with the others counting up from it
+    /// and skipping any that are taken (default: 4444).
+    ///
+    /// With 0, each WebDriver gets whichever port the OS picks instead.
+    #[must_use]
+    pub fn base_port(mut self, base_port: Port) -> Self {
+        self.base_port = base_port;
+        self
+    }
+    /// Whether the browsers run without a window (default: yes).
+    #[must_use]
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+    /// Which kind of driver the WebDrivers are, instead of guessing it from the binary's name,
+    /// or not knowing at all for remote ones.
+    #[must_use]
+    pub fn driver_kind(mut self, kind: DriverKind) -> Self {
+        self.driver_kind = Some(kind);
+        self
+    }
+    #[must_use]
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+    /// Where the results go.
+    ///
+    /// By default, the statistics use the configured vocabulary and the per-site records are discarded.
+    #[must_use]
+    pub fn output(mut self, output: Output) -> Self {
+        self.output = Some(output);
+        self
+    }
+    #[must_use]
+    pub fn dashboard(mut self, dashboard: Dashboard) -> Self {
+        self.dashboard = dashboard;
+        self
+    }
+    /// Stops the crawl once anything is sent, or every sender is dropped.
+    #[must_use]
+    pub fn shutdown(mut self, shutdown_rx: ShutdownRx) -> Self {
+        self.shutdown_rx = Some(shutdown_rx);
+        self
+    }
+
+    /// Leaves out the sites with these URLs, e.g. because a previous run already crawled them.
+    #[must_use]
+    pub fn skip_sites(mut self, urls: HashSet<String>) -> Self {
+        self.skip_sites = urls;
+        self
+    }
+
+    /// Leaves out the sites listed more than once, keeping track of the ones seen in memory
+    /// that doesn't grow with every site, at the cost of very rarely leaving out one that isn't.
+    #[must_use]
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Looks up the domains of as many as `ahead` upcoming sites at once, leaving out the ones
+    /// that don't resolve rather than having a crawler find out.
+    #[must_use]
+    pub fn resolve_ahead(mut self, ahead: usize) -> Self {
+        self.resolve_ahead = Some(ahead);
+        self
+    }
+
+    /// Stops assigning sites after this many, and winds down once they're done.
+    #[must_use]
+    pub fn max_sites(mut self, max_sites: usize) -> Self {
+        self.max_sites = Some(max_sites);
+        self
+    }
+    /// Stops assigning sites after the crawl has run for this long, and winds down
+    /// once the ones in progress are done.
+    #[must_use]
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Starts with the policy's minimum of crawlers, and adds and retires them as the machine
+    /// and the sites allow, instead of running a fixed number of them.
+    #[must_use]
+    pub fn autoscale(mut self, policy: AutoscalePolicy) -> Self {
+        self.workers = policy.min_workers;
+        self.autoscale = Some(policy);
+        self
+    }
+
+    pub async fn build(mut self) -> Result<CrawlSession> {
+        // a grid is just another remote WebDriver, only one that can hold many sessions at once
+        self.remotes.extend(self.config.grid.clone());
+        let kind = self.driver_kind;
+        let drivers: Vec<_> = if self.remotes.is_empty() {
+            let driver = self.driver.map(|path| WebDriver::spawn(path, kind));
+            driver.into_iter().collect()
+        } else {
+            let remotes = self.remotes.into_iter();
+            remotes.map(|url| WebDriver::remote(url, kind)).collect()
+        };
+        let seats = Seat::assign(
+            &drivers,
+            self.headless,
+            &self.config.capabilities,
+            self.workers,
+            &self.config.profiles,
+        )?;
+        // better now than once every crawler has failed to start
+        seats.iter().try_for_each(Seat::check_driver)?;
+
+        let output = if let Some(output) = self.output {
+            output
+        } else {
+            let (records_tx, mut records_rx) = mpsc::unbounded_channel();
+            tokio::spawn(async move { while records_rx.recv().await.is_some() {} });
+            Output::load(records_tx, &self.config).await?
+        };
+        let (shutdown_tx, shutdown_rx) = if let Some(rx) = self.shutdown_rx {
+            (None, rx)
+        } else {
+            let (tx, rx) = watch::channel(());
+            (Some(tx), rx)
+        };
+
+        let eval_script = match &self.config.eval_script {
+            Some(path) => Some(
+                tokio::fs::read_to_string(path)
+                    .await
+                    .wrap_err_with(|| format!("Failed to read eval script {}", path.display()))?
+                    .into(),
+            ),
+            None => None,
+        };
+        // the queue has to keep up with as many crawlers as there may ever be
+        let max_workers = self
+            .autoscale
+            .as_ref()
+            .map_or(seats.len(), |policy| usize::from(policy.max_workers()));
+        let mut crawlers = Crawlers::new(
+            seats,
+            max_workers,
+            self.base_port,
+            self.config,
+            output,
+            self.dashboard,
+            shutdown_rx,
+        )?;
+        crawlers.shared.eval_script = eval_script;
+        crawlers.autoscaler = self.autoscale.map(Autoscaler::new);
+        let queue = crawlers.shared.job_queue.clone();
+        let (assigner, sites_count) = match (self.coordinator, self.sites) {
+            (Some(client), _) => {
+                let status = client.status().await?;
+                let sites = RemoteSites::new(client, max_workers);
+                let left = status.total.saturating_sub(status.completed);
+                (Assigner::remote(sites, queue), left)
+            }
+            (None, Some(sites)) => Assigner::new(&sites, queue).await?,
+            (None, None) => bail!("A list of sites to crawl is required"),
+        };
+        let visited = self
+            .dedup
+            .then(|| Visited::new(sites_count, DEDUP_FALSE_POSITIVE_RATE));
+        // the maximum is of the sites crawled this time round
+        let skipped = self.skip_sites.len();
+        let assigner = assigner
+            .skip(self.skip_sites)
+            .dedup(visited)
+            .resolve_ahead(self.resolve_ahead)
+            .record_left_out(
+                crawlers.shared.output.clone(),
+                crawlers.shared.dashboard.clone(),
+            )
+            .limit(self.max_sites);
+        let sites_count = self
+            .max_sites
+            .map_or(sites_count, |max| sites_count.min(skipped + max));
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+
+        Ok(CrawlSession {
+            crawlers,
+            assigner,
+            sites_count,
+            max_duration: self.max_duration,
+            control_tx,
+            control_rx,
+            _shutdown_tx: shutdown_tx,
+        })
+    }
+
+    /// Builds the session and crawls every site.
+    pub async fn run(self) -> Result<Output> {
+        self.build().await?.run().await
+    }
+}
+
+/// How one of the crawlers started along with the session gets its browser.
+#[derive(Clone, Debug)]
+struct Seat {
+    driver: WebDriver,
+    caps: Capabilities,
+    profile: Option<String>,
+}
+impl Seat {
+    /// Asks for the driver's own capabilities first, and then for the ones given,
+    /// most specific last.
+    fn new(
+        driver: WebDriver,
+        headless: bool,
+        extra: &[&Capabilities],
+        profile: Option<String>,
+    ) -> Self {
+        let mut caps = driver.kind().capabilities(headless);
+        for extra in extra {
+            caps.extend((*extra).clone());
+        }
+        Self {
+            driver,
+            caps,
+            profile,
+        }
+    }
+
+    /// Makes sure there's a WebDriver binary to spawn, if the seat spawns one.
+    fn check_driver(&self) -> Result<()> {
+        if let WebDriver::Spawn { path, .. } = &self.driver {
+            ensure!(
+                driver::exists(path),
+                "No WebDriver binary found at {}",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Lines up the crawlers, either as the profiles say, or `workers` of them
+    /// taking turns with the WebDrivers.
+    fn assign(
+        drivers: &[WebDriver],
+        headless: bool,
+        caps: &Capabilities,
+        workers: Port,
+        profiles: &[Profile],
+    ) -> Result<Vec<Self>> {
+        if profiles.is_empty() {
+            ensure!(
+                !drivers.is_empty(),
+                "A WebDriver binary or a remote WebDriver is required"
+            );
+            return Ok((0..usize::from(workers))
+                .map(|i| {
+                    let driver = drivers[i % drivers.len()].clone();
+                    Self::new(driver, headless, &[caps], None)
+                })
+                .collect());
+        }
+
+        let mut seats = Vec::new();
+        for profile in profiles {
+            let driver = match (&profile.driver, &profile.remote) {
+                (Some(_), Some(_)) => bail!(
+                    "Profile {:?} can't both run a WebDriver and connect to a remote one",
+                    profile.name
+                ),
+                (Some(path), None) => WebDriver::spawn(path.clone(), profile.kind),
+                (None, Some(url)) => WebDriver::remote(url.clone(), profile.kind),
+                (None, None) => {
+                    let driver = drivers.first().cloned().wrap_err_with(|| {
+                        format!("Profile {:?} needs a WebDriver to run", profile.name)
+                    })?;
+                    match profile.kind {
+                        Some(kind) => driver.with_kind(kind),
+                        None => driver,
+                    }
+                }
+            };
+            let seat = Self::new(
+                driver,
+                headless,
+                &[caps, &profile.capabilities],
+                Some(profile.name.clone()),
+            );
+            seats.extend(std::iter::repeat_n(seat, usize::from(profile.workers)));
+        }
+        ensure!(!seats.is_empty(), "The profiles don't have any workers");
+        Ok(seats)
+    }
+}
+
+/// How a crawler ended.
+struct Exit {
+    /// So that a replacement can take the same seat
+    seat: usize,
+    /// How many times in a row the seat's crawler had failed to start before this one
+    failures: u32,
+    /// Any error, along with whether it was in starting up, and thus worth respawning over
+    result: Result<(), (bool, eyre::Report)>,
+}
+
+/// A crawler started ahead of time, to take over from one that crashes.
+struct Spare {
+    /// Which seat it was started for, since only seats of the same profile can be taken over
+    seat: usize,
+    crawler: JoinHandle<Result<Crawler>>,
+}
+
+struct Crawlers {
+    set: JoinSet<Exit>,
+    spare: Option<Spare>,
+
+    seats: Vec<Seat>,
+    spawned: usize,
+    /// Failed starts across all crawlers
+    failed_starts: u32,
+    port: Port,
+    shared: Shared,
+    shutdown_rx: ShutdownRx,
+    autoscaler: Option<Autoscaler>,
+}
+impl Crawlers {
+    fn new(
+        seats: Vec<Seat>,
+        max_workers: usize,
+        base_port: Port,
+        config: Config,
+        output: Output,
+        dashboard: Dashboard,
+        shutdown_rx: ShutdownRx,
+    ) -> Result<Self> {
+        let job_queue = Arc::new(SiteQueue::new(max_workers * 2));
+        dashboard.watch_queue(job_queue.clone());
+        let limiter = match config.max_rps {
+            Some(rps) => Some(Arc::new(RateLimiter::new(rps)?)),
+            None => None,
+        };
+        let bandwidth_limiter = config
+            .max_bandwidth
+            .map(|bytes| Arc::new(BandwidthLimiter::new(bytes)));
+
+        Ok(Self {
+            set: JoinSet::new(),
+            spare: None,
+            shared: Shared {
+                config: Arc::new(config),
+                output,
+                job_queue,
+                limiter,
+                bandwidth_limiter,
+                dashboard,
+                retiring: Arc::default(),
+                skip_tx: broadcast::channel(16).0,
+                driver_pids: DriverPids::default(),
+                deadline: None,
+                eval_script: None,
+            },
+            seats,
+            spawned: 0,
+            failed_starts: 0,
+            port: base_port,
+            shutdown_rx,
+            autoscaler: None,
+        })
+    }
+    fn spawn(&mut self, seat: usize) {
+        self.spawn_after(seat, 0, Duration::ZERO);
+    }
+    fn spawn_after(&mut self, seat: usize, failures: u32, delay: Duration) {
+        let crawler = self.new_crawler(seat);
+        self.start(seat, failures, delay, crawler);
+    }
+
+    /// A crawler for the seat, with a port of its own.
+    fn new_crawler(
+        &mut self,
+        seat: usize,
+    ) -> impl Future<Output = Result<Crawler>> + Send + 'static {
+        let Seat {
+            driver,
+            caps,
+            profile,
+        } = self.seats[seat].clone();
+        let port = match driver {
+            WebDriver::Spawn { .. } => self.free_port(),
+            // remote crawlers don't listen on anything, and only need a port to tell them apart
+            WebDriver::Remote { .. } => {
+                let port = self.port;
+                self.port = self.port.wrapping_add(1);
+                port
+            }
+        };
+        Crawler::new(driver, port, caps, profile, self.shared.clone())
+    }
+
+    /// Runs the crawler once it's started, and after the delay.
+    fn start(
+        &mut self,
+        seat: usize,
+        failures: u32,
+        delay: Duration,
+        crawler: impl Future<Output = Result<Crawler>> + Send + 'static,
+    ) {
+        let mut rx = self.shutdown_rx.clone();
+
+        self.set.spawn(
+            async move {
+                let exit = |result| Exit {
+                    seat,
+                    failures,
+                    result,
+                };
+                tokio::select! {
+                    _ = rx.changed() => return exit(Ok(())),
+                    () = tokio::time::sleep(delay) => {}
+                }
+                exit(match crawler.await {
+                    Ok(c) => c.run(rx).await.map_err(|e| (false, e)),
+                    Err(e) => Err((true, e)),
+                })
+            }
+            .in_current_span(),
+        );
+        self.spawned += 1;
+    }
+
+    /// Starts a crawler in the background to stand by, for the next seat in turn.
+    fn warm_spare(&mut self) {
+        let seat = self.spawned % self.seats.len();
+        let crawler = self.new_crawler(seat);
+        self.spare = Some(Spare {
+            seat,
+            crawler: tokio::spawn(crawler.in_current_span()),
+        });
+    }
+
+    /// Hands the seat of a crawler that crashed to the spare, if it's of the same profile,
+    /// and warms up another one.
+    fn take_over(&mut self, seat: usize) {
+        let profile = &self.seats[seat].profile;
+        let Some(spare) = self
+            .spare
+            .take_if(|spare| self.seats[spare.seat].profile == *profile)
+        else {
+            return;
+        };
+        info!("Handing the crashed crawler's seat to the spare");
+        self.start(seat, 0, Duration::ZERO, async move { spare.crawler.await? });
+        self.warm_spare();
+    }
+
+    /// Stops the spare, which was never needed.
+    async fn close_spare(&mut self) {
+        let Some(spare) = self.spare.take() else {
+            return;
+        };
+        // one that failed to start has nothing to close
+        if let Ok(Ok(crawler)) = spare.crawler.await {
+            if let Err(e) = crawler.close().await {
+                warn!(%e, "Failed to close the spare crawler");
+            }
+        }
+    }
+
+    /// Finds a port for a new WebDriver to listen on: the next one up that's free,
+    /// or one picked by the OS if there are none left, or if the base port was 0.
+    fn free_port(&mut self) -> Port {
+        let is_free = |port| TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_ok();
+        if self.port != 0 {
+            while self.port < Port::MAX {
+                let port = self.port;
+                self.port += 1;
+                if is_free(port) {
+                    return port;
+                }
+                warn!(port, "Port already in use - skipping");
+            }
+        }
+
+        match TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).and_then(|l| l.local_addr()) {
+            Ok(addr) => addr.port(),
+            Err(e) => {
+                // the driver will most likely fail to start too, with a better error
+                error!(%e, "Failed to get a port from the OS");
+                0
+            }
+        }
+    }
+    async fn run(mut self, mut control_rx: mpsc::UnboundedReceiver<Control>) -> Result<()> {
+        loop {
+            tokio::select! {
+                res = self.set.join_next() => {
+                    let Some(res) = res else { break };
+                    let exit = res?;
+                    if let Err((respawn, e)) = exit.result {
+                        error!(?e, "Encountered error while crawling");
+                        if respawn {
+                            self.respawn(exit.seat, exit.failures + 1)?;
+                        } else {
+                            self.take_over(exit.seat);
+                        }
+                    }
+                }
+                Some(control) = control_rx.recv() => self.control(control),
+                () = autoscale::tick(self.autoscaler.as_mut()) => self.autoscale(),
+            }
+        }
+
+        self.close_spare().await;
+
+        let given_up = self.shared.dashboard.get().given_up;
+        ensure!(
+            given_up == 0 || self.shared.job_queue.is_empty(),
+            "Gave up on {given_up} crawlers, and nobody's left to crawl the remaining sites"
+        );
+        Ok(())
+    }
+
+    /// Starts a crawler that failed to start again, unless the respawn policy says to give up.
+    fn respawn(&mut self, seat: usize, failures: u32) -> Result<()> {
+        let policy = &self.shared.config.respawn;
+        self.failed_starts += 1;
+        if let Some(max) = policy.give_up_after {
+            ensure!(
+                self.failed_starts < max,
+                "Crawlers failed to start {} times - giving up on the crawl",
+                self.failed_starts
+            );
+        }
+        if failures >= policy.attempts {
+            error!(
+                failures,
+                "Crawler failed to start too many times - giving up on it"
+            );
+            self.shared.dashboard.gave_up();
+            return Ok(());
+        }
+
+        let backoff = policy.backoff(failures);
+        warn!(failures, ?backoff, "Attempting to respawn");
+        self.shared.dashboard.respawned();
+        self.spawn_after(seat, failures, backoff);
+        Ok(())
+    }
+    fn autoscale(&mut self) {
+        let workers = self.workers();
+        let Some(autoscaler) = &mut self.autoscaler else {
+            return;
+        };
+        match autoscaler.decide(workers, &self.shared) {
+            Some(Control::AddWorker) => self.spawn(self.spawned % self.seats.len()),
+            Some(Control::RetireWorker) => {
+                self.shared.retiring.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+    /// Crawlers running that aren't about to retire.
+    fn workers(&self) -> usize {
+        // crawlers may well exit some other way before they get around to retiring
+        self.set
+            .len()
+            .saturating_sub(self.shared.retiring.load(Ordering::Relaxed))
+    }
+    fn control(&mut self, control: Control) {
+        match control {
+            Control::AddWorker => {
+                info!("Adding a crawler on request");
+                self.spawn(self.spawned % self.seats.len());
+            }
+            Control::Skip(port) => {
+                info!(port, "Skipping a crawler's current site on request");
+                // no receivers just means there's no crawler left to skip anything
+                let _ = self.shared.skip_tx.send(port);
+            }
+            Control::RetireWorker => {
+                // keep at least one crawler around, or the remaining sites would never get crawled
+                let retiring = self.shared.retiring.load(Ordering::Relaxed);
+                if self.set.len() > retiring + 1 {
+                    info!("Retiring a crawler on request");
+                    self.shared.retiring.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vocabulary::Vocabulary;
+
+    #[tokio::test]
+    async fn counts_no_workers_when_a_crawler_exits_with_a_retirement_pending() {
+        let (records_tx, _) = mpsc::unbounded_channel();
+        let config = Config::default();
+        let output = Output::new(records_tx, &config, Vocabulary::builtin());
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let mut crawlers = Crawlers::new(
+            Vec::new(),
+            1,
+            0,
+            config,
+            output,
+            Dashboard::default(),
+            shutdown_rx,
+        )
+        .unwrap();
+
+        crawlers.set.spawn(async {
+            Exit {
+                seat: 0,
+                failures: 0,
+                result: Err((false, eyre::eyre!("crashed"))),
+            }
+        });
+        crawlers.shared.retiring.fetch_add(1, Ordering::Relaxed);
+        crawlers.set.join_next().await.unwrap().unwrap();
+
+        // one retiring with none left running mustn't underflow
+        assert_eq!(crawlers.workers(), 0);
+    }
+}
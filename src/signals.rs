@@ -0,0 +1,89 @@
+use eyre::Result;
+use tokio::sync::mpsc;
+
+/// Where requests to stop come from besides signals, e.g. the [status API](crate::api),
+/// each saying who made the request.
+pub type StopRequests = mpsc::UnboundedReceiver<&'static str>;
+
+/// Requests to stop from outside, e.g. from systemd, `timeout` or `kill`, and from Ctrl-C
+/// when there's no terminal UI to catch it as a key.
+pub struct Signals {
+    #[cfg(unix)]
+    streams: Vec<(&'static str, tokio::signal::unix::Signal)>,
+    requests: Option<StopRequests>,
+}
+impl Signals {
+    #[cfg(unix)]
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let streams = [
+            ("SIGINT", SignalKind::interrupt()),
+            ("SIGTERM", SignalKind::terminate()),
+            ("SIGHUP", SignalKind::hangup()),
+        ]
+        .into_iter()
+        .map(|(name, kind)| Ok((name, signal(kind)?)))
+        .collect::<Result<_>>()?;
+        Ok(Self {
+            streams,
+            requests: None,
+        })
+    }
+    #[cfg(not(unix))]
+    pub fn new() -> Result<Self> {
+        Ok(Self { requests: None })
+    }
+
+    /// Also takes requests to stop from elsewhere, as if they were signals.
+    #[must_use]
+    pub fn with_requests(mut self, requests: Option<StopRequests>) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Waits for the next signal or request, returning its name.
+    pub async fn recv(&mut self) -> &'static str {
+        let requests = &mut self.requests;
+        let request = async {
+            match requests {
+                Some(requests) => match requests.recv().await {
+                    Some(from) => from,
+                    // nobody can ask anymore
+                    None => std::future::pending().await,
+                },
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(unix)]
+        let signal = Self::recv_signal(&mut self.streams);
+        #[cfg(not(unix))]
+        let signal = Self::recv_signal();
+
+        tokio::select! {
+            from = request => from,
+            signal = signal => signal,
+        }
+    }
+
+    #[cfg(unix)]
+    async fn recv_signal(
+        streams: &mut [(&'static str, tokio::signal::unix::Signal)],
+    ) -> &'static str {
+        let received = streams.iter_mut().map(|(name, stream)| {
+            Box::pin(async move {
+                stream.recv().await;
+                *name
+            })
+        });
+        futures_util::future::select_all(received).await.0
+    }
+    #[cfg(not(unix))]
+    async fn recv_signal() -> &'static str {
+        // without a handler, Ctrl-C would just kill us
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        "Ctrl-C"
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
+    sync::{broadcast, mpsc},
+};
+use tracing::*;
+use ulid::Ulid;
+
+use crate::{
+    bandwidth::TransferSummary,
+    cooccurrence::Pair,
+    coordinator::CoordinatorClient,
+    custom,
+    domains::DomainSummary,
+    foreign::ForeignTag,
+    forms,
+    geometry::TagGeometry,
+    obsolete::Usage,
+    report::Census,
+    script::MetricSummary,
+    semantic,
+    state::{OutcomeSummary, Output, SiteRecord},
+    tables,
+    timing::{Timing, TimingSummary},
+    topk::TopItem,
+    util::ObsoleteTag,
+    vocabulary::Vocabulary,
+};
+
+/// Writes every received [`SiteRecord`] as a line of JSON, until all senders are gone.
+///
+/// Existing results are kept when `append`ing, and overwritten otherwise.
+#[tracing::instrument(skip(rx))]
+pub async fn write_records(
+    path: &Path,
+    append: bool,
+    mut rx: mpsc::UnboundedReceiver<SiteRecord>,
+) -> Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .await?;
+    let mut file = BufWriter::new(file);
+    let mut line = Vec::new();
+
+    while let Some(record) = rx.recv().await {
+        line.clear();
+        serde_json::to_writer(&mut line, &record)?;
+        line.push(b'\n');
+
+        file.write_all(&line).await?;
+        // flush eagerly so a crashed run still leaves its results behind
+        file.flush().await?;
+    }
+
+    info!("All site records written");
+    Ok(())
+}
+
+/// Passes every received [`SiteRecord`] on, announcing it to whoever's listening first,
+/// e.g. the [status API](crate::api).
+#[must_use]
+pub fn tee(
+    mut rx: mpsc::UnboundedReceiver<SiteRecord>,
+    announce: broadcast::Sender<Arc<SiteRecord>>,
+) -> mpsc::UnboundedReceiver<SiteRecord> {
+    let (tx, teed_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(record) = rx.recv().await {
+            // nobody listening is fine
+            let _ = announce.send(Arc::new(record.clone()));
+            if tx.send(record).is_err() {
+                break;
+            }
+        }
+    });
+    teed_rx
+}
+
+/// Sends every received [`SiteRecord`] to the coordinator, along with its tag counts in the
+/// `vocabulary`, until all senders are gone.
+#[tracing::instrument(skip_all)]
+pub async fn send_records(
+    client: CoordinatorClient,
+    vocabulary: Vocabulary,
+    mut rx: mpsc::UnboundedReceiver<SiteRecord>,
+) -> Result<()> {
+    while let Some(record) = rx.recv().await {
+        // sites that weren't walked, e.g. failed ones, have no tags to count
+        let census = if record.tag_counts.is_empty() {
+            Census::default()
+        } else {
+            Census::count(&vocabulary, &record.tag_counts)
+        };
+        // not lost for good: the site's lease runs out, and it's handed out again
+        if let Err(e) = client.send_record(&record, census).await {
+            warn!(%e, url = record.url, "Failed to send site record to the coordinator");
+        }
+    }
+
+    info!("All site records sent");
+    Ok(())
+}
+
+/// Reads the URLs of the sites in a results file written by [`write_records`].
+pub async fn read_record_urls(path: &Path) -> Result<HashSet<String>> {
+    #[derive(Deserialize)]
+    struct Record {
+        url: String,
+    }
+
+    let mut urls = HashSet::new();
+    let mut lines = BufReader::new(File::open(path).await?).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str::<Record>(&line) {
+            Ok(record) => {
+                urls.insert(record.url);
+            }
+            // most likely the last line, cut short by a crash
+            Err(e) => warn!(%e, "Skipping unreadable site record"),
+        }
+    }
+    Ok(urls)
+}
+
+/// A site as a previous run recorded it, with what it takes to [replay](crate::offline::Snapshots::replay) it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Recorded {
+    pub url: String,
+    pub crawled_at: u64,
+    pub run: Option<Ulid>,
+    pub snapshot: Option<PathBuf>,
+    /// Missing from records written before sites were timed
+    #[serde(default)]
+    pub timing: Timing,
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub excluded: Option<String>,
+    #[serde(default)]
+    pub skipped: bool,
+}
+impl Recorded {
+    /// Whether the site was left out of the statistics, having failed, been skipped or
+    /// excluded.
+    #[must_use]
+    pub fn left_out(&self) -> bool {
+        self.error.is_some() || self.excluded.is_some() || self.skipped
+    }
+
+    /// The site's record again, as far as the outcome goes, for sites that didn't make it
+    /// into the statistics and so have nothing else to reproduce.
+    #[must_use]
+    pub fn outcome(self) -> SiteRecord {
+        let mut record = SiteRecord::new(self.url);
+        record.crawled_at = self.crawled_at;
+        record.timing = self.timing;
+        record.status = self.status;
+        record.retries = self.retries;
+        record.error = self.error;
+        record.excluded = self.excluded;
+        record.skipped = self.skipped;
+        record.snapshot = self.snapshot;
+        record
+    }
+}
+
+/// The records in a results file, in the order they were written.
+pub async fn read_records(path: &Path) -> Result<Vec<Recorded>> {
+    let mut records = vec![];
+    let mut lines = BufReader::new(File::open(path).await?).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            // most likely the last line, cut short by a crash
+            Err(e) => warn!(%e, "Skipping unreadable site record"),
+        }
+    }
+    Ok(records)
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    /// The run the statistics are of, if it has an ID
+    run: Option<Ulid>,
+    /// How many sites failed, were skipped or left out, and so on
+    outcomes: OutcomeSummary,
+    /// Including the baseline, if the run started from one
+    freq: BTreeMap<&'a str, u64>,
+    /// Including the baseline, if the run started from one
+    categories: BTreeMap<&'a str, u64>,
+    /// The tag counts of the previous run this one started from, which only `freq` and
+    /// `categories` include
+    baseline: Option<BTreeMap<&'a str, u64>>,
+    /// The tag histogram with each domain weighing the same
+    domain_freq: BTreeMap<String, f64>,
+    domains: Vec<DomainSummary>,
+    geometry: BTreeMap<&'a str, TagGeometry>,
+    classes: Vec<TopItem>,
+    cooccurrence: Vec<Pair>,
+    obsolete: BTreeMap<ObsoleteTag, Usage>,
+    semantics: semantic::Summary,
+    custom_elements: custom::Summary,
+    foreign: Vec<ForeignTag>,
+    forms: forms::Summary,
+    tables: tables::Summary,
+    unknown_tags: Vec<TopItem>,
+    script_metrics: BTreeMap<String, MetricSummary>,
+    timing: TimingSummary,
+    transferred: TransferSummary,
+}
+
+impl<'a> Summary<'a> {
+    async fn new(output: &'a Output) -> Summary<'a> {
+        let vocabulary = &output.vocabulary;
+        let counts = output.freq.get();
+        let freq = vocabulary.names().zip(counts.iter().copied()).collect();
+        let categories = vocabulary.group_totals(&counts).collect();
+        let baseline = output
+            .baseline
+            .as_ref()
+            .map(|baseline| vocabulary.names().zip(baseline.iter().copied()).collect());
+        let geometry = output
+            .geometry
+            .get()
+            .await
+            .iter()
+            .map(|(&tag, geometry)| (vocabulary.name(tag), geometry.clone()))
+            .collect();
+
+        Summary {
+            run: output.run,
+            outcomes: output.outcomes.summary(),
+            freq,
+            categories,
+            baseline,
+            domain_freq: output.domains.freq().await.into_iter().collect(),
+            domains: output.domains.summary().await,
+            geometry,
+            classes: output.classes.top().await,
+            cooccurrence: output.cooccurrence.pairs().await,
+            obsolete: output.obsolete.usage().await,
+            semantics: output.semantics.summary().await,
+            custom_elements: output.custom_elements.summary().await,
+            foreign: output.foreign.tags().await,
+            forms: output.forms.summary().await,
+            tables: output.tables.summary().await,
+            unknown_tags: output.unknown_tags.top().await,
+            script_metrics: output.script_metrics.summary().await,
+            timing: output.timing.summary(),
+            transferred: output.bandwidth.summary(),
+        }
+    }
+}
+
+/// Writes the statistics aggregated across all sites as a JSON document.
+#[tracing::instrument(skip(output))]
+pub async fn write_summary(path: &Path, output: &Output) -> Result<()> {
+    let json = serde_json::to_vec_pretty(&Summary::new(output).await)?;
+    tokio::fs::write(path, json).await?;
+
+    info!("Summary written");
+    Ok(())
+}
+
+/// Appends the statistics of one of a series of scheduled runs as a line of JSON,
+/// along with when it ran and how many sites it crawled.
+#[tracing::instrument(skip(output))]
+pub async fn append_run(
+    path: &Path,
+    started: SystemTime,
+    finished: SystemTime,
+    sites: usize,
+    output: &Output,
+) -> Result<()> {
+    #[derive(Serialize)]
+    struct Run<'a> {
+        started: u64,
+        finished: u64,
+        sites: usize,
+        summary: Summary<'a>,
+    }
+
+    let mut line = serde_json::to_vec(&Run {
+        started: started.duration_since(UNIX_EPOCH)?.as_secs(),
+        finished: finished.duration_since(UNIX_EPOCH)?.as_secs(),
+        sites,
+        summary: Summary::new(output).await,
+    })?;
+    line.push(b'\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&line).await?;
+
+    info!("Run appended to the time series");
+    Ok(())
+}
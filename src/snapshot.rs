@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+use fantoccini::Client;
+use openssl::sha::sha256;
+use url::Url;
+
+/// Saves the serialized DOM of the currently loaded page into `dir`,
+/// returning the path of the snapshot.
+///
+/// The snapshot starts with a `saved from url` comment like browsers write,
+/// so that the original URL can be recovered later.
+pub async fn save(client: &Client, url: &Url, dir: &Path) -> Result<PathBuf> {
+    let html = client
+        .execute("return document.documentElement.outerHTML;", vec![])
+        .await?;
+    let html = html.as_str().unwrap_or_default();
+
+    let path = dir.join(file_name(url));
+    let contents = format!(
+        "<!-- saved from url=({:04}){url} -->\n{html}",
+        url.as_str().len()
+    );
+    tokio::fs::write(&path, contents).await?;
+
+    Ok(path)
+}
+
+/// A name for the URL's snapshot that is readable but also unique, since URLs that only
+/// differ in characters a file name can't have would otherwise share it.
+fn file_name(url: &Url) -> String {
+    let name: String = url[url::Position::BeforeHost..url::Position::AfterQuery]
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let [a, b, c, d, ..] = sha256(url.as_str().as_bytes());
+    let hash = u32::from_be_bytes([a, b, c, d]);
+    format!("{}-{hash:08x}.html", name.trim_end_matches('_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_similar_urls_apart() {
+        let name = |url| file_name(&Url::parse(url).unwrap());
+        assert!(name("https://example.com/a/b").starts_with("example.com_a_b-"));
+        assert_ne!(
+            name("https://example.com/a/b"),
+            name("https://example.com/a_b")
+        );
+        assert_eq!(name("https://example.com/"), name("https://example.com/"));
+    }
+}
@@ -1,99 +1,568 @@
 use std::{
-    str::FromStr,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use eyre::Result;
 use fantoccini::{elements::Element, Client};
-use strum::EnumCount;
-use tokio::sync::{RwLock, RwLockReadGuard};
+use serde::Serialize;
+use tokio::sync::mpsc;
 use tracing::*;
+use ulid::Ulid;
+use url::Url;
 
-use crate::util::Tag;
+use crate::{
+    accessibility::{Aria, Headings, MediaAlternatives},
+    analyzer::{Analyzer, PageContext, Pipeline},
+    bandwidth::Bandwidth,
+    config::Config,
+    cooccurrence::Cooccurrence,
+    crawler::Browser,
+    custom::CustomElements,
+    domains::Domains,
+    findings::Findings,
+    foreign::{self, ForeignCounts, ForeignElements, Namespace},
+    forms::{FormUsage, Forms},
+    geometry::Geometry,
+    language::Language,
+    meta::Meta,
+    metrics::{InlineCode, TextContent, TreeShape},
+    obsolete::Obsolete,
+    parked,
+    plugin::Plugin,
+    psl::PublicSuffixList,
+    redirect::Redirects,
+    report::Census,
+    robots,
+    script::{Script, ScriptMetrics},
+    security::Security,
+    semantic::{SemanticUsage, Semantics},
+    tables::{TableUsage, Tables},
+    timing::{SiteTimes, Timing},
+    topk::TopK,
+    util::ObsoleteTag,
+    vocabulary::Vocabulary,
+};
 
+/// How often each tag appears, across all sites.
+///
+/// Every tag has a counter of its own, so crawlers never wait on each other to count.
 #[derive(Clone, Debug)]
 pub struct Freq {
-    inner: Arc<RwLock<[u64; Tag::COUNT]>>,
+    counts: Arc<[AtomicU64]>,
     dirty: Arc<AtomicBool>,
 }
 
 impl Freq {
-    pub async fn get(&self) -> RwLockReadGuard<'_, [u64; Tag::COUNT]> {
-        self.inner.read().await
+    #[must_use]
+    pub fn new(len: usize) -> Self {
+        Self {
+            counts: (0..len).map(|_| AtomicU64::new(0)).collect(),
+            dirty: Arc::default(),
+        }
     }
+    /// The counts of every tag, indexed by [`Tag::index`](crate::vocabulary::Tag::index).
+    #[must_use]
+    pub fn get(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect()
+    }
+    #[must_use]
     pub fn is_dirty(&self) -> bool {
         self.dirty.load(Ordering::Relaxed)
     }
     pub fn mark_dirty(&self) {
         self.dirty.store(true, Ordering::Relaxed);
     }
-    pub async fn bump(&self, tag: Tag) {
-        let mut inner = self.inner.write().await;
-        inner[tag as usize] += 1;
+    /// Adds a whole page's counts at once, indexed by
+    /// [`Tag::index`](crate::vocabulary::Tag::index).
+    pub fn add(&self, counts: &[u64]) {
+        for (total, &n) in self.counts.iter().zip(counts) {
+            if n > 0 {
+                total.fetch_add(n, Ordering::Relaxed);
+            }
+        }
         self.mark_dirty();
     }
 }
-impl Default for Freq {
-    fn default() -> Self {
+
+/// How many sites ended up in each of the less happy outcomes.
+#[derive(Clone, Debug, Default)]
+pub struct Outcomes {
+    pub failed: Arc<AtomicU64>,
+    pub retried: Arc<AtomicU64>,
+    pub skipped: Arc<AtomicU64>,
+    pub excluded: Arc<AtomicU64>,
+    /// Sites left out as their robots.txt disallows crawling them, also counted as excluded
+    pub robots: Arc<AtomicU64>,
+    /// Sites left out for being listed more than once, which have no record of their own
+    pub duplicates: Arc<AtomicU64>,
+}
+impl Outcomes {
+    #[must_use]
+    pub fn summary(&self) -> OutcomeSummary {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        OutcomeSummary {
+            failed: load(&self.failed),
+            retried: load(&self.retried),
+            skipped: load(&self.skipped),
+            excluded: load(&self.excluded),
+            robots: load(&self.robots),
+            duplicates: load(&self.duplicates),
+        }
+    }
+
+    fn count(&self, record: &SiteRecord) {
+        let counters = [
+            (&self.failed, record.error.is_some()),
+            (&self.retried, record.retries > 0),
+            (&self.skipped, record.skipped),
+            (&self.excluded, record.excluded.is_some()),
+            (
+                &self.robots,
+                record.excluded.as_deref() == Some(robots::EXCLUSION),
+            ),
+        ];
+        for (counter, applies) in counters {
+            if applies {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// The [`Outcomes`] as of the end of a run.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct OutcomeSummary {
+    pub failed: u64,
+    pub retried: u64,
+    pub skipped: u64,
+    pub excluded: u64,
+    pub robots: u64,
+    pub duplicates: u64,
+}
+
+/// Everything worth knowing about a single crawled site, written out as one
+/// line of the results file.
+///
+/// The types of its fields live in their own public modules:
+///
+/// ```
+/// use quotelementa::{accessibility::Headings, security::Security, state::SiteRecord};
+///
+/// fn outline_is_fine(headings: &Headings) -> bool {
+///     !headings.issues
+/// }
+///
+/// let record = SiteRecord {
+///     headings: Some(Headings::from_sequence(vec![1, 2, 4])),
+///     security: Some(Security { https: true, ..Security::default() }),
+///     ..SiteRecord::default()
+/// };
+/// assert!(!record.headings.as_ref().is_some_and(outline_is_fine));
+/// assert!(record.security.is_some_and(|s| s.https));
+/// ```
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SiteRecord {
+    pub url: String,
+    /// When the site was crawled, in seconds since the Unix epoch
+    pub crawled_at: u64,
+    /// The registrable domain of the URL, e.g. `example.co.uk` for `www.example.co.uk`
+    pub domain: Option<String>,
+    pub browser: Option<Browser>,
+    /// The [profile](crate::config::Profile) of the crawler, if any
+    pub profile: Option<String>,
+    /// HTTP status code of the main document, if the browser exposes it
+    pub status: Option<u16>,
+    /// Number of times navigation had to be retried
+    pub retries: u32,
+    pub redirects: Option<Redirects>,
+    /// Signs that the domain may be parked or a placeholder, rather than an actual site, which
+    /// only add up to it being one in some combinations
+    pub parked: Vec<parked::Sign>,
+    /// Why the site was left out of the statistics, if it was
+    pub excluded: Option<String>,
+    /// Whether crawling the site was abandoned on request
+    pub skipped: bool,
+    /// Number of `alert()`/`confirm()`/`prompt()` dialogs that had to be dismissed
+    pub dialogs_dismissed: u32,
+    /// Number of elements found on the page
+    pub elements: usize,
+    /// Number of elements counted towards the tag frequencies, i.e. the analyzed ones in the
+    /// vocabulary
+    pub counted: u64,
+    /// Whether only a sample of the elements was analyzed
+    pub truncated: bool,
+    /// The page's tag counts, indexed by [`Tag::index`](crate::vocabulary::Tag::index), for a
+    /// [coordinator](crate::coordinator) to add up
+    #[serde(skip)]
+    pub tag_counts: Vec<u64>,
+    /// Front-end frameworks detected on the page
+    pub frameworks: Vec<String>,
+    /// Distinct third-party hosts serving scripts
+    pub third_party_script_origins: usize,
+    /// Distinct known trackers among the third-party script hosts
+    pub trackers: usize,
+    pub inline_code: Option<InlineCode>,
+    pub tree: Option<TreeShape>,
+    /// Number of distinct `id`s shared by more than one element
+    pub duplicate_ids: Option<u64>,
+    pub text: Option<TextContent>,
+    pub language: Option<Language>,
+    pub meta: Option<Meta>,
+    /// Obsolete elements used on the page, by tag
+    pub obsolete_tags: BTreeMap<ObsoleteTag, u64>,
+    /// Number of custom elements (web components) on the page
+    pub custom_elements: u64,
+    /// Number of SVG and MathML elements on the page, by namespace
+    pub foreign_elements: BTreeMap<Namespace, u64>,
+    pub semantics: Option<Semantics>,
+    pub forms: Option<Forms>,
+    pub tables: Option<Tables>,
+    /// The outline formed by the headings, unless only a sample of the elements was analyzed
+    pub headings: Option<Headings>,
+    pub media: Option<MediaAlternatives>,
+    pub aria: Option<Aria>,
+    /// Share of semantic elements among semantic and generic ones
+    pub semantic_score: Option<f64>,
+    /// Where the page's DOM snapshot was saved to
+    pub snapshot: Option<PathBuf>,
+    pub security: Option<Security>,
+    /// Measurements made by [scripts](crate::script) and [plugins](crate::plugin),
+    /// as `script.metric`
+    pub script_metrics: BTreeMap<String, f64>,
+    /// Where the time crawling the site went
+    pub timing: Timing,
+    /// What the page and everything it loaded transferred, in bytes, as far as it tells
+    pub transferred_bytes: Option<u64>,
+    /// What the [eval script](crate::config::Config::eval_script) returned
+    pub eval: Option<serde_json::Value>,
+    /// The [run](Output::run) the site was crawled in
+    pub run: Option<Ulid>,
+    pub error: Option<String>,
+}
+impl SiteRecord {
+    #[must_use]
+    pub fn new(url: String) -> Self {
+        let domain = Url::parse(&url)
+            .ok()
+            .and_then(|url| PublicSuffixList::get().site_domain(&url));
+        let crawled_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
         Self {
-            inner: Arc::new(RwLock::new([0; Tag::COUNT])),
-            dirty: Default::default(),
+            url,
+            crawled_at,
+            domain,
+            ..Default::default()
         }
     }
+
+    /// Records the page's custom elements, here and in the aggregate statistics.
+    pub async fn set_custom_elements(&mut self, output: &Output, counts: HashMap<String, u64>) {
+        self.custom_elements = counts.values().sum();
+        output.custom_elements.add_site(counts).await;
+    }
+
+    /// Records the page's SVG and MathML elements, here and in the aggregate statistics.
+    pub async fn set_foreign_elements(&mut self, output: &Output, counts: ForeignCounts) {
+        self.foreign_elements = foreign::totals(&counts);
+        output.foreign.add_all(counts).await;
+    }
+
+    /// Records the page's forms, here and in the aggregate statistics.
+    pub async fn set_forms(&mut self, output: &Output, forms: Forms) {
+        output.forms.add(&forms).await;
+        self.forms = Some(forms);
+    }
+
+    /// Records the page's tables, here and in the aggregate statistics.
+    pub async fn set_tables(&mut self, output: &Output, tables: Tables) {
+        output.tables.add(&tables).await;
+        self.tables = Some(tables);
+    }
+
+    /// Records the page's semantic element usage, here and in the aggregate statistics.
+    pub async fn set_semantics(&mut self, output: &Output, semantics: Semantics) {
+        output.semantics.add(&semantics).await;
+        self.semantic_score = semantics.score();
+        self.semantics = Some(semantics);
+    }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct Output {
+    /// The tags counted in [`Freq`] and friends
+    pub vocabulary: Vocabulary,
     pub freq: Freq,
+    /// Tag counts per registrable domain
+    pub domains: Domains,
+    pub geometry: Geometry,
+    /// The most common tokens in `class` attributes
+    pub classes: TopK,
+    /// How often each tag directly contains each other tag
+    pub cooccurrence: Cooccurrence,
+    pub obsolete: Obsolete,
+    pub custom_elements: CustomElements,
+    /// The most common tag names missing from the vocabulary, other than custom, SVG, MathML
+    /// and obsolete elements
+    pub unknown_tags: TopK,
+    /// SVG and MathML elements, which are not part of the regular tag frequencies
+    pub foreign: ForeignElements,
+    pub semantics: SemanticUsage,
+    pub forms: FormUsage,
+    pub tables: TableUsage,
+    pub outcomes: Outcomes,
+    /// Notable things about recently crawled sites
+    pub findings: Findings,
+    /// The analyzers each crawler runs on every element, from [`Config::analyzers`]
+    pub analyzers: Pipeline,
+    /// Measurements made by [scripts](crate::script) and [plugins](crate::plugin)
+    pub script_metrics: ScriptMetrics,
+    /// How long sites took, and where the time went
+    pub timing: SiteTimes,
+    /// What the sites transferred over the network
+    pub bandwidth: Bandwidth,
+    /// The tag counts [`freq`](Self::freq) started out from, indexed by
+    /// [`Tag::index`](crate::vocabulary::Tag::index), if it was
+    /// [warm-started](Self::with_baseline)
+    pub baseline: Option<Arc<[u64]>>,
+    /// Tells this run's records, logs and statistics apart from other runs'
+    pub run: Option<Ulid>,
+    pub records: mpsc::UnboundedSender<SiteRecord>,
+}
+impl Output {
+    #[must_use]
+    pub fn new(
+        records: mpsc::UnboundedSender<SiteRecord>,
+        config: &Config,
+        vocabulary: Vocabulary,
+    ) -> Self {
+        let geometry_tags = config.geometry_tags.iter().filter_map(|name| {
+            let tag = vocabulary.get(name);
+            if tag.is_none() {
+                warn!(name, "Geometry requested for a tag not in the vocabulary");
+            }
+            tag
+        });
+
+        Self {
+            freq: Freq::new(vocabulary.len()),
+            domains: Domains::new(vocabulary.clone(), config.top_domains),
+            geometry: Geometry::new(geometry_tags),
+            classes: TopK::new(config.top_classes),
+            cooccurrence: Cooccurrence::new(vocabulary.clone()),
+            obsolete: Obsolete::default(),
+            custom_elements: CustomElements::new(config.top_custom_elements),
+            unknown_tags: TopK::new(config.top_unknown_tags),
+            foreign: ForeignElements::default(),
+            semantics: SemanticUsage::default(),
+            forms: FormUsage::default(),
+            tables: TableUsage::default(),
+            outcomes: Outcomes::default(),
+            findings: Findings::default(),
+            analyzers: Pipeline::new(config.analyzers.iter().copied()),
+            script_metrics: ScriptMetrics::default(),
+            timing: SiteTimes::default(),
+            bandwidth: Bandwidth::default(),
+            baseline: None,
+            run: None,
+            vocabulary,
+            records,
+        }
+    }
+    /// Loads the vocabulary, scripts and plugins the config asks for, and starts counting from zero.
+    pub async fn load(records: mpsc::UnboundedSender<SiteRecord>, config: &Config) -> Result<Self> {
+        let vocabulary = match &config.vocabulary {
+            Some(path) => Vocabulary::load(path).await?,
+            None => Vocabulary::builtin(),
+        };
+        let scripts = Script::load_all(&config.scripts).await?;
+        let plugins = Plugin::load_all(&config.plugins).await?;
+        Ok(Self::new(records, config, vocabulary)
+            .with_scripts(scripts)
+            .with_plugins(plugins))
+    }
+
+    /// Runs an analyzer of your own on every element, on top of the [configured ones](Config::analyzers).
+    #[must_use]
+    pub fn with_analyzer(
+        mut self,
+        make: impl Fn(&Output) -> Result<Box<dyn Analyzer>> + Send + Sync + 'static,
+    ) -> Self {
+        self.analyzers = self.analyzers.with(make);
+        self
+    }
+
+    /// Marks every record, and the summary, as part of the run.
+    #[must_use]
+    pub fn with_run(mut self, run: Ulid) -> Self {
+        self.run = Some(run);
+        self
+    }
+
+    /// Starts counting the tags from a previous run's counts, e.g. to extend that run with more
+    /// sites, keeping them around to tell what this run added.
+    #[must_use]
+    pub fn with_baseline(mut self, census: &Census) -> Self {
+        let mut counts = vec![0; self.vocabulary.len()];
+        let mut unknown = 0;
+        for (name, &count) in &census.freq {
+            match self.vocabulary.get(name) {
+                Some(tag) => counts[tag.index()] = count,
+                None => unknown += 1,
+            }
+        }
+        if unknown > 0 {
+            warn!(
+                tags = unknown,
+                "The baseline has tags missing from the vocabulary - leaving them out"
+            );
+        }
+        self.freq.add(&counts);
+        self.baseline = Some(counts.into());
+        self
+    }
+
+    /// Runs each of the plugins on every page, on top of the other analyzers.
+    #[must_use]
+    pub fn with_plugins(self, plugins: Vec<Plugin>) -> Self {
+        plugins.into_iter().fold(self, |output, plugin| {
+            output.with_analyzer(move |output| Ok(plugin.analyzer(output)))
+        })
+    }
+
+    /// Runs each of the scripts on every page, on top of the other analyzers.
+    #[must_use]
+    pub fn with_scripts(self, scripts: Vec<Script>) -> Self {
+        scripts.into_iter().fold(self, |output, script| {
+            output.with_analyzer(move |output| script.analyzer(output))
+        })
+    }
+
+    pub fn record(&self, mut record: SiteRecord) {
+        record.run = self.run;
+        self.outcomes.count(&record);
+        self.findings.add(&record);
+        self.timing.add(&record.timing);
+        if let Some(bytes) = record.transferred_bytes {
+            self.bandwidth.add(bytes);
+        }
+        if self.records.send(record).is_err() {
+            warn!("Results writer is gone - dropping site record");
+        }
+    }
+}
+
+/// A DOM element, as seen by whichever engine produced it.
+pub trait Node {
+    fn tag_name(&self) -> impl Future<Output = Result<String>> + Send;
+    /// The element's bounding rectangle as `(x, y, width, height)`,
+    /// or `None` if the engine has no layout information.
+    fn rectangle(&self) -> impl Future<Output = Result<Option<(f64, f64, f64, f64)>>> + Send;
+}
+impl Node for Element {
+    async fn tag_name(&self) -> Result<String> {
+        Ok(Element::tag_name(self).await?)
+    }
+    async fn rectangle(&self) -> Result<Option<(f64, f64, f64, f64)>> {
+        Ok(Some(Element::rectangle(self).await?))
+    }
 }
 
-#[derive(Clone, Debug, Default)]
 pub struct State {
     pub output: Output,
     pub window_width: u64,
     pub window_height: u64,
+    /// What's done with each element, see [`Output::analyzers`]
+    analyzers: Vec<Box<dyn Analyzer>>,
+    /// Whether the analyzers have been handed elements of a page not finished yet
+    mid_page: bool,
 }
 impl State {
     pub async fn new(output: Output, c: &Client) -> Result<Self> {
         let (window_width, window_height) = c.get_window_size().await?;
         Ok(Self {
+            analyzers: output.analyzers.build(&output)?,
             output,
             window_width,
             window_height,
+            mid_page: false,
+        })
+    }
+
+    /// State for engines without a browser window, and thus without layout information.
+    pub fn offline(output: Output) -> Result<Self> {
+        Ok(Self {
+            analyzers: output.analyzers.build(&output)?,
+            output,
+            window_width: 0,
+            window_height: 0,
+            mid_page: false,
         })
     }
 
+    /// Hands an element of the current page to every analyzer.
     #[allow(clippy::cast_precision_loss)]
-    pub async fn accept_node(self, elem: Element) -> Result<Self> {
-        let Ok(tag) = elem.tag_name().await else {
-            warn!(v = ?elem.element_id(), "Unable to get name for element - perhaps it has already been removed from the DOM?");
-            return Ok(self);
+    pub async fn accept_node<N: Node>(&mut self, elem: N) -> Result<()> {
+        let Ok(name) = elem.tag_name().await else {
+            warn!("Unable to get name for element - perhaps it has already been removed from the DOM?");
+            return Ok(());
         };
+        let tag = self.output.vocabulary.get(&name);
+        self.mid_page = true;
 
-        let Ok(tag) = Tag::from_str(&tag) else {
-            debug!(
-                tag,
-                "Found unrecognized tag — might be a web component/XML/SVG/..."
-            );
-            return Ok(self);
-        };
+        let mut rectangle = None;
+        if self.analyzers.iter().any(|a| a.wants_rectangle(&name, tag)) {
+            if let Some((x, y, w, h)) = elem.rectangle().await? {
+                let (vw, vh) = (self.window_width as f64, self.window_height as f64);
+                rectangle = Some((x / vw, y / vh, w / vw, h / vh));
+            }
+        }
 
-        match tag {
-            Tag::Div => {
-                let (x, y, w, h) = elem.rectangle().await?;
-                let w = w / self.window_width as f64;
-                let h = h / self.window_height as f64;
+        let page_ctx = PageContext {
+            name: &name,
+            tag,
+            rectangle,
+        };
+        for analyzer in &mut self.analyzers {
+            analyzer.accept(&page_ctx).await?;
+        }
+        Ok(())
+    }
 
-                trace!("Found div element ({x:.2}, {y:.2}) {w:.2} x {h:.2}");
+    /// Lets every analyzer wrap up the page just analyzed, and start over for the next one.
+    ///
+    /// One failing to only leaves out what it would have added: the others have already
+    /// counted the page towards the totals, so failing the page would leave it without a record.
+    pub async fn finish_page(&mut self, record: &mut SiteRecord) {
+        for analyzer in &mut self.analyzers {
+            if let Err(e) = analyzer.finalize(record).await {
+                warn!(%e, "Analyzer failed to finish the page - leaving out what it found");
+                analyzer.reset();
             }
-            _ => {}
         }
+        self.mid_page = false;
+    }
 
-        self.output.freq.bump(tag).await;
-
-        Ok(self)
+    /// Throws away what the analyzers made of a page given up on halfway through, so that it
+    /// isn't counted along with the next page.
+    ///
+    /// The analyzers themselves are kept, since scripts and plugins are costly to start again.
+    pub fn discard_page(&mut self) {
+        if self.mid_page {
+            for analyzer in &mut self.analyzers {
+                analyzer.reset();
+            }
+            self.mid_page = false;
+        }
     }
 }
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use eyre::Result;
+use fantoccini::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+/// Classifies each table as holding data or merely laying out the page.
+///
+/// Tables that are marked up as presentational, nest or are nested in other tables,
+/// or lack any header cells or caption are taken to be layout tables.
+const TABLES_SCRIPT: &str = r#"
+let data = 0, layout = 0, maxNesting = 0;
+for (const t of document.querySelectorAll("table")) {
+    let nesting = 0;
+    for (let p = t.parentElement?.closest("table"); p; p = p.parentElement?.closest("table")) nesting++;
+    maxNesting = Math.max(maxNesting, nesting);
+
+    const role = t.getAttribute("role");
+    const isLayout = role === "presentation" || role === "none"
+        || nesting > 0
+        || t.querySelector("table") !== null
+        || t.querySelector("th, caption, thead") === null;
+    if (isLayout) layout++; else data++;
+}
+return { data, layout, max_nesting: maxNesting };
+"#;
+
+/// How a page uses `<table>`s.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Tables {
+    /// Tables that look like they hold tabular data
+    pub data: u64,
+    /// Tables that look like they are used to lay out the page
+    pub layout: u64,
+    /// How deeply tables are nested inside each other, 0 if not at all
+    pub max_nesting: u64,
+}
+
+/// Classifies the tables on the currently loaded page.
+pub async fn tables(client: &Client) -> Result<Tables> {
+    let v = client.execute(TABLES_SCRIPT, vec![]).await?;
+    Ok(serde_json::from_value(v)?)
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub data: u64,
+    pub layout: u64,
+    /// Number of sites with at least one layout table
+    pub sites_with_layout_tables: u64,
+}
+
+/// Data and layout tables, aggregated across all sites.
+#[derive(Clone, Debug, Default)]
+pub struct TableUsage {
+    inner: Arc<Mutex<Summary>>,
+}
+impl TableUsage {
+    pub async fn add(&self, tables: &Tables) {
+        let mut inner = self.inner.lock().await;
+        inner.data += tables.data;
+        inner.layout += tables.layout;
+        if tables.layout > 0 {
+            inner.sites_with_layout_tables += 1;
+        }
+    }
+
+    pub async fn summary(&self) -> Summary {
+        self.inner.lock().await.clone()
+    }
+}
@@ -0,0 +1,53 @@
+//! Ships `tracing` spans to an OpenTelemetry collector, e.g. to follow slow sites in Jaeger or Tempo.
+
+use eyre::Result;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+use url::Url;
+
+/// How the spans are told apart from other services'
+const SERVICE_NAME: &str = "quotelementa";
+/// Where OTLP/HTTP collectors take traces, relative to their base URL
+const TRACES_PATH: &str = "v1/traces";
+
+/// Sends the spans left over when dropped, and stops exporting.
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to export the last spans: {e}");
+        }
+    }
+}
+
+/// A layer exporting every span to the OTLP/HTTP collector at `endpoint`,
+/// e.g. `http://localhost:4318`, for as long as the returned [`Telemetry`] is kept around.
+pub fn otlp_layer<S>(endpoint: &Url) -> Result<(impl Layer<S>, Telemetry)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let mut endpoint = endpoint.clone();
+    if !endpoint.path().ends_with(TRACES_PATH) {
+        // the base URL is given, as with `OTEL_EXPORTER_OTLP_ENDPOINT`
+        if !endpoint.path().ends_with('/') {
+            endpoint.set_path(&format!("{}/", endpoint.path()));
+        }
+        endpoint = endpoint.join(TRACES_PATH)?;
+    }
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.as_str())
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME));
+    Ok((layer, Telemetry { provider }))
+}
@@ -0,0 +1,199 @@
+//! Where the time crawling each site goes, and how that adds up across sites.
+
+use std::{
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// How much wider each bucket of a [`Histogram`] is than the one before
+const GROWTH: f64 = 1.05;
+
+/// How long a site took, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timing {
+    /// From taking on the site to being done with it, whether or not that went well
+    pub total_ms: u64,
+    /// Loading the page, retries and dialogs included, if the engine navigates and got that far
+    pub navigation_ms: Option<u64>,
+    /// Handing the page's elements to the analyzers, if it got that far
+    pub walk_ms: Option<u64>,
+}
+impl Timing {
+    /// The duration in whole milliseconds, as recorded.
+    #[must_use]
+    pub fn millis(duration: Duration) -> u64 {
+        u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+    }
+}
+
+/// Durations bucketed on a log scale, each bucket about 5% wider than the one before, so that
+/// the percentiles of millions of sites take a few hundred counters.
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+impl Histogram {
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn bucket(ms: u64) -> usize {
+        if ms == 0 {
+            0
+        } else {
+            ((ms as f64).ln() / GROWTH.ln()) as usize + 1
+        }
+    }
+
+    /// The largest duration that falls in the bucket.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_sign_loss
+    )]
+    fn upper_bound(bucket: usize) -> u64 {
+        if bucket == 0 {
+            0
+        } else {
+            GROWTH.powi(bucket as i32).floor() as u64
+        }
+    }
+
+    fn add(&mut self, ms: u64) {
+        let bucket = Self::bucket(ms);
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.max = self.max.max(ms);
+    }
+
+    /// The duration `p` of them took at most, to within a bucket.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn percentile(&self, p: f64) -> u64 {
+        let rank = ((self.count as f64 * p).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Self::upper_bound(bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    fn summary(&self) -> Percentiles {
+        Percentiles {
+            sites: self.count,
+            p50: self.percentile(0.5),
+            p90: self.percentile(0.9),
+            p99: self.percentile(0.99),
+            max: self.max,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histograms {
+    total: Histogram,
+    navigation: Histogram,
+    walk: Histogram,
+}
+
+/// The timings of all sites so far, shared between crawlers.
+#[derive(Clone, Debug, Default)]
+pub struct SiteTimes {
+    inner: Arc<Mutex<Histograms>>,
+}
+impl SiteTimes {
+    pub fn add(&self, timing: &Timing) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.total.add(timing.total_ms);
+        if let Some(ms) = timing.navigation_ms {
+            inner.navigation.add(ms);
+        }
+        if let Some(ms) = timing.walk_ms {
+            inner.walk.add(ms);
+        }
+    }
+
+    #[must_use]
+    pub fn summary(&self) -> TimingSummary {
+        let inner = self.inner.lock().unwrap();
+        TimingSummary {
+            total: inner.total.summary(),
+            navigation: inner.navigation.summary(),
+            walk: inner.walk.summary(),
+        }
+    }
+}
+
+/// How long sites took, in milliseconds, to within about 5%.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Percentiles {
+    /// How many sites got as far as this
+    pub sites: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+/// Where the time crawling the sites went, as written to the summary.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct TimingSummary {
+    pub total: Percentiles,
+    pub navigation: Percentiles,
+    pub walk: Percentiles,
+}
+impl TimingSummary {
+    /// The timings in a summary written by [`write_summary`](crate::sink::write_summary),
+    /// if it has any.
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        #[derive(Deserialize)]
+        struct Summary {
+            timing: Option<TimingSummary>,
+        }
+
+        let s = tokio::fs::read(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read summary {}", path.display()))?;
+        let summary: Summary = serde_json::from_slice(&s)
+            .wrap_err_with(|| format!("Failed to parse summary {}", path.display()))?;
+        Ok(summary.timing)
+    }
+}
+impl fmt::Display for TimingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>12} {:>9} {:>9} {:>9} {:>9}",
+            "ms", "sites", "p50", "p90", "p99", "max"
+        )?;
+        for (stage, p) in [
+            ("total", &self.total),
+            ("navigation", &self.navigation),
+            ("walk", &self.walk),
+        ] {
+            writeln!(
+                f,
+                "{stage:>12} {:>12} {:>9} {:>9} {:>9} {:>9}",
+                p.sites, p.p50, p.p90, p.p99, p.max
+            )?;
+        }
+        Ok(())
+    }
+}
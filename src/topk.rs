@@ -0,0 +1,120 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+/// A bounded counter that keeps track of the (approximately) most frequent items,
+/// using the Space-Saving algorithm.
+///
+/// Once full, a new item evicts the least frequent one and inherits its count,
+/// which is remembered as that item's maximum overestimation.
+#[derive(Clone, Debug)]
+pub struct SpaceSaving {
+    capacity: usize,
+    counts: HashMap<String, Counter>,
+    /// Items ordered by count, so that the minimum can be found quickly
+    by_count: BTreeSet<(u64, String)>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Counter {
+    count: u64,
+    error: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TopItem {
+    pub name: String,
+    pub count: u64,
+    /// By how much `count` may overestimate the true count
+    pub error: u64,
+}
+
+impl SpaceSaving {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counts: HashMap::with_capacity(capacity),
+            by_count: BTreeSet::new(),
+        }
+    }
+
+    pub fn add(&mut self, item: &str, n: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(counter) = self.counts.get_mut(item) {
+            self.by_count.remove(&(counter.count, item.to_owned()));
+            counter.count += n;
+            self.by_count.insert((counter.count, item.to_owned()));
+            return;
+        }
+
+        let mut counter = Counter { count: n, error: 0 };
+        if self.counts.len() >= self.capacity {
+            let (min, evicted) = self.by_count.pop_first().expect("sketch should be full");
+            self.counts.remove(&evicted);
+            counter = Counter {
+                count: min + n,
+                error: min,
+            };
+        }
+        self.counts.insert(item.to_owned(), counter);
+        self.by_count.insert((counter.count, item.to_owned()));
+    }
+
+    /// The tracked items, most frequent first.
+    #[must_use]
+    pub fn top(&self) -> Vec<TopItem> {
+        self.by_count
+            .iter()
+            .rev()
+            .map(|(_, item)| {
+                let Counter { count, error } = self.counts[item];
+                TopItem {
+                    name: item.clone(),
+                    count,
+                    error,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A [`SpaceSaving`] sketch shared between crawlers.
+#[derive(Clone, Debug)]
+pub struct TopK {
+    inner: Arc<Mutex<SpaceSaving>>,
+}
+
+impl TopK {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(SpaceSaving::new(capacity))),
+        }
+    }
+
+    pub async fn add(&self, item: &str, n: u64) {
+        self.inner.lock().await.add(item, n);
+    }
+
+    /// Adds the items in order of name, so that the sketch comes out the same whatever order
+    /// they were collected in, e.g. from a `HashMap`.
+    pub async fn add_all(&self, items: impl IntoIterator<Item = (String, u64)>) {
+        let mut items: Vec<_> = items.into_iter().collect();
+        items.sort_unstable();
+        let mut inner = self.inner.lock().await;
+        for (item, n) in items {
+            inner.add(&item, n);
+        }
+    }
+
+    pub async fn top(&self) -> Vec<TopItem> {
+        self.inner.lock().await.top()
+    }
+}
@@ -1,6 +1,17 @@
 mod bar_chart;
+mod keymap;
+mod rate;
+mod theme;
 
-use std::{collections::BTreeMap, io::Stdout, time::Duration, vec};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    io::Stdout,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    vec,
+};
 
 use crossterm::{
     event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers},
@@ -10,22 +21,32 @@ use eyre::Result;
 use futures_util::StreamExt;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Gauge, Paragraph, Wrap},
+    widgets::{Block, Borders, Gauge, Paragraph, Sparkline, Tabs, Wrap},
     Frame, Terminal,
 };
+use strum::{Display, EnumIter, FromRepr, IntoEnumIterator};
 use tokio::sync::{mpsc, oneshot, watch};
-use tracing::info;
+use tracing::{error, info, warn, Level};
 
 use crate::{
-    crawler::{CrawlerReport, CrawlerState},
+    crawler::{Control, CrawlerState, Dashboard, QueueDepth, Utilization},
+    findings::Finding,
+    logs::LogLine,
+    report::Census,
+    resources::{DriverPids, ProcessUsage, Sampler},
+    signals::{Signals, StopRequests},
     state::Output,
-    util::{Port, Tag},
+    util::Port,
 };
 
-use self::bar_chart::BarChart;
+use self::{bar_chart::BarChart, rate::Rate, theme::Palette};
+pub use self::{
+    keymap::{Action, KeyBinding, Keymap},
+    theme::Theme,
+};
 
 type Backend = CrosstermBackend<Stdout>;
 
@@ -35,10 +56,30 @@ pub struct Tui {
 }
 impl Tui {
     pub fn new(app: App) -> Result<Self> {
+        // a panic on the main thread takes everything down, and would otherwise leave the terminal
+        // in raw mode on the alternate screen, with the panic message nowhere to be seen. Panics
+        // elsewhere only end a task, while the UI keeps running, so they're logged instead of
+        // being printed all over it. The UI's own task restores the terminal as it unwinds.
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !UI_SHOWN.load(Ordering::Relaxed) {
+                hook(info);
+            } else if std::thread::current().name() == Some("main") {
+                let _ = restore_terminal();
+                hook(info);
+            } else {
+                error!(%info, "A task panicked");
+            }
+        }));
+
         let backend = {
             terminal::enable_raw_mode()?;
+            UI_SHOWN.store(true, Ordering::Relaxed);
             let mut stdout = std::io::stdout();
             execute!(stdout, terminal::EnterAlternateScreen)?;
+            if let Some(run) = app.output.run {
+                execute!(stdout, terminal::SetTitle(format!("quotelementa {run}")))?;
+            }
             CrosstermBackend::new(stdout)
         };
         let terminal = Terminal::new(backend)?;
@@ -46,14 +87,14 @@ impl Tui {
         Ok(Self { terminal, app })
     }
     pub fn end(mut self) -> Result<()> {
-        terminal::disable_raw_mode()?;
-        execute!(self.terminal.backend_mut(), terminal::LeaveAlternateScreen)?;
+        restore_terminal()?;
         self.terminal.show_cursor()?;
 
         Ok(())
     }
     pub async fn run(mut self, mut close_rx: oneshot::Receiver<()>) -> Result<()> {
         let mut events = EventStream::new();
+        let mut signals = self.app.signals()?;
         let mut ui_update_ticker = tokio::time::interval(Duration::from_millis(100));
 
         loop {
@@ -66,13 +107,16 @@ impl Tui {
                         // we're already shutting down anyway
                         break;
                     }
-                    _ => unreachable!(),
+                    AppState::Done => unreachable!(),
+                },
+                Some(event) = events.next() => if self.app.on_event(&event?) {
+                    break;
                 },
-                Some(event) = events.next() => if self.app.on_event(event?)? {
+                signal = signals.recv() => if self.app.on_signal(signal) {
                     break;
                 },
                 _ = ui_update_ticker.tick() => {
-                    self.app.update().await;
+                    self.app.update();
                     let ui = self.app.ui();
                     self.terminal.draw(ui)?;
                 }
@@ -83,9 +127,118 @@ impl Tui {
     }
 }
 
+impl Drop for Tui {
+    fn drop(&mut self) {
+        // harmless if the terminal was already restored, and essential if it wasn't
+        let _ = restore_terminal();
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// Whether the UI is on screen, and panics have to go around it
+static UI_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Leaves raw mode and the alternate screen.
+fn restore_terminal() -> Result<()> {
+    UI_SHOWN.store(false, Ordering::Relaxed);
+    terminal::disable_raw_mode()?;
+    execute!(std::io::stdout(), terminal::LeaveAlternateScreen)?;
+    Ok(())
+}
+
+/// How soon Ctrl-C has to be pressed again for a running crawl to actually shut down.
+const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(2);
+
+/// How often the memory and CPU use of the WebDriver processes is looked up.
+const RESOURCE_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long each set of findings stays on screen.
+const FINDINGS_ROTATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many log lines are kept around for the log pane.
+const MAX_LOG_LINES: usize = 1000;
+
 const SPINNER_STATES: [&str; 8] = ["⣼", "⣹", "⢻", "⠿", "⡟", "⣏", "⣧", "⣶"];
 type SpinnerState = u8;
 
+/// How the bars of the histogram are ordered.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Display)]
+enum SortOrder {
+    #[default]
+    #[strum(serialize = "most common")]
+    Descending,
+    #[strum(serialize = "least common")]
+    Ascending,
+    #[strum(serialize = "by name")]
+    Alphabetical,
+}
+impl SortOrder {
+    fn next(self) -> Self {
+        match self {
+            Self::Descending => Self::Ascending,
+            Self::Ascending => Self::Alphabetical,
+            Self::Alphabetical => Self::Descending,
+        }
+    }
+    fn sort(self, bars: &mut [(String, u64)]) {
+        match self {
+            Self::Descending => bars.sort_by(|(_, v1), (_, v2)| v2.cmp(v1)),
+            Self::Ascending => bars.sort_by_key(|(_, v)| *v),
+            Self::Alphabetical => bars.sort_by(|(k1, _), (k2, _)| k1.cmp(k2)),
+        }
+    }
+}
+
+/// How the overview is arranged, depending on how much room the terminal has.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OverviewLayout {
+    /// Crawlers and status on the left, histogram and findings on the right
+    Wide,
+    /// All panels on top of each other
+    Stacked,
+    /// Only the crawlers and status, as there's no room for a useful histogram
+    Compact,
+}
+impl OverviewLayout {
+    /// Narrower than this, the histogram is squeezed next to the crawler list.
+    const MIN_WIDE_WIDTH: u16 = 100;
+    /// Smaller than this, the histogram doesn't fit under everything else.
+    const MIN_STACKED_WIDTH: u16 = 40;
+    const MIN_STACKED_HEIGHT: u16 = 30;
+
+    fn for_area(area: Rect) -> Self {
+        if area.width >= Self::MIN_WIDE_WIDTH {
+            Self::Wide
+        } else if area.width >= Self::MIN_STACKED_WIDTH && area.height >= Self::MIN_STACKED_HEIGHT {
+            Self::Stacked
+        } else {
+            Self::Compact
+        }
+    }
+}
+
+/// The views of the UI, switched between with Tab or their number keys.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Display, EnumIter, FromRepr)]
+enum Tab {
+    #[default]
+    Overview,
+    Crawlers,
+    Histogram,
+    Errors,
+    Logs,
+}
+impl Tab {
+    fn next(self) -> Self {
+        Self::from_repr(self as usize + 1).unwrap_or(Self::Overview)
+    }
+    fn previous(self) -> Self {
+        match self as usize {
+            0 => Self::Logs,
+            i => Self::from_repr(i - 1).unwrap_or(Self::Overview),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default, PartialEq, Eq)]
 enum AppState {
     #[default]
@@ -95,89 +248,458 @@ enum AppState {
 }
 
 pub struct App {
+    tab: Tab,
     freq: Vec<(String, u64)>,
+    categories: Vec<(String, u64)>,
+    /// Whether the histogram shows tag categories rather than individual tags
+    show_categories: bool,
+    sort_order: SortOrder,
+    /// Only show the tags of this category, as an index into the vocabulary's groups
+    category_filter: Option<usize>,
+    /// Only show the tags whose name contains this
+    name_filter: String,
+    /// The counts the histogram started out from, if it was warm-started from a previous run
+    baseline: Option<Census>,
+    /// Whether keys are currently typed into the name filter
+    editing_filter: bool,
     output: Output,
 
     state: AppState,
     shutdown_tx: watch::Sender<()>,
+    /// When Ctrl-C was last pressed without that shutting anything down
+    quit_requested: Option<Instant>,
 
     crawled_sites: usize,
+    /// Crawlers started again after failing to start, and ones given up on
+    respawns: usize,
+    given_up: usize,
     total_sites: usize,
+    rate: Rate,
+    utilization: Utilization,
+    queue: Option<QueueDepth>,
 
     crawlers: BTreeMap<Port, (SpinnerState, CrawlerState)>,
-    report_rx: mpsc::Receiver<CrawlerReport>,
+    /// Index of the first crawler shown in the crawler list
+    crawler_scroll: usize,
+    /// How many crawlers fit in the crawler list, as of the last draw
+    crawler_rows: usize,
+    /// Index of the crawler selected in the crawler tab
+    crawler_selected: usize,
+    dashboard: Dashboard,
+
+    /// The WebDriver processes behind the crawlers, if the engine has any
+    driver_pids: Option<DriverPids>,
+    /// Requests to shut down that don't come from the user or signals, e.g. from the status API
+    stop_requests: Option<StopRequests>,
+    sampler: Sampler,
+    sampled_at: Option<Instant>,
+    /// Memory and CPU use of each crawler's WebDriver and browser, as of the last sample
+    usage: BTreeMap<Port, ProcessUsage>,
+
+    /// Notable things about recently crawled sites, oldest first
+    findings: Vec<Finding>,
+    /// Index of the first finding shown, wrapping around
+    findings_offset: usize,
+    /// How many findings fit in their panel, as of the last draw
+    findings_rows: usize,
+    findings_rotated_at: Instant,
+
+    /// The most recent log lines, oldest first
+    logs: VecDeque<LogLine>,
+    show_logs: bool,
+    log_rx: mpsc::UnboundedReceiver<LogLine>,
+
+    /// Where to ask for more or fewer crawlers, if the engine has any
+    control_tx: Option<mpsc::UnboundedSender<Control>>,
+    palette: Palette,
+    keymap: Keymap,
+    /// Where exports of the current state are written
+    export_dir: PathBuf,
 }
 impl App {
+    #[must_use]
     pub fn new(
         output: Output,
-        report_rx: mpsc::Receiver<CrawlerReport>,
+        dashboard: Dashboard,
+        log_rx: mpsc::UnboundedReceiver<LogLine>,
         total_sites: usize,
         shutdown_tx: watch::Sender<()>,
     ) -> Self {
         Self {
+            tab: Tab::default(),
             freq: vec![],
+            categories: vec![],
+            show_categories: false,
+            sort_order: SortOrder::default(),
+            category_filter: None,
+            name_filter: String::new(),
+            editing_filter: false,
+            baseline: output
+                .baseline
+                .as_deref()
+                .map(|counts| Census::count(&output.vocabulary, counts)),
             output,
             state: AppState::default(),
             shutdown_tx,
-            crawled_sites: 0,
+            quit_requested: None,
+            // a resumed crawl doesn't start from zero
+            crawled_sites: dashboard.get().crawled(),
+            respawns: 0,
+            given_up: 0,
             total_sites,
+            rate: Rate::new(),
+            utilization: Utilization::default(),
+            queue: None,
             crawlers: BTreeMap::new(),
-            report_rx,
+            crawler_scroll: 0,
+            crawler_rows: 0,
+            crawler_selected: 0,
+            dashboard,
+            driver_pids: None,
+            stop_requests: None,
+            sampler: Sampler::new(),
+            sampled_at: None,
+            usage: BTreeMap::new(),
+            findings: vec![],
+            findings_offset: 0,
+            findings_rows: 0,
+            findings_rotated_at: Instant::now(),
+            logs: VecDeque::new(),
+            show_logs: false,
+            log_rx,
+            control_tx: None,
+            palette: Theme::default().palette(),
+            keymap: Keymap::default(),
+            export_dir: PathBuf::from("."),
         }
     }
 
-    fn on_event(&mut self, event: Event) -> Result<bool> {
-        match event {
-            Event::Key(key) => match key {
-                KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                } => {
-                    info!("Received Ctrl-C event - issuing shut down");
-                    self.state = AppState::ShuttingDown;
-                    self.shutdown_tx.send(()).unwrap();
+    /// Lets the user add and retire crawlers.
+    #[must_use]
+    pub fn with_control(mut self, control_tx: mpsc::UnboundedSender<Control>) -> Self {
+        self.control_tx = Some(control_tx);
+        self
+    }
+
+    /// Shows how much memory and CPU each crawler's WebDriver and browser use.
+    #[must_use]
+    pub fn with_driver_pids(mut self, driver_pids: DriverPids) -> Self {
+        self.driver_pids = Some(driver_pids);
+        self
+    }
+
+    /// Shuts down gracefully when asked to, just like on a signal.
+    #[must_use]
+    pub fn with_stop_requests(mut self, requests: StopRequests) -> Self {
+        self.stop_requests = Some(requests);
+        self
+    }
+    pub(crate) fn signals(&mut self) -> Result<Signals> {
+        Ok(Signals::new()?.with_requests(self.stop_requests.take()))
+    }
+
+    /// Draws the UI in the given theme's colors.
+    #[must_use]
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.palette = theme.palette();
+        self
+    }
+
+    /// Uses different keys for some actions.
+    #[must_use]
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Writes exports of the current state into the directory, e.g. the run's,
+    /// rather than into the working directory.
+    #[must_use]
+    pub fn with_export_dir(mut self, dir: PathBuf) -> Self {
+        self.export_dir = dir;
+        self
+    }
+
+    fn control(&self, control: Control) {
+        if let Some(tx) = &self.control_tx {
+            // the crawlers are only gone once everything's done anyway
+            let _ = tx.send(control);
+        }
+    }
+
+    /// Shuts down on a signal just like on the quit key, only without asking first,
+    /// and forcibly the second time, killing the WebDrivers and exiting right away.
+    /// Returns whether to leave the UI, which is once everything's done.
+    pub(crate) fn on_signal(&mut self, signal: &str) -> bool {
+        match self.state {
+            AppState::Done => return true,
+            AppState::ShuttingDown => {
+                info!(signal, "Received second signal - forcing quit");
+                self.kill_drivers();
+                if UI_SHOWN.load(Ordering::Relaxed) {
+                    let _ = restore_terminal();
                 }
-                KeyEvent {
-                    code: KeyCode::Enter,
-                    ..
-                } if self.state == AppState::Done => {
-                    return Ok(true);
+                std::process::exit(130);
+            }
+            AppState::Running => {}
+        }
+        info!(signal, "Received signal - issuing shut down");
+        self.shut_down();
+        false
+    }
+    /// Asks all workers to stop after their current site.
+    pub(crate) fn shut_down(&mut self) {
+        self.state = AppState::ShuttingDown;
+        self.shutdown_tx.send(()).unwrap();
+    }
+    /// Whether Ctrl-C was pressed recently enough that pressing it again shuts down.
+    fn is_quit_pending(&self) -> bool {
+        self.quit_requested
+            .is_some_and(|t| t.elapsed() < QUIT_CONFIRM_WINDOW)
+    }
+    /// The sites that would be abandoned midway by shutting down now.
+    fn in_flight_sites(&self) -> usize {
+        self.crawlers
+            .values()
+            .filter(|(_, state)| matches!(state, CrawlerState::InProgress(_)))
+            .count()
+    }
+    /// Kills every WebDriver still running, for when there's no time to shut down properly.
+    pub(crate) fn kill_drivers(&self) {
+        if let Some(pids) = &self.driver_pids {
+            pids.sweep();
+        }
+    }
+    pub(crate) fn is_shutting_down(&self) -> bool {
+        self.state == AppState::ShuttingDown
+    }
+    /// Whether the crawlers hold off on taking new sites, e.g. through the control socket.
+    pub(crate) fn is_paused(&self) -> bool {
+        self.dashboard.is_paused()
+    }
+    pub(crate) fn crawled_sites(&self) -> usize {
+        self.crawled_sites
+    }
+    pub(crate) fn total_sites(&self) -> usize {
+        self.total_sites
+    }
+    pub(crate) fn active_crawlers(&self) -> usize {
+        self.crawlers.len()
+    }
+
+    /// Moves the selection in the crawler tab, or scrolls the crawler list elsewhere.
+    fn move_crawlers(&mut self, up: bool, by: usize) {
+        let position = if self.tab == Tab::Crawlers {
+            &mut self.crawler_selected
+        } else {
+            &mut self.crawler_scroll
+        };
+        *position = if up {
+            position.saturating_sub(by)
+        } else {
+            position.saturating_add(by)
+        };
+    }
+
+    /// Writes the histogram and the state of every crawler to a timestamped JSON file
+    /// in the [export directory](Self::with_export_dir).
+    fn export(&self) -> Result<PathBuf> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = self
+            .export_dir
+            .join(format!("quotelementa-{timestamp}.json"));
+
+        let outcomes = &self.output.outcomes;
+        let crawlers: Vec<_> = self
+            .crawlers
+            .iter()
+            .map(|(port, (_, state))| {
+                let usage = self.usage.get(port);
+                serde_json::json!({
+                    "port": port,
+                    "state": state.to_string(),
+                    "memory": usage.map(|u| u.memory),
+                    "cpu": usage.map(|u| u.cpu),
+                })
+            })
+            .collect();
+        let snapshot = serde_json::json!({
+            "timestamp": timestamp,
+            "crawled": self.crawled_sites,
+            "total": self.total_sites,
+            "failed": outcomes.failed.load(Ordering::Relaxed),
+            "retried": outcomes.retried.load(Ordering::Relaxed),
+            "skipped": outcomes.skipped.load(Ordering::Relaxed),
+            "excluded": outcomes.excluded.load(Ordering::Relaxed),
+            "robots": outcomes.robots.load(Ordering::Relaxed),
+            "duplicates": outcomes.duplicates.load(Ordering::Relaxed),
+            "crawlers": crawlers,
+            "freq": self.freq,
+            "categories": self.categories,
+        });
+
+        std::fs::write(&path, serde_json::to_vec_pretty(&snapshot)?)?;
+        Ok(path)
+    }
+
+    /// Types into the histogram's name filter.
+    fn edit_filter(&mut self, key: &KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) if is_plain(key) => self.name_filter.push(c),
+            KeyCode::Backspace => {
+                self.name_filter.pop();
+            }
+            KeyCode::Esc => {
+                self.name_filter.clear();
+                self.editing_filter = false;
+            }
+            KeyCode::Enter => self.editing_filter = false,
+            _ => {}
+        }
+    }
+
+    fn on_event(&mut self, event: &Event) -> bool {
+        let Event::Key(key) = event else {
+            return false;
+        };
+        let action = self.keymap.action(key);
+        // typing into the filter mustn't trigger a quit bound to a plain character
+        if self.editing_filter && (action != Some(Action::Quit) || is_plain(key)) {
+            self.edit_filter(key);
+            return false;
+        }
+        let Some(action) = action else {
+            if let (KeyCode::Char(c @ '1'..='9'), true) = (key.code, is_plain(key)) {
+                if let Some(tab) = Tab::from_repr(c as usize - '1' as usize) {
+                    self.tab = tab;
+                }
+            }
+            return false;
+        };
+
+        match action {
+            Action::Quit => {
+                // an accidental keypress shouldn't end a run that's been going for hours
+                if self.state == AppState::Running && !self.is_quit_pending() {
+                    self.quit_requested = Some(Instant::now());
+                } else {
+                    info!("Received quit key - issuing shut down");
+                    self.shut_down();
                 }
-                _ => {}
+            }
+            Action::Exit if self.state == AppState::Done => return true,
+            Action::NextTab => self.tab = self.tab.next(),
+            Action::PreviousTab => self.tab = self.tab.previous(),
+            Action::ShowErrors => self.tab = Tab::Errors,
+            Action::ToggleLogs => self.show_logs = !self.show_logs,
+            Action::ToggleCategories => self.show_categories = !self.show_categories,
+            Action::CycleSort => self.sort_order = self.sort_order.next(),
+            Action::CycleCategory => {
+                let groups = self.output.vocabulary.groups().len();
+                self.category_filter = match self.category_filter {
+                    None if groups > 0 => Some(0),
+                    Some(i) if i + 1 < groups => Some(i + 1),
+                    _ => None,
+                };
+            }
+            Action::Filter => self.editing_filter = true,
+            Action::Up => self.move_crawlers(true, 1),
+            Action::Down => self.move_crawlers(false, 1),
+            Action::PageUp => self.move_crawlers(true, self.crawler_rows),
+            Action::PageDown => self.move_crawlers(false, self.crawler_rows),
+            Action::AddWorker => self.control(Control::AddWorker),
+            Action::RetireWorker => self.control(Control::RetireWorker),
+            Action::Pause => {
+                if self.dashboard.resume() {
+                    info!("Resumed from the terminal UI");
+                } else if self.dashboard.pause() {
+                    info!("Paused from the terminal UI");
+                }
+            }
+            Action::Skip if self.tab == Tab::Crawlers => {
+                let selected = self.crawlers.iter().nth(self.crawler_selected);
+                if let Some((&port, (_, CrawlerState::InProgress(_)))) = selected {
+                    self.control(Control::Skip(port));
+                }
+            }
+            Action::Export => match self.export() {
+                Ok(path) => info!(?path, "Exported the current state"),
+                Err(e) => warn!(%e, "Failed to export the current state"),
             },
-            _ => {}
+            Action::Exit | Action::Skip => {}
         }
-        Ok(false)
+        false
     }
 
-    async fn update(&mut self) {
+    pub(crate) fn update(&mut self) {
         if self.output.freq.is_dirty() {
             // kinda jank but... oh well
-            let freq = self.output.freq.get().await;
-            self.freq = freq
-                .iter()
-                .enumerate()
-                .filter_map(|(i, v)| Tag::from_repr(i).map(|tag| (tag.to_string(), *v)))
+            let freq = self.output.freq.get();
+            self.freq = self
+                .output
+                .vocabulary
+                .names()
+                .map(str::to_owned)
+                .zip(freq.iter().copied())
                 .collect();
             self.freq.sort_by(|(_, v1), (_, v2)| v2.cmp(v1));
+
+            self.categories = self
+                .output
+                .vocabulary
+                .group_totals(&freq)
+                .map(|(group, total)| (group.to_owned(), total))
+                .collect();
+            self.categories.sort_by(|(_, v1), (_, v2)| v2.cmp(v1));
         }
-        while let Ok(report) = self.report_rx.try_recv() {
-            match report.state {
-                CrawlerState::Complete => {
-                    self.crawled_sites += 1;
-                }
-                CrawlerState::Terminated => {
-                    self.crawlers.remove(&report.port);
-                }
-                _ => {
-                    self.crawlers.insert(report.port, (0, report.state));
-                }
+        if self.findings_rotated_at.elapsed() >= FINDINGS_ROTATE_INTERVAL {
+            self.findings = self.output.findings.recent();
+            self.findings_offset = self.findings_offset.wrapping_add(self.findings_rows);
+            self.findings_rotated_at = Instant::now();
+        } else if self.findings.is_empty() {
+            self.findings = self.output.findings.recent();
+        }
+        while let Ok(line) = self.log_rx.try_recv() {
+            if self.logs.len() == MAX_LOG_LINES {
+                self.logs.pop_front();
+            }
+            self.logs.push_back(line);
+        }
+        if let Some(pids) = &self.driver_pids {
+            if self
+                .sampled_at
+                .is_none_or(|t| t.elapsed() >= RESOURCE_SAMPLE_INTERVAL)
+            {
+                self.usage = self.sampler.sample(&pids.get());
+                self.sampled_at = Some(Instant::now());
             }
         }
+
+        let dashboard = self.dashboard.get();
+        let completed = dashboard.crawled().saturating_sub(self.crawled_sites);
+        self.rate.record(completed as u64);
+        self.crawled_sites = dashboard.crawled();
+        self.respawns = dashboard.respawns;
+        self.given_up = dashboard.given_up;
+        self.utilization = dashboard.utilization;
+        self.queue = dashboard.queue;
+        self.crawlers = dashboard
+            .crawlers
+            .into_iter()
+            .map(|(port, state)| {
+                // spinners start over whenever their crawler moves on
+                let spinner = match self.crawlers.get(&port) {
+                    Some((spinner, old)) if *old == state => *spinner,
+                    _ => 0,
+                };
+                (port, (spinner, state))
+            })
+            .collect();
     }
 
     fn ui(&mut self) -> impl FnOnce(&mut Frame<'_, Backend>) + '_ {
+        let palette = self.palette;
+        let usage = &self.usage;
         let status: Vec<_> = self
             .crawlers
             .iter_mut()
@@ -189,108 +711,522 @@ impl App {
                     *spinner = 0;
                     "⣿"
                 };
-                let spinner = Span::styled(spinner, Style::default().fg(v.spinner_color()));
+                let spinner = Span::styled(spinner, Style::default().fg(palette.spinner(v)));
+
+                let usage = usage.get(k).map_or_else(String::new, |u| {
+                    format!("{:>6} {:>4.0}% ", format_bytes(u.memory), u.cpu)
+                });
 
                 Spans::from(vec![
                     Span::from(" "),
-                    Span::from(k.to_string()),
+                    // ports the OS picks are longer than the usual ones
+                    Span::from(format!("{k:>5}")),
                     Span::from(" "),
                     spinner,
                     Span::from(" "),
+                    Span::styled(usage, Style::default().fg(palette.muted)),
                     Span::from(v.to_string()),
                 ])
             })
             .collect();
 
         |f| {
-            let layout = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Max(40), Constraint::Percentage(70)])
-                .split(f.size());
-            let left = Layout::default()
+            let split = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(70), Constraint::Min(5)])
-                .split(layout[0]);
-
-            let chart = BarChart::new(&self.freq)
-                .block(Block::default().title(" Histogram ").borders(Borders::ALL))
-                .bar_width(10)
-                .bar_gap(1);
-            f.render_widget(chart, layout[1]);
+                .constraints([Constraint::Length(1), Constraint::Min(0)])
+                .split(f.size());
+            self.draw_tabs(f, split[0]);
 
-            {
-                let block = Block::default()
-                    .title(" Active Crawlers ")
-                    .borders(Borders::ALL);
+            let main = if self.show_logs && self.tab != Tab::Logs {
                 let split = Layout::default()
                     .direction(Direction::Vertical)
-                    .constraints([Constraint::Percentage(70), Constraint::Max(1)])
-                    .split(block.inner(left[0]));
-                let status = Paragraph::new(status);
-                f.render_widget(block, left[0]);
-                f.render_widget(status, split[0]);
-
-                let ratio = self.crawled_sites as f64 / self.total_sites as f64;
-                f.render_widget(
-                    Gauge::default()
-                        .gauge_style(Style::default().fg(Color::LightGreen))
-                        .label(format!(
-                            "{:.1}% ({}/{})",
-                            ratio * 100.0,
-                            self.crawled_sites,
-                            self.total_sites
-                        ))
-                        .ratio(ratio),
-                    split[1],
-                );
+                    .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+                    .split(split[1]);
+                let title = format!(" Logs ({}: hide) ", self.keymap.key(Action::ToggleLogs));
+                self.draw_logs(f, split[1], &title, false);
+                split[0]
+            } else {
+                split[1]
+            };
+
+            match self.tab {
+                Tab::Overview => self.draw_overview(f, main, status),
+                Tab::Crawlers => self.draw_crawlers(f, main, status),
+                Tab::Histogram => self.draw_histogram(f, main),
+                Tab::Errors => self.draw_logs(f, main, " Warnings and Errors ", true),
+                Tab::Logs => self.draw_logs(f, main, " Logs ", false),
             }
-            {
-                let block = Block::default().borders(Borders::ALL);
-                let split = Layout::default()
+        }
+    }
+
+    fn draw_tabs(&self, f: &mut Frame<'_, Backend>, area: Rect) {
+        let titles = Tab::iter()
+            .enumerate()
+            .map(|(i, tab)| Spans::from(format!("{} {tab}", i + 1)))
+            .collect();
+        let tabs = Tabs::new(titles)
+            .select(self.tab as usize)
+            .highlight_style(Style::default().fg(self.palette.accent));
+        f.render_widget(tabs, area);
+
+        if let Some(run) = self.output.run {
+            // styling the paragraph rather than the text would restyle the tabs underneath too
+            let run = Span::styled(
+                format!("run {run} "),
+                Style::default().fg(self.palette.muted),
+            );
+            let run = Paragraph::new(Spans::from(run)).alignment(Alignment::Right);
+            f.render_widget(run, area);
+        }
+    }
+
+    fn draw_overview(&mut self, f: &mut Frame<'_, Backend>, area: Rect, status: Vec<Spans<'_>>) {
+        match OverviewLayout::for_area(area) {
+            OverviewLayout::Wide => {
+                let layout = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Max(40), Constraint::Percentage(70)])
+                    .split(area);
+                let left = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(70), Constraint::Min(5)])
+                    .split(layout[0]);
+                let right = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(5)])
+                    .split(layout[1]);
+
+                self.draw_histogram(f, right[0]);
+                self.draw_findings(f, right[1]);
+                self.draw_crawlers(f, left[0], status);
+                self.draw_status(f, left[1]);
+            }
+            OverviewLayout::Stacked => {
+                let layout = Layout::default()
                     .direction(Direction::Vertical)
                     .constraints([
-                        Constraint::Percentage(40),
-                        Constraint::Min(3),
-                        Constraint::Percentage(40),
+                        Constraint::Percentage(35),
+                        Constraint::Length(5),
+                        Constraint::Length(5),
+                        Constraint::Min(0),
                     ])
-                    .split(block.inner(left[1]));
-
-                let status = match self.state {
-                    AppState::Running => Paragraph::new(vec![
-                        Spans::from(""),
-                        Spans::from(" quotelementa v0.1.0 "),
-                        Spans::from(""),
-                    ]),
-                    AppState::ShuttingDown => {
-                        Paragraph::new(vec![Spans::from(" Press CTRL+C again to force quit. ")])
-                    }
-                    AppState::Done => Paragraph::new(vec![
-                        Spans::from(" Everything done! "),
-                        Spans::from(""),
-                        Spans::from(" Press <ENTER> to exit "),
+                    .split(area);
+
+                self.draw_crawlers(f, layout[0], status);
+                self.draw_status(f, layout[1]);
+                self.draw_findings(f, layout[2]);
+                self.draw_histogram(f, layout[3]);
+            }
+            OverviewLayout::Compact => {
+                let layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(5)])
+                    .split(area);
+
+                self.draw_crawlers(f, layout[0], status);
+                self.draw_status(f, layout[1]);
+            }
+        }
+    }
+
+    fn draw_histogram(&self, f: &mut Frame<'_, Backend>, area: Rect) {
+        let vocabulary = &self.output.vocabulary;
+        let category = self
+            .category_filter
+            .and_then(|i| vocabulary.groups().get(i))
+            .filter(|_| !self.show_categories);
+
+        let mut data: Vec<_> = if self.show_categories {
+            &self.categories
+        } else {
+            &self.freq
+        }
+        .iter()
+        .filter(|(name, _)| {
+            category.is_none_or(|(_, tags)| tags.iter().any(|&t| vocabulary.name(t) == name))
+        })
+        .filter(|(name, _)| name.contains(&self.name_filter))
+        .cloned()
+        .collect();
+        self.sort_order.sort(&mut data);
+
+        let mut title = if self.show_categories {
+            " Categories".to_owned()
+        } else {
+            " Histogram".to_owned()
+        };
+        let _ = write!(title, ", {}", self.sort_order);
+        if self.baseline.is_some() {
+            title.push_str(", + since baseline");
+        }
+        if let Some((group, _)) = category {
+            let _ = write!(title, ", in {group}");
+        }
+        if self.editing_filter || !self.name_filter.is_empty() {
+            let _ = write!(title, ", /{}", self.name_filter);
+            if self.editing_filter {
+                title.push('▏');
+            }
+        }
+        let keys = &self.keymap;
+        let _ = write!(
+            title,
+            " ({}: categories, {}: sort, {}: category, {}: filter) ",
+            keys.key(Action::ToggleCategories),
+            keys.key(Action::CycleSort),
+            keys.key(Action::CycleCategory),
+            keys.key(Action::Filter),
+        );
+
+        // the part of each bar that was there to begin with
+        let baseline: Option<Vec<_>> = self.baseline.as_ref().map(|baseline| {
+            let counts = if self.show_categories {
+                &baseline.categories
+            } else {
+                &baseline.freq
+            };
+            data.iter()
+                .map(|(name, _)| counts.get(name).copied().unwrap_or_default())
+                .collect()
+        });
+
+        let mut chart = BarChart::new(&data)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .bar_width(10)
+            .bar_gap(1);
+        if let Some(baseline) = &baseline {
+            chart = chart
+                .baseline(baseline)
+                .baseline_style(Style::default().fg(self.palette.muted));
+        }
+        f.render_widget(chart, area);
+    }
+
+    fn draw_findings(&mut self, f: &mut Frame<'_, Backend>, area: Rect) {
+        let block = Block::default().title(" Findings ").borders(Borders::ALL);
+        let rows = usize::from(block.inner(area).height);
+        self.findings_rows = rows;
+
+        let count = self.findings.len();
+        let lines: Vec<_> = if count == 0 {
+            vec![Spans::from(Span::styled(
+                " Nothing remarkable yet ",
+                Style::default().fg(self.palette.muted),
+            ))]
+        } else {
+            // newest first, so that rotating goes back in time
+            (0..rows.min(count))
+                .map(|i| &self.findings[count - 1 - (self.findings_offset + i) % count])
+                .map(|finding| {
+                    Spans::from(vec![
+                        Span::styled(
+                            format!(" {} ", finding.url),
+                            Style::default().fg(self.palette.accent),
+                        ),
+                        Span::from(finding.message.as_str()),
                     ])
-                    .style(Style::default().fg(Color::LightYellow)),
+                })
+                .collect()
+        };
+
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_crawlers(&mut self, f: &mut Frame<'_, Backend>, area: Rect, status: Vec<Spans<'_>>) {
+        let block = Block::default().borders(Borders::ALL);
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(70),
+                Constraint::Max(1),
+                Constraint::Max(1),
+                Constraint::Max(1),
+            ])
+            .split(block.inner(area));
+
+        // the scroll position is only clamped here, where we know how much fits
+        let rows = usize::from(split[0].height);
+        let selecting = self.tab == Tab::Crawlers;
+        if selecting {
+            self.crawler_selected = self.crawler_selected.min(status.len().saturating_sub(1));
+            // keep the selection in view
+            self.crawler_scroll = self.crawler_scroll.clamp(
+                (self.crawler_selected + 1).saturating_sub(rows),
+                self.crawler_selected,
+            );
+        }
+        let scroll = self.crawler_scroll.min(status.len().saturating_sub(rows));
+        let (above, below) = (scroll, status.len().saturating_sub(scroll + rows));
+        self.crawler_scroll = scroll;
+        self.crawler_rows = rows;
+
+        let hint = if selecting {
+            format!(" ({}: skip site)", self.keymap.key(Action::Skip))
+        } else {
+            String::new()
+        };
+        let title = match (above, below) {
+            (0, 0) => format!(" Active Crawlers{hint} "),
+            (above, below) => format!(" Active Crawlers (↑{above} ↓{below}){hint} "),
+        };
+        let block = block.title(title);
+        let status: Vec<_> = status
+            .into_iter()
+            .enumerate()
+            .skip(scroll)
+            .map(|(i, mut line)| {
+                if selecting && i == self.crawler_selected {
+                    for span in &mut line.0 {
+                        span.style = span.style.add_modifier(Modifier::REVERSED);
+                    }
+                }
+                line
+            })
+            .collect();
+        let status = Paragraph::new(status);
+        f.render_widget(block, area);
+        f.render_widget(status, split[0]);
+        f.render_widget(
+            Paragraph::new(self.utilization_line()).style(Style::default().fg(self.palette.muted)),
+            split[1],
+        );
+        f.render_widget(
+            Paragraph::new(self.bandwidth_line()).style(Style::default().fg(self.palette.muted)),
+            split[2],
+        );
+
+        let progress = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(split[3]);
+
+        let ratio = self.crawled_sites as f64 / self.total_sites as f64;
+        let remaining = self.total_sites.saturating_sub(self.crawled_sites);
+        let eta = match self.rate.eta(remaining) {
+            _ if remaining == 0 => String::new(),
+            Some(eta) => format!(" ETA {}", format_duration(eta)),
+            None => " ETA ?".to_owned(),
+        };
+        f.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(self.palette.progress))
+                .label(format!(
+                    "{:.1}% ({}/{}){eta}",
+                    ratio * 100.0,
+                    self.crawled_sites,
+                    self.total_sites
+                ))
+                .ratio(ratio.min(1.0)),
+            progress[0],
+        );
+
+        let history = self.rate.per_minute(usize::from(progress[1].width));
+        f.render_widget(
+            Sparkline::default()
+                .data(&history)
+                .style(Style::default().fg(self.palette.rate)),
+            progress[1],
+        );
+    }
+
+    /// How full the queue is, and where the crawlers' time went, e.g. to tell whether
+    /// they're waiting on the assigner, the network or the analyzers. The rest of the
+    /// time is left out, for the line to fit next to the histogram.
+    fn utilization_line(&self) -> String {
+        let mut line = String::new();
+        if let Some(QueueDepth { waiting, capacity }) = self.queue {
+            let _ = write!(line, " queue {waiting}/{capacity} ");
+        }
+        let time = &self.utilization;
+        if !time.total().is_zero() {
+            let share = |part| time.share(part) * 100.0;
+            let _ = write!(
+                line,
+                " idle {:.0}% net {:.0}% DOM {:.0}%",
+                share(time.waiting),
+                share(time.navigating),
+                share(time.walking)
+            );
+        }
+        line
+    }
+
+    /// How fast the sites are transferring right now, and how much they have so far, once
+    /// any of them told.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn bandwidth_line(&self) -> String {
+        let bandwidth = &self.output.bandwidth;
+        let transferred = bandwidth.summary();
+        if transferred.sites == 0 {
+            return String::new();
+        }
+        format!(
+            " {}/s, {} transferred",
+            format_bytes(bandwidth.rate() as u64),
+            format_bytes(transferred.bytes)
+        )
+    }
+
+    fn draw_logs(&self, f: &mut Frame<'_, Backend>, area: Rect, title: &str, errors_only: bool) {
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let rows = usize::from(block.inner(area).height);
+
+        let logs: Vec<_> = self
+            .logs
+            .iter()
+            .filter(|line| !errors_only || line.level <= Level::WARN)
+            .collect();
+        let lines: Vec<_> = logs
+            .iter()
+            .skip(logs.len().saturating_sub(rows))
+            .map(|line| {
+                let color = match line.level {
+                    Level::ERROR => self.palette.error,
+                    Level::WARN => self.palette.warning,
+                    _ => self.palette.normal,
                 };
-                let status = status
-                    .wrap(Wrap { trim: false })
-                    .alignment(ratatui::layout::Alignment::Center);
+                Spans::from(vec![
+                    Span::styled(format!(" {:>5} ", line.level), Style::default().fg(color)),
+                    Span::styled(
+                        format!("{}: ", line.target),
+                        Style::default().fg(self.palette.muted),
+                    ),
+                    Span::from(line.message.as_str()),
+                ])
+            })
+            .collect();
 
-                f.render_widget(block, left[1]);
-                f.render_widget(status, split[1]);
-            }
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_status(&self, f: &mut Frame<'_, Backend>, area: Rect) {
+        let block = Block::default().borders(Borders::ALL);
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(40),
+                Constraint::Min(3),
+                Constraint::Percentage(40),
+            ])
+            .split(block.inner(area));
+
+        let palette = &self.palette;
+        let outcomes = &self.output.outcomes;
+        let counter = |name: &'static str, n: u64, color: Color| {
+            let style = if n == 0 {
+                Style::default().fg(palette.muted)
+            } else {
+                Style::default().fg(color)
+            };
+            Span::styled(format!(" {n} {name} "), style)
+        };
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut counters = vec![
+            counter("failed", load(&outcomes.failed), palette.error),
+            counter("retried", load(&outcomes.retried), palette.warning),
+            counter("skipped", load(&outcomes.skipped), palette.warning),
+            counter("excluded", load(&outcomes.excluded), palette.normal),
+        ];
+        // only with --respect-robots-txt
+        let robots = load(&outcomes.robots);
+        if robots > 0 {
+            counters.push(counter("robots.txt", robots, palette.normal));
+        }
+        // only with --dedup
+        let duplicates = load(&outcomes.duplicates);
+        if duplicates > 0 {
+            counters.push(counter("duplicates", duplicates, palette.normal));
         }
+        // crawlers failing to start is rare enough not to be worth the space otherwise
+        if self.respawns > 0 || self.given_up > 0 {
+            counters.push(counter("respawned", self.respawns as u64, palette.warning));
+            counters.push(counter("gave up", self.given_up as u64, palette.error));
+        }
+        let counters = Spans::from(counters);
+
+        let status = match self.state {
+            AppState::Running if self.is_quit_pending() => Paragraph::new(vec![
+                Spans::from(format!(
+                    " Press {} again to stop. ",
+                    self.keymap.key(Action::Quit)
+                )),
+                Spans::from(format!(
+                    " {} sites in progress will be abandoned. ",
+                    self.in_flight_sites()
+                )),
+            ])
+            .style(Style::default().fg(palette.warning)),
+            AppState::Running => Paragraph::new(vec![
+                if self.is_paused() {
+                    Spans::from(Span::styled(
+                        " Paused ",
+                        Style::default().fg(palette.warning),
+                    ))
+                } else {
+                    Spans::from(" quotelementa v0.1.0 ")
+                },
+                counters,
+                Spans::from(Span::styled(
+                    format!(
+                        " {}: show errors, {}: export, {}: {} ",
+                        self.keymap.key(Action::ShowErrors),
+                        self.keymap.key(Action::Export),
+                        self.keymap.key(Action::Pause),
+                        if self.is_paused() { "resume" } else { "pause" }
+                    ),
+                    Style::default().fg(palette.muted),
+                )),
+            ]),
+            AppState::ShuttingDown => Paragraph::new(vec![Spans::from(format!(
+                " Press {} again to force quit. ",
+                self.keymap.key(Action::Quit)
+            ))]),
+            AppState::Done => Paragraph::new(vec![
+                Spans::from(" Everything done! "),
+                Spans::from(""),
+                Spans::from(format!(" Press {} to exit ", self.keymap.key(Action::Exit))),
+            ])
+            .style(Style::default().fg(palette.accent)),
+        };
+        let status = status
+            .wrap(Wrap { trim: false })
+            .alignment(ratatui::layout::Alignment::Center);
+
+        f.render_widget(block, area);
+        f.render_widget(status, split[1]);
     }
 }
 
-impl CrawlerState {
-    pub fn spinner_color(&self) -> Color {
-        match self {
-            Self::Initializing => Color::Yellow,
-            Self::InProgress(_) => Color::LightGreen,
-            Self::ShuttingDown => Color::LightRed,
-            _ => Color::DarkGray,
-        }
+/// Formats a duration as e.g. `1h02m` or `3m07s`.
+pub(crate) fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs / 60 % 60)
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Formats a number of bytes as e.g. `512K` or `1.2G`.
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
     }
+    if value < 10.0 {
+        format!("{value:.1}{}", UNITS[unit])
+    } else {
+        format!("{value:.0}{}", UNITS[unit])
+    }
+}
+
+impl CrawlerState {
+    #[must_use]
     pub fn should_spinner_spin(&self) -> bool {
         matches!(
             self,
@@ -298,3 +1234,10 @@ impl CrawlerState {
         )
     }
 }
+
+/// Whether the key was pressed on its own, or with just Shift, rather than as a shortcut
+/// like Ctrl-C.
+fn is_plain(key: &KeyEvent) -> bool {
+    !key.modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+}
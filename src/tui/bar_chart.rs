@@ -30,6 +30,11 @@ pub struct BarChart<'a, I, S> {
     /// Style for the widget
     style: Style,
     data: I,
+    /// What each bar started out from, drawn in its own style, with the difference printed
+    /// above the value
+    baseline: Option<&'a [u64]>,
+    /// Style of the part of the bars up to the baseline
+    baseline_style: Style,
     /// Value necessary for a bar to reach the maximum height (if no value is specified,
     /// the maximum value in the data is taken as reference)
     max: Option<u64>,
@@ -50,6 +55,8 @@ impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> BarChart<'a,
             label_style: Style::default(),
             style: Style::default(),
             data,
+            baseline: None,
+            baseline_style: Style::default(),
             max: None,
             _phan: PhantomData,
         }
@@ -103,10 +110,21 @@ impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> BarChart<'a,
         self.style = style;
         self
     }
+
+    /// What each bar started out from, in the same order as the data.
+    pub fn baseline(mut self, baseline: &'a [u64]) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    pub fn baseline_style(mut self, style: Style) -> Self {
+        self.baseline_style = style;
+        self
+    }
 }
 
 impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> Widget for BarChart<'a, I, S> {
-    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
     fn render(mut self, area: Rect, buf: &mut Buffer) {
         buf.set_style(area, self.style);
 
@@ -142,9 +160,13 @@ impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> Widget for Ba
         data.truncate(max_index);
 
         for (i, (label, value)) in data.iter_mut().enumerate() {
-            let mut cnt = *value * u64::from(chart_area.height - 1) * 8 / max.max(1);
+            let eighths = |value: u64| value * u64::from(chart_area.height - 1) * 8 / max.max(1);
+            let mut cnt = eighths(*value);
+            let base = self.baseline.and_then(|b| b.get(i)).copied();
+            // whole cells up to the baseline, with the one it ends in counted as above it
+            let base_cells = base.map_or(0, |base| eighths(base.min(*value)) / 8);
 
-            for j in (0..chart_area.height - 1).rev() {
+            for (k, j) in (0..chart_area.height - 1).rev().enumerate() {
                 let symbol = match cnt {
                     0 => self.bar_set.empty,
                     1 => self.bar_set.one_eighth,
@@ -163,7 +185,11 @@ impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> Widget for Ba
                         chart_area.top() + j,
                     )
                     .set_symbol(symbol)
-                    .set_style(self.bar_style);
+                    .set_style(if (k as u64) < base_cells {
+                        self.baseline_style
+                    } else {
+                        self.bar_style
+                    });
                 }
 
                 if cnt > 8 {
@@ -175,10 +201,22 @@ impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> Widget for Ba
 
             let label = label.as_ref();
 
-            let value_label = match NumberPrefix::decimal(*value as f64) {
-                NumberPrefix::Standalone(n) => format!("{n}"),
-                NumberPrefix::Prefixed(prefix, n) => format!("{n:.1}{prefix}"),
-            };
+            // the empty bars are many, and nothing was added to them anyway
+            if let (Some(base), true) = (base, *value > 0 && chart_area.height >= 4) {
+                let delta_label = format!("+{}", format_count(value.saturating_sub(base)));
+                let width = delta_label.width() as u16;
+                if width < self.bar_width {
+                    buf.set_string(
+                        chart_area.left()
+                            + i as u16 * (self.bar_width + self.bar_gap)
+                            + (self.bar_width - width) / 2,
+                        chart_area.bottom() - 3,
+                        delta_label,
+                        self.value_style,
+                    );
+                }
+            }
+            let value_label = format_count(*value);
             let width = value_label.width() as u16;
             if width < self.bar_width {
                 buf.set_string(
@@ -202,3 +240,12 @@ impl<'a, S: AsRef<str> + 'a, I: IntoIterator<Item = &'a (S, u64)>> Widget for Ba
         }
     }
 }
+
+/// Formats a count as e.g. `512` or `1.2k`.
+#[allow(clippy::cast_precision_loss)]
+fn format_count(count: u64) -> String {
+    match NumberPrefix::decimal(count as f64) {
+        NumberPrefix::Standalone(n) => format!("{n}"),
+        NumberPrefix::Prefixed(prefix, n) => format!("{n:.1}{prefix}"),
+    }
+}
@@ -0,0 +1,211 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use eyre::{bail, eyre, Result};
+use serde::Deserialize;
+use strum::{EnumIter, IntoEnumIterator};
+
+/// Everything the TUI can be told to do from the keyboard.
+#[derive(Deserialize, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Shut down, asking for confirmation first while crawling
+    Quit,
+    /// Leave the UI once everything's done
+    Exit,
+    NextTab,
+    PreviousTab,
+    ShowErrors,
+    ToggleLogs,
+    ToggleCategories,
+    CycleSort,
+    CycleCategory,
+    Filter,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    AddWorker,
+    RetireWorker,
+    /// Have the crawlers hold off on new sites, or carry on if they already were
+    Pause,
+    Skip,
+    /// Save what's on screen to a file
+    Export,
+}
+impl Action {
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Self::Quit => &["ctrl-c"],
+            Self::Exit => &["enter"],
+            Self::NextTab => &["tab"],
+            Self::PreviousTab => &["backtab"],
+            Self::ShowErrors => &["e"],
+            Self::ToggleLogs => &["l"],
+            Self::ToggleCategories => &["g"],
+            Self::CycleSort => &["o"],
+            Self::CycleCategory => &["c"],
+            Self::Filter => &["/"],
+            Self::Up => &["up"],
+            Self::Down => &["down"],
+            Self::PageUp => &["pageup"],
+            Self::PageDown => &["pagedown"],
+            Self::AddWorker => &["+", "="],
+            Self::RetireWorker => &["-"],
+            Self::Pause => &["p"],
+            Self::Skip => &["s"],
+            Self::Export => &["x"],
+        }
+    }
+}
+
+/// A key together with the modifiers held down with it, written like `ctrl-c`, `q` or `pageup`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+impl KeyBinding {
+    fn from_event(key: &KeyEvent) -> Self {
+        // shift is already part of which character was typed, and of `BackTab`
+        Self {
+            code: key.code,
+            modifiers: key.modifiers - KeyModifiers::SHIFT,
+        }
+    }
+}
+impl FromStr for KeyBinding {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = s;
+        // `-` on its own, or as in `ctrl--`, is a key rather than a separator
+        while let Some((modifier, rest)) = key.split_once('-').filter(|(_, rest)| !rest.is_empty())
+        {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(eyre!("Unknown modifier {modifier:?} in key {s:?}")),
+            };
+            key = rest;
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "backspace" => KeyCode::Backspace,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(eyre!("Unknown key {s:?}")),
+                },
+            },
+        };
+
+        Ok(Self {
+            code,
+            modifiers: modifiers - KeyModifiers::SHIFT,
+        })
+    }
+}
+impl TryFrom<String> for KeyBinding {
+    type Error = eyre::Report;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::BackTab => write!(f, "shift-tab"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            code => write!(f, "{}", format!("{code:?}").to_lowercase()),
+        }
+    }
+}
+
+/// Which keys do what in the TUI.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    actions: HashMap<KeyBinding, Action>,
+    keys: HashMap<Action, Vec<KeyBinding>>,
+}
+impl Keymap {
+    /// The default keys, except for the actions given other keys, unless that leaves a key
+    /// bound to two actions.
+    pub fn new(overrides: &HashMap<Action, Vec<KeyBinding>>) -> Result<Self> {
+        let keys: HashMap<_, _> = Action::iter()
+            .map(|action| {
+                let keys = overrides.get(&action).cloned().unwrap_or_else(|| {
+                    action
+                        .default_keys()
+                        .iter()
+                        .map(|k| k.parse().expect("default keys should be valid"))
+                        .collect()
+                });
+                (action, keys)
+            })
+            .collect();
+        let mut actions = HashMap::new();
+        // in a fixed order, so that the same action is named first every time
+        for action in Action::iter() {
+            for &key in &keys[&action] {
+                match actions.insert(key, action) {
+                    Some(other) if other != action => {
+                        bail!("Key {key} is bound to both {other:?} and {action:?}")
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Self { actions, keys })
+    }
+
+    #[must_use]
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.actions.get(&KeyBinding::from_event(key)).copied()
+    }
+
+    /// The first key bound to the action, for showing in hints.
+    #[must_use]
+    pub fn key(&self, action: Action) -> String {
+        self.keys
+            .get(&action)
+            .and_then(|keys| keys.first())
+            .map_or_else(|| "unbound".to_owned(), ToString::to_string)
+    }
+}
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(&HashMap::new()).expect("default keys shouldn't conflict")
+    }
+}
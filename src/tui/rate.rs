@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+/// How long each bucket of the rate history spans.
+const BUCKET: Duration = Duration::from_secs(10);
+/// How many of the most recent buckets the ETA is based on.
+const ETA_WINDOW: usize = 30;
+
+/// How many sites were crawled when, for telling how fast the crawl is going.
+#[derive(Debug)]
+pub struct Rate {
+    started: Instant,
+    /// Sites completed in each [`BUCKET`] since the start
+    buckets: Vec<u64>,
+}
+impl Rate {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            buckets: vec![0],
+        }
+    }
+
+    /// Index of the bucket we're currently in, adding empty buckets for any idle time.
+    #[allow(clippy::cast_possible_truncation)]
+    fn current(&mut self) -> usize {
+        let current = (self.started.elapsed().as_secs() / BUCKET.as_secs()) as usize;
+        if self.buckets.len() <= current {
+            self.buckets.resize(current + 1, 0);
+        }
+        current
+    }
+
+    pub fn record(&mut self, sites: u64) {
+        let current = self.current();
+        self.buckets[current] += sites;
+    }
+
+    /// Sites per minute over the last `n` complete buckets, oldest first.
+    pub fn per_minute(&mut self, n: usize) -> Vec<u64> {
+        let current = self.current();
+        let per_minute = 60 / BUCKET.as_secs();
+        self.buckets[current.saturating_sub(n)..current]
+            .iter()
+            .map(|v| v * per_minute)
+            .collect()
+    }
+
+    /// The estimated time left for the `remaining` sites, at the recent pace.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn eta(&mut self, remaining: usize) -> Option<Duration> {
+        let current = self.current();
+        let window = &self.buckets[current.saturating_sub(ETA_WINDOW)..current];
+        let done: u64 = window.iter().sum();
+        if done == 0 {
+            return None;
+        }
+        let per_sec = done as f64 / (window.len() as f64 * BUCKET.as_secs_f64());
+        Some(Duration::from_secs_f64(remaining as f64 / per_sec))
+    }
+}
@@ -0,0 +1,115 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+use strum::{Display, EnumString};
+
+use crate::crawler::CrawlerState;
+
+/// The sets of colors the UI can be drawn in.
+#[derive(EnumString, Display, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// The terminal's own palette
+    #[default]
+    Default,
+    /// Fixed colors for dark backgrounds, regardless of the terminal's palette
+    Dark,
+    /// Darker colors that stay readable on light backgrounds
+    Light,
+    /// The Okabe–Ito palette, which avoids telling things apart by red and green alone
+    Colorblind,
+}
+impl Theme {
+    #[must_use]
+    pub fn palette(self) -> Palette {
+        match self {
+            Self::Default => Palette {
+                accent: Color::LightYellow,
+                progress: Color::LightGreen,
+                rate: Color::LightBlue,
+                error: Color::LightRed,
+                warning: Color::LightYellow,
+                normal: Color::Gray,
+                muted: Color::DarkGray,
+                initializing: Color::Yellow,
+                connected: Color::LightBlue,
+                in_progress: Color::LightGreen,
+                shutting_down: Color::LightRed,
+            },
+            Self::Dark => Palette {
+                accent: Color::Rgb(250, 210, 90),
+                progress: Color::Rgb(120, 210, 120),
+                rate: Color::Rgb(110, 170, 250),
+                error: Color::Rgb(250, 110, 100),
+                warning: Color::Rgb(250, 210, 90),
+                normal: Color::Rgb(200, 200, 200),
+                muted: Color::Rgb(110, 110, 110),
+                initializing: Color::Rgb(230, 190, 60),
+                connected: Color::Rgb(110, 170, 250),
+                in_progress: Color::Rgb(120, 210, 120),
+                shutting_down: Color::Rgb(250, 110, 100),
+            },
+            Self::Light => Palette {
+                accent: Color::Magenta,
+                progress: Color::Green,
+                rate: Color::Blue,
+                error: Color::Red,
+                warning: Color::Rgb(170, 100, 0),
+                normal: Color::Black,
+                muted: Color::Gray,
+                initializing: Color::Rgb(170, 100, 0),
+                connected: Color::Blue,
+                in_progress: Color::Green,
+                shutting_down: Color::Red,
+            },
+            Self::Colorblind => Palette {
+                accent: Color::Rgb(240, 228, 66),
+                progress: Color::Rgb(0, 114, 178),
+                rate: Color::Rgb(86, 180, 233),
+                error: Color::Rgb(213, 94, 0),
+                warning: Color::Rgb(230, 159, 0),
+                normal: Color::Gray,
+                muted: Color::DarkGray,
+                initializing: Color::Rgb(230, 159, 0),
+                connected: Color::Rgb(86, 180, 233),
+                in_progress: Color::Rgb(0, 158, 115),
+                shutting_down: Color::Rgb(204, 121, 167),
+            },
+        }
+    }
+}
+
+/// The colors of a [`Theme`], by what they're used for.
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    /// The selected tab and the final status message
+    pub accent: Color,
+    /// The progress gauge
+    pub progress: Color,
+    /// The crawl rate sparkline
+    pub rate: Color,
+    pub error: Color,
+    pub warning: Color,
+    /// Log lines and counters that are neither errors nor warnings
+    pub normal: Color,
+    /// Hints, log targets and counters that are still zero
+    pub muted: Color,
+
+    pub initializing: Color,
+    pub connected: Color,
+    pub in_progress: Color,
+    pub shutting_down: Color,
+}
+impl Palette {
+    /// The color of a crawler's spinner.
+    #[must_use]
+    pub fn spinner(&self, state: &CrawlerState) -> Color {
+        match state {
+            CrawlerState::Initializing => self.initializing,
+            CrawlerState::Connected(_) => self.connected,
+            CrawlerState::InProgress(_) => self.in_progress,
+            CrawlerState::ShuttingDown => self.shutting_down,
+            _ => self.muted,
+        }
+    }
+}
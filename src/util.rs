@@ -1,127 +1,193 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use deadqueue::limited::Queue;
-use strum::{Display, EnumCount, EnumString, FromRepr};
-use tokio::sync::watch;
+use hyper::client::HttpConnector;
+use hyper_tls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString};
+use tokio::{sync::watch, time::Instant};
 use url::Url;
 
+use crate::queue::SiteQueue;
+
 pub type Port = u16;
 pub type ShutdownRx = watch::Receiver<()>;
-pub type JobQueue = Arc<Queue<Url>>;
+pub type JobQueue = Arc<SiteQueue>;
 pub type Capabilities = serde_json::Map<String, serde_json::Value>;
+pub type HttpClient = hyper::Client<HttpsConnector<HttpConnector>>;
+
+pub const USER_AGENT: &str = "Quotelementa-Crawler";
+
+/// Waits until the deadline, if there is one, or forever otherwise.
+pub async fn until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Parses a duration like `90s`, `45m`, `2h` or `1d`, or a plain number of seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (n, unit) = s
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((s, ""), |i| s.split_at(i));
+    let n: u64 = n
+        .parse()
+        .map_err(|_| format!("{s:?} isn't a duration like 90s, 45m or 2h"))?;
+    let secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit {unit:?} in duration {s:?}")),
+    };
+    let secs = n
+        .checked_mul(secs)
+        .ok_or_else(|| format!("{s:?} is too long a duration"))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// The instant `duration` from now, or no deadline at all if there's no duration or it's too
+/// far away to be represented.
+#[must_use]
+pub fn deadline_after(duration: Option<Duration>) -> Option<Instant> {
+    duration.and_then(|d| Instant::now().checked_add(d))
+}
+
+/// Parses a size like `512K`, `1.5M` or `2G`, in powers of 1024, or a plain number of bytes.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn parse_bytes(s: &str) -> Result<u64, String> {
+    let (n, unit) = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .map_or((s, ""), |i| s.split_at(i));
+    let n: f64 = n
+        .parse()
+        .map_err(|_| format!("{s:?} isn't a size like 512K, 2M or 1G"))?;
+    let bytes: u64 = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("Unknown unit {unit:?} in size {s:?}")),
+    };
+    Ok((n * bytes as f64) as u64)
+}
+
+/// Whether `host` is `domain` itself or one of its subdomains.
+#[must_use]
+pub fn is_same_or_subdomain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+}
 
-#[derive(EnumString, EnumCount, FromRepr, Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The URL without any username or password in it, fit to be recorded or logged.
+#[must_use]
+pub fn without_credentials(url: &Url) -> Url {
+    let mut url = url.clone();
+    // only fails for URLs that cannot have a host, which have no credentials either
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url
+}
+
+/// The host of the URL, minus any `www.` prefix.
+#[must_use]
+pub fn site_host(url: &Url) -> &str {
+    let host = url.host_str().unwrap_or_default();
+    host.strip_prefix("www.").unwrap_or(host)
+}
+
+#[derive(EnumString, Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Engine {
+    /// Crawl live sites through WebDriver sessions
+    WebDriver,
+    /// Re-analyze DOM snapshots saved by a previous crawl
+    Snapshot,
+}
+
+/// Elements that are obsolete in HTML5, but that browsers still render.
+#[derive(
+    EnumString,
+    EnumIter,
+    Display,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
 #[strum(serialize_all = "snake_case")]
-pub enum Tag {
-    A,
-    Abbr,
-    Address,
-    Area,
-    Article,
-    Aside,
-    Audio,
-    B,
-    Base,
-    Bdi,
-    Bdo,
-    Blockquote,
-    Body,
-    Br,
-    Button,
-    Canvas,
-    Caption,
-    Cite,
-    Code,
-    Col,
-    Colgroup,
-    Data,
-    Datalist,
-    Dd,
-    Del,
-    Details,
-    Dfn,
-    Dialog,
-    Div,
-    Dl,
-    Dt,
-    Em,
-    Embed,
-    Fieldset,
-    Figcaption,
-    Figure,
-    Footer,
-    Form,
-    H1,
-    H2,
-    H3,
-    H4,
-    H5,
-    H6,
-    Head,
-    Header,
-    Hgroup,
-    Hr,
-    Html,
-    I,
-    Iframe,
-    Img,
-    Input,
-    Ins,
-    Kbd,
-    Label,
-    Legend,
-    Li,
-    Link,
-    Main,
-    Map,
-    Mark,
-    Menu,
-    Meta,
-    Meter,
-    Nav,
-    Noscript,
-    Object,
-    Ol,
-    Optgroup,
-    Option,
-    Output,
-    P,
-    Picture,
-    Pre,
-    Progress,
-    Q,
-    Rp,
-    Rt,
-    Ruby,
-    S,
-    Samp,
-    Script,
-    Section,
-    Select,
-    Slot,
-    Small,
-    Source,
-    Span,
-    Strong,
-    Style,
-    Sub,
-    Summary,
-    Sup,
-    Table,
-    Tbody,
-    Td,
-    Template,
-    Textarea,
-    Tfoot,
-    Th,
-    Thead,
-    Time,
-    Title,
-    Tr,
-    Track,
-    U,
-    Ul,
-    Var,
-    Video,
-    Wbr,
+#[serde(rename_all = "snake_case")]
+pub enum ObsoleteTag {
+    Acronym,
+    Applet,
+    Basefont,
+    Bgsound,
+    Big,
+    Blink,
+    Center,
+    Dir,
+    Font,
+    Frame,
+    Frameset,
+    Image,
+    Isindex,
+    Keygen,
+    Listing,
+    Marquee,
+    Menuitem,
+    Multicol,
+    Nextid,
+    Nobr,
+    Noembed,
+    Noframes,
+    Plaintext,
+    Rb,
+    Rtc,
+    Spacer,
+    Strike,
+    Tt,
+    Xmp,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("45m").map(|d| d.as_secs()), Ok(2700));
+        assert_eq!(parse_duration("2h").map(|d| d.as_secs()), Ok(7200));
+        assert_eq!(parse_duration("1d").map(|d| d.as_secs()), Ok(86_400));
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert!(parse_duration("300000000000000d").is_err());
+        assert!(parse_duration("18446744073709551616").is_err());
+        assert_eq!(
+            parse_duration("18446744073709551615"),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn a_deadline_too_far_away_is_no_deadline() {
+        assert_eq!(deadline_after(None), None);
+        assert_eq!(deadline_after(Some(Duration::from_secs(u64::MAX))), None);
+        assert!(deadline_after(Some(Duration::from_secs(30))).is_some());
+    }
 }
@@ -0,0 +1,142 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Arc,
+};
+
+use eyre::{bail, ensure, Context, ContextCompat, Result};
+use serde::Deserialize;
+
+/// The vocabulary used when none is given.
+const BUILTIN: &str = include_str!("tags.toml");
+
+/// A tag name interned in a [`Vocabulary`], and the index of its counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tag(usize);
+impl Tag {
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The shape of a vocabulary file, in either TOML or JSON.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VocabularyFile {
+    tags: Vec<String>,
+    /// Named groups of tags, which are reported as a whole
+    #[serde(default)]
+    groups: BTreeMap<String, Vec<String>>,
+}
+
+/// The set of tags whose occurrences are counted, fixed for the duration of a run.
+#[derive(Clone, Debug)]
+pub struct Vocabulary {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    names: Vec<String>,
+    ids: HashMap<String, Tag>,
+    groups: Vec<(String, Vec<Tag>)>,
+}
+
+impl Vocabulary {
+    /// The tags defined by the HTML living standard.
+    #[must_use]
+    pub fn builtin() -> Self {
+        let file = toml::from_str(BUILTIN).expect("builtin vocabulary should be valid");
+        Self::from_file(file).expect("builtin vocabulary should be valid")
+    }
+
+    /// Loads a vocabulary from a TOML or JSON file, depending on its extension.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .wrap_err("Failed to read vocabulary file")?;
+
+        let file = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text).wrap_err("Invalid vocabulary file")?,
+            Some("toml") => toml::from_str(&text).wrap_err("Invalid vocabulary file")?,
+            _ => bail!("Vocabulary files must be either .toml or .json"),
+        };
+        Self::from_file(file)
+    }
+
+    fn from_file(file: VocabularyFile) -> Result<Self> {
+        let mut names = vec![];
+        let mut ids = HashMap::new();
+        for name in file.tags {
+            let name = name.to_ascii_lowercase();
+            if !ids.contains_key(&name) {
+                ids.insert(name.clone(), Tag(names.len()));
+                names.push(name);
+            }
+        }
+        ensure!(!names.is_empty(), "The vocabulary must contain some tags");
+
+        let groups = file
+            .groups
+            .into_iter()
+            .map(|(group, tags)| {
+                let tags = tags
+                    .iter()
+                    .map(|t| {
+                        ids.get(&t.to_ascii_lowercase())
+                            .copied()
+                            .wrap_err_with(|| format!("Group {group} has unknown tag {t}"))
+                    })
+                    .collect::<Result<_>>()?;
+                Ok((group, tags))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            inner: Arc::new(Inner { names, ids, groups }),
+        })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inner.names.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.inner.names.is_empty()
+    }
+
+    /// Looks up a tag by its (lowercase) name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Tag> {
+        self.inner.ids.get(name).copied()
+    }
+
+    #[must_use]
+    pub fn name(&self, tag: Tag) -> &str {
+        &self.inner.names[tag.0]
+    }
+
+    /// All tag names, in index order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.inner.names.iter().map(String::as_str)
+    }
+
+    /// The named groups of tags, in alphabetical order.
+    #[must_use]
+    pub fn groups(&self) -> &[(String, Vec<Tag>)] {
+        &self.inner.groups
+    }
+
+    /// Sums up per-tag counts, indexed by [`Tag::index`], into per-group totals.
+    pub fn group_totals<'a: 'c, 'c>(
+        &'a self,
+        counts: &'c [u64],
+    ) -> impl Iterator<Item = (&'a str, u64)> + 'c {
+        self.inner.groups.iter().map(|(group, tags)| {
+            let total = tags.iter().map(|t| counts[t.index()]).sum();
+            (group.as_str(), total)
+        })
+    }
+}
@@ -0,0 +1,204 @@
+mod harness;
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use eyre::Result;
+use harness::Harness;
+use quotelementa::{
+    analyzer::{Analyzer, PageContext},
+    config::Config,
+    state::SiteRecord,
+};
+
+const ARTICLE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>Article</title></head>
+<body>
+  <article>
+    <h1>Title</h1>
+    <p>One <em>paragraph</em></p>
+    <p>Another <a href="/">paragraph</a></p>
+  </article>
+</body>
+</html>"#;
+
+const LIST: &str = r#"<!DOCTYPE html>
+<html>
+<body>
+  <ul><li>a</li><li>b</li><li>c</li></ul>
+  <p>After the list</p>
+</body>
+</html>"#;
+
+/// Counts the list items, only adding a page's to the total once it's done.
+struct ListItems {
+    total: Arc<AtomicU64>,
+    page: u64,
+}
+#[async_trait]
+impl Analyzer for ListItems {
+    async fn accept(&mut self, page_ctx: &PageContext<'_>) -> Result<()> {
+        if page_ctx.name == "li" {
+            self.page += 1;
+        }
+        Ok(())
+    }
+    async fn finalize(&mut self, _record: &mut SiteRecord) -> Result<()> {
+        self.total.fetch_add(self.page, Ordering::Relaxed);
+        self.page = 0;
+        Ok(())
+    }
+    fn reset(&mut self) {
+        self.page = 0;
+    }
+}
+
+#[tokio::test]
+async fn counts_the_elements_of_every_site() {
+    let harness = Harness::start(&[("/article", ARTICLE), ("/list", LIST)]);
+    let (output, mut rx) = harness::output().await;
+    let urls = [harness.pages.url("/article"), harness.pages.url("/list")];
+
+    harness.session(&urls, &output).run().await.unwrap();
+
+    let mut records = harness::records(&mut rx);
+    records.sort_by(|a, b| a.url.cmp(&b.url));
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].url, urls[0].as_str());
+    assert_eq!(records[0].elements, 6);
+    assert_eq!(records[0].status, Some(200));
+    assert_eq!(records[1].elements, 5);
+    assert!(records.iter().all(|r| r.error.is_none()));
+    assert!(records
+        .iter()
+        .all(|r| r.timing.navigation_ms.is_some() && r.timing.walk_ms.is_some()));
+
+    assert_eq!(harness::count(&output, "p"), 3);
+    assert_eq!(harness::count(&output, "li"), 3);
+    assert_eq!(harness::count(&output, "article"), 1);
+    assert_eq!(harness::failed(&output), 0);
+}
+
+#[tokio::test]
+async fn records_sites_that_fail_to_load() {
+    let harness = Harness::start(&[("/article", ARTICLE)]);
+    let (output, mut rx) = harness::output().await;
+    // nothing listens on port 1
+    let gone = "http://127.0.0.1:1/".parse().unwrap();
+    let urls = [gone, harness.pages.url("/article")];
+
+    harness.session(&urls, &output).run().await.unwrap();
+
+    let records = harness::records(&mut rx);
+    assert_eq!(records.len(), 2);
+    let failed = records.iter().find(|r| r.url == urls[0].as_str()).unwrap();
+    assert!(failed.error.is_some());
+    assert_eq!(failed.elements, 0);
+    assert_eq!(failed.timing.walk_ms, None);
+    assert_eq!(harness::failed(&output), 1);
+    // the failed site counts for nothing
+    assert_eq!(harness::count(&output, "p"), 2);
+}
+
+#[tokio::test]
+async fn stops_after_the_most_sites_allowed() {
+    let harness = Harness::start(&[("/article", ARTICLE), ("/list", LIST)]);
+    let (output, mut rx) = harness::output().await;
+    let urls = [
+        harness.pages.url("/article"),
+        harness.pages.url("/list"),
+        harness.pages.url("/missing"),
+    ];
+
+    harness
+        .session(&urls, &output)
+        .max_sites(2)
+        .run()
+        .await
+        .unwrap();
+
+    assert_eq!(harness::records(&mut rx).len(), 2);
+    assert_eq!(harness.driver.visited(), urls[..2]);
+}
+
+#[tokio::test]
+async fn crawls_the_site_a_browser_failed_to_restart_for() {
+    let harness = Harness::start(&[("/article", ARTICLE), ("/list", LIST)]);
+    let (output, mut rx) = harness::output().await;
+    let urls = [harness.pages.url("/article"), harness.pages.url("/list")];
+    harness.driver.refuse_restart();
+    let mut config = Config::default();
+    // restart the browser before the second site, and have the spare take over when that fails
+    config.recycle.after_sites = Some(1);
+    config.respawn.warm_spare = true;
+
+    harness
+        .session(&urls, &output)
+        .config(config)
+        .run()
+        .await
+        .unwrap();
+
+    let mut records = harness::records(&mut rx);
+    records.sort_by(|a, b| a.url.cmp(&b.url));
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].url, urls[1].as_str());
+    assert!(records.iter().all(|r| r.error.is_none()));
+    assert_eq!(harness::count(&output, "li"), 3);
+}
+
+/// Fails every page it's asked to finish.
+struct Failing;
+#[async_trait]
+impl Analyzer for Failing {
+    async fn accept(&mut self, _page_ctx: &PageContext<'_>) -> Result<()> {
+        Ok(())
+    }
+    async fn finalize(&mut self, _record: &mut SiteRecord) -> Result<()> {
+        eyre::bail!("out of cheese")
+    }
+    fn reset(&mut self) {}
+}
+
+#[tokio::test]
+async fn keeps_the_record_of_a_page_an_analyzer_failed_to_finish() {
+    let harness = Harness::start(&[("/list", LIST)]);
+    let (output, mut rx) = harness::output().await;
+    let output = output.with_analyzer(|_| Ok(Box::new(Failing)));
+    let urls = [harness.pages.url("/list"), harness.pages.url("/list?again")];
+
+    harness.session(&urls, &output).run().await.unwrap();
+
+    // the pages are in the totals already, so they have to be in the records too
+    let records = harness::records(&mut rx);
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|r| r.error.is_none()));
+    assert_eq!(harness::count(&output, "li"), 6);
+}
+
+#[tokio::test]
+async fn runs_analyzers_from_outside_the_crate() {
+    let harness = Harness::start(&[("/list", LIST)]);
+    let (output, _rx) = harness::output().await;
+    let total = Arc::new(AtomicU64::new(0));
+    let output = output.with_analyzer({
+        let total = total.clone();
+        move |_| {
+            Ok(Box::new(ListItems {
+                total: total.clone(),
+                page: 0,
+            }))
+        }
+    });
+    let urls = [harness.pages.url("/list"), harness.pages.url("/list?again")];
+
+    harness.session(&urls, &output).run().await.unwrap();
+
+    assert_eq!(total.load(Ordering::Relaxed), 6);
+    // the built-in analyzers still run alongside it
+    assert_eq!(harness::count(&output, "li"), 6);
+}
@@ -0,0 +1,132 @@
+//! Crawls synthetic pages from end to end, from the list of sites to the records written out,
+//! with a [mock WebDriver](MockWebDriver) in place of a browser.
+
+mod webdriver;
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, StatusCode,
+};
+use quotelementa::{
+    config::Config,
+    state::{Output, SiteRecord},
+    CrawlSession, CrawlSessionBuilder,
+};
+use tempfile::TempDir;
+use tokio::{sync::mpsc, task::JoinHandle};
+use url::Url;
+
+pub use self::webdriver::MockWebDriver;
+
+/// Serves fixed pages on localhost, with anything else not found, until dropped.
+pub struct PageServer {
+    addr: SocketAddr,
+    server: JoinHandle<hyper::Result<()>>,
+}
+impl PageServer {
+    /// Serves each page's HTML at its path, e.g. `/about`.
+    pub fn start(pages: &[(&str, &str)]) -> Self {
+        let pages: Arc<Vec<(String, String)>> = Arc::new(
+            pages
+                .iter()
+                .map(|(path, html)| ((*path).to_owned(), (*html).to_owned()))
+                .collect(),
+        );
+        let service = make_service_fn(move |_| {
+            let pages = pages.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let pages = pages.clone();
+                    async move { Ok::<_, Infallible>(serve(&pages, &req)) }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(service);
+        Self {
+            addr: server.local_addr(),
+            server: tokio::spawn(server),
+        }
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        Url::parse(&format!("http://{}{path}", self.addr)).unwrap()
+    }
+}
+impl Drop for PageServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn serve(pages: &[(String, String)], req: &Request<Body>) -> Response<Body> {
+    match pages.iter().find(|(path, _)| path == req.uri().path()) {
+        Some((_, html)) => Response::new(Body::from(html.clone())),
+        None => {
+            let mut res = Response::new(Body::from("<html><body><h1>Not found</h1></body></html>"));
+            *res.status_mut() = StatusCode::NOT_FOUND;
+            res
+        }
+    }
+}
+
+/// Synthetic pages, a mock WebDriver to crawl them with, and somewhere to put the list of sites.
+pub struct Harness {
+    pub pages: PageServer,
+    pub driver: MockWebDriver,
+    dir: TempDir,
+}
+impl Harness {
+    pub fn start(pages: &[(&str, &str)]) -> Self {
+        Self {
+            pages: PageServer::start(pages),
+            driver: MockWebDriver::start(),
+            dir: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    /// A session crawling the URLs in order with the mock WebDriver, reporting to `output`.
+    pub fn session(&self, urls: &[Url], output: &Output) -> CrawlSessionBuilder {
+        let list: String = urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| format!("{},{url}\n", i + 1))
+            .collect();
+        let sites = self.dir.path().join("sites.csv");
+        std::fs::write(&sites, list).unwrap();
+
+        CrawlSession::builder()
+            .remote(self.driver.url())
+            .sites(sites)
+            .workers(1)
+            .output(output.clone())
+    }
+}
+
+/// An output counting from zero, and the records it's sent.
+pub async fn output() -> (Output, mpsc::UnboundedReceiver<SiteRecord>) {
+    let (records_tx, records_rx) = mpsc::unbounded_channel();
+    let output = Output::load(records_tx, &Config::default()).await.unwrap();
+    (output, records_rx)
+}
+
+/// The records sent so far.
+pub fn records(rx: &mut mpsc::UnboundedReceiver<SiteRecord>) -> Vec<SiteRecord> {
+    std::iter::from_fn(|| rx.try_recv().ok()).collect()
+}
+
+/// How often the tag was counted.
+pub fn count(output: &Output, tag: &str) -> u64 {
+    let tag = output.vocabulary.get(tag).unwrap();
+    output.freq.get()[tag.index()]
+}
+
+/// How many sites failed.
+pub fn failed(output: &Output) -> u64 {
+    output.outcomes.failed.load(Ordering::Relaxed)
+}
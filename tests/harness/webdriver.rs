@@ -0,0 +1,249 @@
+//! A WebDriver that fetches pages over plain HTTP and parses them, rather than driving a browser.
+//!
+//! It speaks just enough of the protocol for a crawler: sessions, navigation, finding
+//! elements and reading their names, and the handful of scripts whose answers matter.
+//! Every other script returns `null`, which the crawler takes as a measurement that failed.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use hyper::{
+    body,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use scraper::{Html, Selector};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// What WebDriver calls the key of an element reference
+const ELEMENT_KEY: &str = "element-6066-11e4-a52e-4f735466cecf";
+/// The reference of the page's `body`, with the elements in it numbered from 0
+const BODY: &str = "body";
+/// The size of the pretend window, and of every element in it
+const WINDOW: (u32, u32) = (1280, 720);
+
+/// A page loaded in a session.
+#[derive(Clone, Debug, Default)]
+struct Page {
+    url: Option<Url>,
+    status: u16,
+    /// The names of the elements in the body, in document order
+    elements: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Sessions {
+    next_id: usize,
+    pages: HashMap<String, Page>,
+    /// Every URL navigated to, across sessions
+    visited: Vec<Url>,
+    /// Whether to refuse the first session asked for after one has been ended
+    refuse_restart: bool,
+    /// Whether a session was ended while [`refuse_restart`](Self::refuse_restart) was set
+    restarting: bool,
+}
+
+/// A mock WebDriver listening on localhost, until dropped.
+pub struct MockWebDriver {
+    addr: SocketAddr,
+    sessions: Arc<Mutex<Sessions>>,
+    server: JoinHandle<hyper::Result<()>>,
+}
+impl MockWebDriver {
+    pub fn start() -> Self {
+        let sessions = Arc::new(Mutex::new(Sessions::default()));
+        let state = sessions.clone();
+        let service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(&state, req).await) }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(service);
+        Self {
+            addr: server.local_addr(),
+            sessions,
+            server: tokio::spawn(server),
+        }
+    }
+
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).unwrap()
+    }
+
+    /// Refuses the first session asked for after one has been ended, as if the browser
+    /// failed to come back up after a restart.
+    pub fn refuse_restart(&self) {
+        self.sessions.lock().unwrap().refuse_restart = true;
+    }
+
+    /// Every URL navigated to so far, in order.
+    pub fn visited(&self) -> Vec<Url> {
+        self.sessions.lock().unwrap().visited.clone()
+    }
+}
+impl Drop for MockWebDriver {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn handle(sessions: &Mutex<Sessions>, req: Request<Body>) -> Response<Body> {
+    let method = req.method().clone();
+    let path: Vec<String> = req
+        .uri()
+        .path()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let body = body::to_bytes(req.into_body()).await.unwrap_or_default();
+    let args: Value = serde_json::from_slice(&body).unwrap_or_default();
+    let path: Vec<_> = path.iter().map(String::as_str).collect();
+
+    match (&method, path.as_slice()) {
+        (&Method::GET, ["status"]) => reply(json!({ "ready": true, "message": "" })),
+        (&Method::POST, ["session"]) => {
+            let mut sessions = sessions.lock().unwrap();
+            if sessions.restarting {
+                sessions.refuse_restart = false;
+                sessions.restarting = false;
+                return error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "session not created",
+                    "browser failed to start",
+                );
+            }
+            let id = format!("session-{}", sessions.next_id);
+            sessions.next_id += 1;
+            sessions.pages.insert(id.clone(), Page::default());
+            reply(json!({ "sessionId": id, "capabilities": { "browserName": "mock" } }))
+        }
+        (&Method::DELETE, ["session", id]) => {
+            let mut sessions = sessions.lock().unwrap();
+            sessions.pages.remove(*id);
+            sessions.restarting = sessions.refuse_restart;
+            reply(Value::Null)
+        }
+        (_, ["session", id, rest @ ..]) => {
+            let Some(page) = sessions.lock().unwrap().pages.get(*id).cloned() else {
+                return error(
+                    StatusCode::NOT_FOUND,
+                    "invalid session id",
+                    "no such session",
+                );
+            };
+            match (&method, rest) {
+                (&Method::POST, ["url"]) => navigate(sessions, id, &args).await,
+                (&Method::GET, ["url"]) => reply(json!(page
+                    .url
+                    .map_or("about:blank".to_owned(), String::from))),
+                (&Method::GET, ["window", "rect"]) => reply(rect()),
+                (&Method::GET, ["title"]) => reply(json!("")),
+                (&Method::GET, ["alert", "text"]) => {
+                    error(StatusCode::NOT_FOUND, "no such alert", "no dialog open")
+                }
+                (&Method::POST, ["execute", "sync"]) => reply(execute(&page, &args)),
+                (&Method::POST, ["element"]) if args["value"] == BODY => {
+                    reply(json!({ ELEMENT_KEY: BODY }))
+                }
+                (&Method::POST, ["elements"]) => reply(json!([])),
+                (&Method::POST, ["element", BODY, "elements"]) => {
+                    let refs: Vec<_> = (0..page.elements.len())
+                        .map(|i| json!({ ELEMENT_KEY: i.to_string() }))
+                        .collect();
+                    reply(json!(refs))
+                }
+                (&Method::GET, ["element", element, "name"]) => {
+                    match element
+                        .parse()
+                        .ok()
+                        .and_then(|i: usize| page.elements.get(i))
+                    {
+                        Some(name) => reply(json!(name)),
+                        None => error(StatusCode::NOT_FOUND, "no such element", "stale"),
+                    }
+                }
+                (&Method::GET, ["element", _, "rect"]) => reply(rect()),
+                _ => unknown(),
+            }
+        }
+        _ => unknown(),
+    }
+}
+
+/// Fetches the page, and takes note of its elements.
+async fn navigate(sessions: &Mutex<Sessions>, id: &str, args: &Value) -> Response<Body> {
+    let Some(url) = args["url"].as_str().and_then(|u| Url::parse(u).ok()) else {
+        return error(StatusCode::BAD_REQUEST, "invalid argument", "bad URL");
+    };
+    sessions.lock().unwrap().visited.push(url.clone());
+
+    let uri = url.as_str().parse().unwrap();
+    let res = match hyper::Client::new().get(uri).await {
+        Ok(res) => res,
+        // what Chrome says, so that the crawler classifies it the same way
+        Err(e) => {
+            let message = format!("unknown error: net::ERR_CONNECTION_REFUSED ({e})");
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "unknown error", &message);
+        }
+    };
+    let status = res.status().as_u16();
+    let html = body::to_bytes(res.into_body()).await.unwrap_or_default();
+    let html = Html::parse_document(&String::from_utf8_lossy(&html));
+    let selector = Selector::parse("body *").unwrap();
+    let elements = html
+        .select(&selector)
+        .map(|e| e.value().name().to_owned())
+        .collect();
+
+    let page = Page {
+        url: Some(url),
+        status,
+        elements,
+    };
+    sessions.lock().unwrap().pages.insert(id.to_owned(), page);
+    reply(Value::Null)
+}
+
+/// Answers the scripts the crawler can't do without, going by what they look at.
+fn execute(page: &Page, args: &Value) -> Value {
+    let script = args["script"].as_str().unwrap_or_default();
+    if script.contains("navigator.userAgent") {
+        json!(["mock", "1.0"])
+    } else if script.contains("responseStatus") {
+        json!(page.status)
+    } else {
+        Value::Null
+    }
+}
+
+fn rect() -> Value {
+    json!({ "x": 0, "y": 0, "width": WINDOW.0, "height": WINDOW.1 })
+}
+
+fn reply(value: Value) -> Response<Body> {
+    let mut res = Response::new(Body::from(json!({ "value": value }).to_string()));
+    res.headers_mut()
+        .insert("content-type", "application/json".parse().unwrap());
+    res
+}
+
+fn error(status: StatusCode, error: &str, message: &str) -> Response<Body> {
+    let mut res = reply(json!({ "error": error, "message": message, "stacktrace": "" }));
+    *res.status_mut() = status;
+    res
+}
+
+fn unknown() -> Response<Body> {
+    error(StatusCode::NOT_FOUND, "unknown command", "not mocked")
+}